@@ -0,0 +1,39 @@
+use std::rc::Rc;
+
+use rustc_middle::{mir::mono::MonoItem, ty::Instance, ty::TyCtxt};
+
+use crate::{
+    reachability::{Collection, CollectionBudget, Collector, UsageGraph},
+    refiner::{refine_from, RefinedUsageGraph, RefinerConfig},
+};
+
+/// Collects the items reachable from the entry and refines the calls among them. Nothing is
+/// written to disk, so drivers other than ours can embed the analysis.
+pub fn analyze_entry<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    instance: Instance<'tcx>,
+) -> (UsageGraph<'tcx>, RefinedUsageGraph<'tcx>) {
+    let (collection, refined_usage_graph) = analyze_entry_with(
+        tcx,
+        &mut Collector::new(tcx, CollectionBudget::default()),
+        instance,
+        RefinerConfig::default(),
+    );
+    // The collector is gone, so the collection is not shared anymore and is not cloned.
+    let (_, usage_graph) = Rc::unwrap_or_clone(collection);
+    (usage_graph, refined_usage_graph)
+}
+
+/// Same as [`analyze_entry`], but collects through the given collector, which reuses the work
+/// done for the entries it collected before, and refines with the given config. The collection is
+/// shared with the collector rather than copied out of it.
+pub fn analyze_entry_with<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    collector: &mut Collector<'tcx>,
+    instance: Instance<'tcx>,
+    config: RefinerConfig,
+) -> (Rc<Collection<'tcx>>, RefinedUsageGraph<'tcx>) {
+    let collection = collector.collect(MonoItem::Fn(instance));
+    let refined_usage_graph = refine_from(instance, &collection.0, config, tcx);
+    (collection, refined_usage_graph)
+}
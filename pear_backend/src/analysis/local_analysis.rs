@@ -1,3 +1,4 @@
+use std::io;
 use std::marker::Sized;
 use std::path::PathBuf;
 
@@ -15,17 +16,26 @@ pub trait LocalAnalysis<'tcx> {
 
     fn perform_analysis(&self, tcx: TyCtxt<'tcx>, local_def_id: LocalDefId) -> Self::Output;
 
-    /// Try to load previously saved analysis results for a given DefId.
+    /// Try to load previously saved analysis results for a given DefId. Results saved by a
+    /// different version of PEAR or rustc are treated as missing.
     fn load_local_analysis_results(tcx: TyCtxt<'tcx>, def_id: DefId) -> Result<Self::Output, String>
     where
         Self: Sized,
     {
         let paths = local_or_remote_paths(def_id.krate, tcx, INTERMEDIATE_ARTIFACT_EXT);
+        let mut stale = vec![];
         for path in &paths {
             let path = path.join(tcx.def_path(def_id).to_filename_friendly_no_crate());
-            if let Ok(data) = decode_from_file(tcx, path) {
-                return Ok(data);
-            };
+            match decode_from_file(tcx, &path) {
+                Ok(data) => return Ok(data),
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => stale.push(path),
+                Err(..) => {}
+            }
+        }
+        if !stale.is_empty() {
+            return Err(format!(
+                "Facts for {def_id:?} are stale, rebuild to recompute them: {stale:?}"
+            ));
         }
         return Err(format!(
             "No facts for {def_id:?} found at any path tried: {paths:?}"
@@ -80,7 +90,7 @@ impl<'tcx, 'a, A: LocalAnalysis<'tcx>> intravisit::Visitor<'tcx> for DumpingVisi
             std::fs::create_dir(dir).unwrap();
         }
 
-        encode_to_file(self.tcx, path, &to_write);
+        encode_to_file(self.tcx, path, &to_write).expect("failed to write local analysis results");
 
         intravisit::walk_fn(
             self,
@@ -110,27 +120,21 @@ fn local_or_remote_paths(krate: CrateNum, tcx: TyCtxt, ext: &str) -> Vec<PathBuf
 
 /// Create the name of the file in which to store intermediate artifacts.
 ///
-/// HACK(Justus): `TyCtxt::output_filenames` returns a file stem of
-/// `lib<crate_name>-<hash>`, whereas `OutputFiles::with_extension` returns a file
-/// stem of `<crate_name>-<hash>`. I haven't found a clean way to get the same
-/// name in both places, so I just assume that these two will always have this
-/// relation and prepend the `"lib"` here.
+/// Dependents find the artifacts next to the crate metadata through
+/// [`TyCtxt::crate_extern_paths`], so the name mirrors the one rustc gives to the
+/// `rlib`/`rmeta` of the crate: `lib<crate_name><extra_filename>`, regardless of
+/// the crate type and the target. It is derived from the crate name rather than
+/// the output file stem, which does not carry the `lib` prefix for binaries and
+/// may already start with `lib` for crates named that way.
 fn intermediate_out_dir(tcx: TyCtxt, ext: &str) -> PathBuf {
-    let rustc_out_file = tcx.output_filenames(()).with_extension(ext);
-    let dir = rustc_out_file
-        .parent()
-        .unwrap_or_else(|| panic!("{} has no parent", rustc_out_file.display()));
-    let file = rustc_out_file
-        .file_name()
-        .unwrap_or_else(|| panic!("has no file name"))
-        .to_str()
-        .unwrap_or_else(|| panic!("not utf8"));
-
-    let file = if file.starts_with("lib") {
-        std::borrow::Cow::Borrowed(file)
-    } else {
-        format!("lib{file}").into()
-    };
-
-    dir.join(file.as_ref())
+    let output_filenames = tcx.output_filenames(());
+    let file = format!(
+        "lib{}{}",
+        tcx.crate_name(LOCAL_CRATE),
+        tcx.sess.opts.cg.extra_filename
+    );
+    output_filenames
+        .out_directory
+        .join(file)
+        .with_extension(ext)
 }
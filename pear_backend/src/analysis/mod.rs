@@ -1,2 +1,3 @@
+pub mod entry_analysis;
 pub mod global_analysis;
 pub mod local_analysis;
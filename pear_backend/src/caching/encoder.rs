@@ -17,13 +17,23 @@
 //! over the `SourceMap`. If we notice this being too slow we can always use a
 //! similar shorthand technique that is used for types.
 //!
+//! Files are gzip-compressed when `PEAR_CACHE_COMPRESSION` is set. Compression
+//! only wraps the file IO and is detected when decoding, so compressed and
+//! uncompressed files can be mixed.
+//!
+//! Every file starts with a header naming the versions of PEAR and rustc that
+//! wrote it. Files with a different header are rejected instead of decoded, as
+//! the encoding is not stable across either.
+//!
 //! Note that we encode `AllocId`s simply as themselves. This is possibly
 //! incorrect but we're not really relying on this information at the moment so
 //! we are not investing in it.
-use std::fs::File;
-use std::io::{self, Read};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::path::Path;
-use std::{num::NonZeroU64, path::PathBuf};
+use std::{env, num::NonZeroU64, path::PathBuf};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 
 use rustc_const_eval::interpret::AllocId;
 use rustc_hash::FxHashMap;
@@ -54,29 +64,57 @@ pub struct PearEncoder<'tcx> {
 
 impl<'tcx> PearEncoder<'tcx> {
     /// Create a new encoder that will write to the provided file.
-    pub fn new(path: impl AsRef<Path>, tcx: TyCtxt<'tcx>) -> Self {
-        Self {
+    pub fn new(path: impl AsRef<Path>, tcx: TyCtxt<'tcx>) -> io::Result<Self> {
+        Ok(Self {
             tcx,
-            file_encoder: FileEncoder::new(path).unwrap(),
+            file_encoder: FileEncoder::new(path)?,
             type_shorthands: Default::default(),
             predicate_shorthands: Default::default(),
-        }
+        })
     }
 
-    pub fn finish(mut self) {
-        self.file_encoder.finish().unwrap();
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file_encoder.finish().map_err(|(_, err)| err)?;
+        Ok(())
     }
 }
 
+/// Header written at the start of every encoded file.
+fn artifact_header() -> String {
+    format!(
+        "pear {} {}\n",
+        env!("CARGO_PKG_VERSION"),
+        rustc_interface::util::rustc_version_str().unwrap_or("unknown")
+    )
+}
+
+const COMPRESSION_ENV_VAR: &str = "PEAR_CACHE_COMPRESSION";
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+fn compression_enabled() -> bool {
+    env::var(COMPRESSION_ENV_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
 /// Convenience function that encodes some value to a file.
 pub fn encode_to_file<'tcx, V: Encodable<PearEncoder<'tcx>>>(
     tcx: TyCtxt<'tcx>,
     path: impl AsRef<Path>,
     v: &V,
-) {
-    let mut encoder = PearEncoder::new(path, tcx);
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut encoder = PearEncoder::new(path, tcx)?;
+    encoder.emit_raw_bytes(artifact_header().as_bytes());
     v.encode(&mut encoder);
-    encoder.finish();
+    encoder.finish()?;
+
+    // `FileEncoder` only writes to a path, so the encoded file is compressed in place.
+    if compression_enabled() {
+        let encoded = fs::read(path)?;
+        let mut compressor = GzEncoder::new(File::create(path)?, Compression::default());
+        compressor.write_all(&encoded)?;
+        compressor.finish()?;
+    }
+    Ok(())
 }
 
 /// Whatever can't survive the crossing we need to live without.
@@ -140,25 +178,42 @@ pub struct PearDecoder<'tcx, 'a> {
 }
 
 impl<'tcx, 'a> PearDecoder<'tcx, 'a> {
-    /// Decode what is in this buffer.
-    pub fn new(tcx: TyCtxt<'tcx>, buf: &'a [u8]) -> Self {
+    /// Decode what is in this buffer, starting at the given position.
+    pub fn new(tcx: TyCtxt<'tcx>, buf: &'a [u8], position: usize) -> Self {
         Self {
             tcx,
-            mem_decoder: MemDecoder::new(buf, 0),
+            mem_decoder: MemDecoder::new(buf, position),
             shorthand_map: Default::default(),
         }
     }
 }
 
-/// Convenience function that decodes a value from a file.
+/// Convenience function that decodes a value from a file. Fails if the file was written by a
+/// different version of PEAR or rustc.
 pub fn decode_from_file<'tcx, V: for<'a> Decodable<PearDecoder<'tcx, 'a>>>(
     tcx: TyCtxt<'tcx>,
     path: impl AsRef<Path>,
 ) -> io::Result<V> {
-    let mut file = File::open(path)?;
+    let mut file = File::open(path.as_ref())?;
     let mut buf = Vec::new();
     file.read_to_end(&mut buf)?;
-    let mut decoder = PearDecoder::new(tcx, buf.as_slice());
+    if buf.starts_with(&GZIP_MAGIC) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(buf.as_slice()).read_to_end(&mut decompressed)?;
+        buf = decompressed;
+    }
+    let header = artifact_header();
+    if !buf.starts_with(header.as_bytes()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} was written by a different version of PEAR or rustc",
+                path.as_ref().display()
+            ),
+        ));
+    }
+    // Positions recorded by the encoder include the header, so decoding starts right after it.
+    let mut decoder = PearDecoder::new(tcx, buf.as_slice(), header.len());
     Ok(V::decode(&mut decoder))
 }
 
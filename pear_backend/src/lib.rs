@@ -3,6 +3,7 @@
 #[macro_use]
 extern crate rustc_middle;
 extern crate polonius_engine;
+extern crate rustc_ast;
 extern crate rustc_borrowck;
 extern crate rustc_const_eval;
 extern crate rustc_data_structures;
@@ -27,21 +28,43 @@ use std::process::Command;
 
 mod analysis;
 mod caching;
+mod profiling;
 mod reachability;
 mod refiner;
 mod serialize;
 mod utils;
 
+pub use analysis::entry_analysis::{analyze_entry, analyze_entry_with};
 pub use analysis::global_analysis::GlobalAnalysis;
 pub use analysis::local_analysis::LocalAnalysis;
-pub use reachability::{collect_from, Node, Usage, UsageGraph};
-pub use refiner::{refine_from, RefinedNode, RefinedUsageGraph, TransitiveRefinedNode};
-
+pub use caching::{decode_from_file, encode_to_file};
+pub use profiling::{print_profile, profile, profiling_enabled};
+pub use reachability::{
+    collect_from, collect_from_many, item_fingerprint, runtime_roots,
+    validate_against_monomorphization, Collection, CollectionBudget, Collector, IncrementalCache,
+    MonoItemDiscrepancies, MonoItemRelation, Node, Usage, UsageGraph,
+};
+pub use refiner::{
+    crate_name_filter, refine_from, AmbiguousFnPtrCall, GraphDiff, GraphPath, InstancePath,
+    PortableRefinedUsageGraph, RefinedNode, RefinedUsageGraph, RefinerConfig, RefinerError,
+    RefinerErrorKind, ResolutionKind, TransitiveRefinedNode,
+};
+pub use serialize::{serialize_span, with_source_map};
+pub use utils::{instance_sig, num_args_for_instance};
+
+/// Returns the target set through `CARGO_BUILD_TARGET`, falling back to the host target of rustc.
 fn get_default_rustc_target() -> Result<String, String> {
+    const CARGO_BUILD_TARGET_ENV_VAR: &str = "CARGO_BUILD_TARGET";
     const RUSTC_COMMAND: &str = "rustc";
     const HOST_PREFIX: &str = "host: ";
     const VERBOSE_VERSION_ARG: &str = "-vV";
 
+    if let Ok(target) = std::env::var(CARGO_BUILD_TARGET_ENV_VAR)
+        && !target.is_empty()
+    {
+        return Ok(target);
+    }
+
     let output = Command::new(RUSTC_COMMAND)
         .arg(VERBOSE_VERSION_ARG)
         .output()
@@ -63,10 +86,39 @@ fn get_default_rustc_target() -> Result<String, String> {
     Ok(target)
 }
 
-pub fn modify_cargo(cargo: &mut Command) {
+/// Returns true if the cargo args already select a target.
+fn has_target_arg(cargo_args: &[String]) -> bool {
+    const TARGET_ARG: &str = "--target";
+    cargo_args
+        .iter()
+        .any(|arg| arg == TARGET_ARG || arg.starts_with(&format!("{TARGET_ARG}=")))
+}
+
+/// Forwards the cargo args and rebuilds the standard library from source unless `build_std` is
+/// unset, which requires passing an explicit target to cargo. Without `build_std`, the target is only
+/// passed if it is overridden. If the default target cannot be detected, `--target` is omitted.
+pub fn modify_cargo(
+    cargo: &mut Command,
+    build_std: bool,
+    target: Option<&str>,
+    cargo_args: &[String],
+) {
     const CARGO_BUILD_STD_ARG: &str = "-Zbuild-std=std,core,alloc,proc_macro";
-    cargo.arg(CARGO_BUILD_STD_ARG);
-    cargo.arg(format!("--target={}", get_default_rustc_target().unwrap()));
+    cargo.args(cargo_args);
+    if let Some(target) = target {
+        cargo.arg(format!("--target={target}"));
+    } else if build_std && !has_target_arg(cargo_args) {
+        // Cargo reports the missing target itself, so the detection failure is not fatal here.
+        match get_default_rustc_target() {
+            Ok(target) => {
+                cargo.arg(format!("--target={target}"));
+            }
+            Err(err) => log::warn!("{err}, not passing --target to cargo"),
+        }
+    }
+    if build_std {
+        cargo.arg(CARGO_BUILD_STD_ARG);
+    }
 }
 
 pub fn modify_compiler_args(compiler_args: &mut Vec<String>) {
@@ -0,0 +1,70 @@
+//! Time spent in each phase of the analysis, broken down by entry.
+//!
+//! Timings are only recorded when `PEAR_PROFILE` is set, and are printed by the global analyses
+//! once they are done with all entries.
+use std::{
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+
+const PROFILE_ENV_VAR: &str = "PEAR_PROFILE";
+
+struct Timing {
+    phase: &'static str,
+    entry: String,
+    elapsed: Duration,
+}
+
+static TIMINGS: Mutex<Vec<Timing>> = Mutex::new(Vec::new());
+
+pub fn profiling_enabled() -> bool {
+    env::var(PROFILE_ENV_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Runs the phase for the entry, recording the time it took if profiling is enabled. The entry is
+/// only named if the timing is recorded.
+pub fn profile<T>(phase: &'static str, entry: impl FnOnce() -> String, f: impl FnOnce() -> T) -> T {
+    if !profiling_enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    TIMINGS.lock().unwrap().push(Timing {
+        phase,
+        entry: entry(),
+        elapsed,
+    });
+    result
+}
+
+/// Prints the total time of each phase followed by the time of each entry in it, slowest first,
+/// and forgets the printed timings.
+pub fn print_profile() {
+    if !profiling_enabled() {
+        return;
+    }
+    let timings = std::mem::take(&mut *TIMINGS.lock().unwrap());
+    println!("PEAR profile:");
+    // Phases are printed in the order they first ran in.
+    let phases = timings
+        .iter()
+        .map(|timing| timing.phase)
+        .unique()
+        .collect_vec();
+    for phase in phases {
+        let phase_timings = timings
+            .iter()
+            .filter(|timing| timing.phase == phase)
+            .sorted_by(|a, b| b.elapsed.cmp(&a.elapsed))
+            .collect_vec();
+        let total: Duration = phase_timings.iter().map(|timing| timing.elapsed).sum();
+        println!("  {phase}: {:.3}s", total.as_secs_f64());
+        for timing in phase_timings {
+            println!("    {}: {:.3}s", timing.entry, timing.elapsed.as_secs_f64());
+        }
+    }
+}
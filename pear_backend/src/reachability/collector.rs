@@ -84,11 +84,13 @@
 //! the trait, as we need to store pointers to these functions even if they never get called
 //! anywhere. This can be seen as a special case of taking a function reference.
 
-use log::trace;
+use log::{trace, warn};
 use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_hir::def::DefKind;
 use rustc_hir::def_id::DefId;
 use rustc_hir::lang_items::LangItem;
 use rustc_hir::{self as hir, Unsafety};
+use rustc_macros::{TyDecodable, TyEncodable};
 use rustc_middle::mir::interpret::{AllocId, ErrorHandled, GlobalAlloc, Scalar};
 use rustc_middle::mir::mono::MonoItem;
 use rustc_middle::mir::visit::TyContext;
@@ -103,13 +105,24 @@ use rustc_middle::ty::{
     self, Instance, InstanceDef, Ty, TyCtxt, TypeFoldable, TypeVisitableExt, VtblEntry,
 };
 use rustc_middle::ty::{FnSig, GenericArgs};
+use rustc_span::Span;
 use serde::Serialize;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
-use crate::serialize::{serialize_def_id, serialize_edges, serialize_mono_item, serialize_sig};
-use crate::utils::{erase_regions_in_sig, fn_trait_method_sig};
+use crate::profiling::profile;
+use crate::reachability::IncrementalCache;
+use crate::refiner::utils::assignments_to;
+use crate::serialize::{
+    serialize_def_id, serialize_edges, serialize_mono_item, serialize_optional_span, serialize_sig,
+};
+use crate::utils::{
+    assert_lang_item, erase_regions_in_sig, fn_trait_method_sig, instance_def_kind,
+};
 
 /// We collect the specifics of how each mono item is used to aid with refinement later.
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, TyEncodable, TyDecodable)]
 pub enum Usage<'tcx> {
     /// Root of the analysis.
     Root,
@@ -129,6 +142,14 @@ pub enum Usage<'tcx> {
     IndirectDrop,
     /// Thread-local shim generated by the compiler for some thread local.
     ThreadLocalShim,
+    /// Function called while evaluating a constant at compile time.
+    ConstEval,
+    /// Function referenced by a constant that is only used while evaluating another constant, so
+    /// it cannot be called at runtime.
+    ConstFn {
+        #[serde(serialize_with = "serialize_sig")]
+        sig: FnSig<'tcx>,
+    },
     /// Static function collected from a compile time function evaluation alloc.
     StaticFn {
         #[serde(serialize_with = "serialize_sig")]
@@ -155,7 +176,7 @@ pub enum Usage<'tcx> {
     },
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, TyEncodable, TyDecodable)]
 
 /// Differentiates between methods coming from an impl block and inherent ones.
 pub enum ImplType {
@@ -167,16 +188,52 @@ pub enum ImplType {
 }
 
 /// Mono item with usage specifics attached.
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct Node<'tcx> {
     #[serde(serialize_with = "serialize_mono_item")]
     item: MonoItem<'tcx>,
     usage: Usage<'tcx>,
+    /// Name of the `InstanceDef` variant for function items (e.g., "Item" or "DropGlue").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<&'static str>,
+    /// Span of the MIR statement or terminator the item was first used at.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_span"
+    )]
+    span: Option<Span>,
+}
+
+// The span only locates a use and is not a part of the node identity, so that the same item used
+// at different locations is still collected once.
+impl<'tcx> PartialEq for Node<'tcx> {
+    fn eq(&self, other: &Self) -> bool {
+        self.item == other.item && self.usage == other.usage && self.kind == other.kind
+    }
+}
+
+impl<'tcx> Eq for Node<'tcx> {}
+
+impl<'tcx> Hash for Node<'tcx> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.item.hash(state);
+        self.usage.hash(state);
+        self.kind.hash(state);
+    }
 }
 
 impl<'tcx> Node<'tcx> {
     pub fn new(item: MonoItem<'tcx>, usage: Usage<'tcx>) -> Self {
-        Self { item, usage }
+        let kind = match item {
+            MonoItem::Fn(instance) => Some(instance_def_kind(instance.def)),
+            MonoItem::Static(..) | MonoItem::GlobalAsm(..) => None,
+        };
+        Self {
+            item,
+            usage,
+            kind,
+            span: None,
+        }
     }
 
     pub fn item(&self) -> MonoItem<'tcx> {
@@ -187,6 +244,18 @@ impl<'tcx> Node<'tcx> {
         self.usage
     }
 
+    pub fn kind(&self) -> Option<&'static str> {
+        self.kind
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub(super) fn with_span(self, span: Option<Span>) -> Self {
+        Self { span, ..self }
+    }
+
     /// Returns true if the mono item was not collected as a result of a direct invocation via a
     /// terminator.
     pub fn is_indirect(&self) -> bool {
@@ -209,7 +278,7 @@ impl<'tcx> Node<'tcx> {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct UsageGraph<'tcx> {
     // Maps every mono item to the mono items used by it.
     #[serde(serialize_with = "serialize_edges")]
@@ -218,15 +287,129 @@ pub struct UsageGraph<'tcx> {
     // Maps every mono item to the mono items that use it.
     #[serde(serialize_with = "serialize_edges")]
     backward_edges: FxHashMap<MonoItem<'tcx>, FxHashSet<Node<'tcx>>>,
+
+    // Whether the collection stopped early because it ran out of budget.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+}
+
+/// Limits on how much of the mono item graph is collected, unbounded by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CollectionBudget {
+    /// Maximum number of distinct nodes to collect.
+    pub max_items: Option<usize>,
+    /// Maximum number of uses between the root and a collected node.
+    pub max_depth: Option<usize>,
 }
 
 type UsedMonoItems<'tcx> = Vec<Node<'tcx>>;
 
+/// The nodes reachable from a root along with the graph of their uses.
+pub type Collection<'tcx> = (FxHashSet<Node<'tcx>>, UsageGraph<'tcx>);
+
 impl<'tcx> UsageGraph<'tcx> {
     fn new() -> UsageGraph<'tcx> {
         UsageGraph {
             forward_edges: FxHashMap::default(),
             backward_edges: FxHashMap::default(),
+            truncated: false,
+        }
+    }
+
+    /// Returns true if some reachable items were not collected because of the budget.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns all nodes used by some mono item in the graph.
+    pub fn nodes(&self) -> FxHashSet<Node<'tcx>> {
+        self.forward_edges.values().flatten().copied().collect()
+    }
+
+    /// Returns all mono items in the graph, including the ones that are never used.
+    pub fn items(&self) -> FxHashSet<MonoItem<'tcx>> {
+        self.forward_edges
+            .keys()
+            .copied()
+            .chain(self.nodes().into_iter().map(|node| node.item()))
+            .collect()
+    }
+
+    /// Merges the edges of another usage graph into this one.
+    pub fn merge(&mut self, other: UsageGraph<'tcx>) {
+        for (item, used_items) in other.forward_edges {
+            self.forward_edges
+                .entry(item)
+                .or_default()
+                .extend(used_items);
+        }
+        for (item, user_items) in other.backward_edges {
+            self.backward_edges
+                .entry(item)
+                .or_default()
+                .extend(user_items);
+        }
+        self.truncated |= other.truncated;
+    }
+
+    /// Removes all nodes with a matching usage along with the items only reachable through them.
+    pub fn remove_usages(&mut self, root: MonoItem<'tcx>, predicate: impl Fn(Usage<'tcx>) -> bool) {
+        for used_items in self.forward_edges.values_mut() {
+            used_items.retain(|used_item| !predicate(used_item.usage()));
+        }
+        let forward_edges = &self.forward_edges;
+        for (item, user_items) in self.backward_edges.iter_mut() {
+            user_items.retain(|user_item| {
+                forward_edges
+                    .get(&user_item.item())
+                    .is_some_and(|used_items| {
+                        used_items.iter().any(|used_item| used_item.item() == *item)
+                    })
+            });
+        }
+        self.prune_unreachable_from(root);
+    }
+
+    /// Returns all mono items transitively used by the item. The item itself is only included if
+    /// it is used recursively.
+    pub fn reachable_from(&self, item: MonoItem<'tcx>) -> FxHashSet<MonoItem<'tcx>> {
+        Self::traverse(&self.forward_edges, item)
+    }
+
+    /// Returns all mono items transitively using the item. The item itself is only included if it
+    /// is used recursively.
+    pub fn callers_of(&self, item: MonoItem<'tcx>) -> FxHashSet<MonoItem<'tcx>> {
+        Self::traverse(&self.backward_edges, item)
+    }
+
+    /// Breadth-first traversal of the edges starting from the item.
+    fn traverse(
+        edges: &FxHashMap<MonoItem<'tcx>, FxHashSet<Node<'tcx>>>,
+        item: MonoItem<'tcx>,
+    ) -> FxHashSet<MonoItem<'tcx>> {
+        let mut visited = FxHashSet::default();
+        let mut queue = VecDeque::from([item]);
+        while let Some(item) = queue.pop_front() {
+            for node in edges.get(&item).into_iter().flatten() {
+                if visited.insert(node.item()) {
+                    queue.push_back(node.item());
+                }
+            }
+        }
+        visited
+    }
+
+    /// Removes all mono items and edges that are not reachable from the root.
+    pub fn prune_unreachable_from(&mut self, root: MonoItem<'tcx>) {
+        let mut reachable = self.reachable_from(root);
+        reachable.insert(root);
+
+        self.forward_edges
+            .retain(|item, _| reachable.contains(item));
+        self.backward_edges
+            .retain(|item, _| reachable.contains(item));
+        for user_items in self.backward_edges.values_mut() {
+            user_items.retain(|user_item| reachable.contains(&user_item.item()));
         }
     }
 
@@ -248,23 +431,89 @@ impl<'tcx> UsageGraph<'tcx> {
     }
 }
 
-/// Collect all monomorphized items reachable from `starting_item`.
+/// Collect all monomorphized items reachable from `starting_item`. Items from crates named by any
+/// of the `crate_filter` entries are collected, but the items they use are not.
+///
+/// Every visited node is mapped to the smallest depth it was reached at. A node reached again at a
+/// smaller depth is walked again, so that the items cut off by the depth budget the first time are
+/// collected if they are within the budget from the shallower use.
 fn collect_items_rec<'tcx>(
     tcx: TyCtxt<'tcx>,
     starting_item: Node<'tcx>,
-    visited: &mut FxHashSet<Node<'tcx>>,
+    visited: &mut FxHashMap<Node<'tcx>, usize>,
     usage_map: &mut UsageGraph<'tcx>,
+    used_items_cache: &mut FxHashMap<Node<'tcx>, UsedMonoItems<'tcx>>,
+    budget: CollectionBudget,
+    crate_filter: &[String],
+    depth: usize,
 ) {
-    if !visited.insert(starting_item) {
-        // We've been here already, no need to search again.
+    if let Some(&visited_depth) = visited.get(&starting_item) {
+        if visited_depth <= depth {
+            // We've been here already, no need to search again.
+            return;
+        }
+    } else if budget
+        .max_items
+        .is_some_and(|max_items| visited.len() >= max_items)
+    {
+        // Keep the partial results instead of exhausting the memory.
+        usage_map.truncated = true;
         return;
     }
+    if budget.max_depth.is_some_and(|max_depth| depth > max_depth) {
+        // Whether this truncates the graph is only known once all uses have been walked.
+        return;
+    }
+    visited.insert(starting_item, depth);
 
     if tcx.is_foreign_item(starting_item.item().def_id()) {
         // A foreign item has no body.
         return;
     }
 
+    if depth > 0 && is_filtered_crate(tcx, starting_item.item().def_id(), crate_filter) {
+        // Keep the item as the boundary of the graph, but do not descend into its crate.
+        return;
+    }
+
+    // The items used by an item do not depend on the root, so they are shared between roots.
+    let used_items = used_items_cache
+        .entry(starting_item)
+        .or_insert_with(|| collect_used_items_of(tcx, starting_item))
+        .clone();
+
+    usage_map.record_used(starting_item, used_items.clone());
+
+    for used_item in used_items {
+        collect_items_rec(
+            tcx,
+            used_item,
+            visited,
+            usage_map,
+            used_items_cache,
+            budget,
+            crate_filter,
+            depth + 1,
+        );
+    }
+}
+
+/// Returns true if the crate defining the item is named by any of the filtered items.
+fn is_filtered_crate(tcx: TyCtxt<'_>, def_id: DefId, crate_filter: &[String]) -> bool {
+    if crate_filter.is_empty() {
+        return false;
+    }
+    let crate_name = tcx.crate_name(def_id.krate);
+    crate_filter
+        .iter()
+        .any(|filtered_item| crate_name.as_str() == filtered_item)
+}
+
+/// Collects the mono items directly used by the item.
+fn collect_used_items_of<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    starting_item: Node<'tcx>,
+) -> UsedMonoItems<'tcx> {
     let mut used_items = Vec::new();
 
     match starting_item.item() {
@@ -280,7 +529,7 @@ fn collect_items_rec<'tcx>(
 
             if let Ok(alloc) = tcx.eval_static_initializer(def_id) {
                 for &prov in alloc.inner().provenance().ptrs().values() {
-                    collect_alloc(tcx, prov.alloc_id(), &mut used_items);
+                    collect_alloc(tcx, prov.alloc_id(), false, &mut used_items);
                 }
             }
 
@@ -331,11 +580,7 @@ fn collect_items_rec<'tcx>(
         }
     }
 
-    usage_map.record_used(starting_item, used_items.clone());
-
-    for used_item in used_items {
-        collect_items_rec(tcx, used_item, visited, usage_map);
-    }
+    used_items
 }
 
 struct MirUsedCollector<'a, 'tcx> {
@@ -361,6 +606,50 @@ impl<'a, 'tcx> MirUsedCollector<'a, 'tcx> {
             );
         Ok(maybe_mono.expect("reachability is not configured to perform partial resolution"))
     }
+
+    /// Finds the function items whose pointers flow into the operand within the body, looking
+    /// through uses and casts of locals that are only ever assigned as a whole.
+    fn reified_fns_behind(&self, operand: &mir::Operand<'tcx>) -> Vec<Ty<'tcx>> {
+        let mut reified_fns = vec![];
+        let mut visited = FxHashSet::default();
+        let mut worklist = vec![operand.clone()];
+        while let Some(operand) = worklist.pop() {
+            let Some(local) = operand.place().and_then(|place| place.as_local()) else {
+                continue;
+            };
+            if !visited.insert(local) {
+                continue;
+            }
+            let Some(assignments) = assignments_to(self.body, local) else {
+                continue;
+            };
+            for rvalue in assignments {
+                match rvalue {
+                    mir::Rvalue::Cast(
+                        mir::CastKind::PointerCoercion(PointerCoercion::ReifyFnPointer),
+                        operand,
+                        _,
+                    ) => {
+                        if let Ok(fn_ty) = self.monomorphize(operand.ty(self.body, self.tcx)) {
+                            reified_fns.push(fn_ty);
+                        }
+                    }
+                    mir::Rvalue::Cast(_, operand, _) | mir::Rvalue::Use(operand) => {
+                        worklist.push(operand)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        reified_fns
+    }
+
+    /// Attaches the span to the items used since `start` that are not located yet.
+    fn record_span(&mut self, start: usize, span: Span) {
+        for node in self.output[start..].iter_mut() {
+            node.span.get_or_insert(span);
+        }
+    }
 }
 
 impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
@@ -368,6 +657,7 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
         trace!("visiting rvalue {:?}", *rvalue);
 
         let span = self.body.source_info(location).span;
+        let start = self.output.len();
 
         match *rvalue {
             // When doing an cast from a regular pointer to a fat pointer, we
@@ -402,6 +692,9 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
                     );
                 }
             }
+            // The coercion is recorded regardless of where the pointer ends up, so pointers stored
+            // in fields of aggregates are candidates for the indirect calls through them as well.
+            // Pointers coerced inside of constants are collected from their allocations instead.
             mir::Rvalue::Cast(
                 mir::CastKind::PointerCoercion(PointerCoercion::ReifyFnPointer),
                 ref operand,
@@ -449,6 +742,19 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
                     _ => bug!(),
                 }
             }
+            // A pointer transmuted into a function pointer is called with the target signature, so
+            // the functions reified in this body that end up transmuted are recorded with it.
+            mir::Rvalue::Cast(mir::CastKind::Transmute, ref operand, target_ty) => {
+                let Ok(target_ty) = self.monomorphize(target_ty) else {
+                    return;
+                };
+                if let ty::FnPtr(poly_fn_sig) = target_ty.kind() {
+                    let sig = erase_regions_in_sig(*poly_fn_sig, self.tcx);
+                    for fn_ty in self.reified_fns_behind(operand) {
+                        visit_fn_use(self.tcx, fn_ty, false, self.output, Usage::FnPtr { sig });
+                    }
+                }
+            }
             mir::Rvalue::ThreadLocalRef(def_id) => {
                 assert!(self.tcx.is_thread_local_static(def_id));
                 trace!("collecting thread-local static {:?}", def_id);
@@ -458,6 +764,7 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
             _ => { /* not interesting */ }
         }
 
+        self.record_span(start, span);
         self.super_rvalue(rvalue, location);
     }
 
@@ -465,6 +772,7 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
     /// to walk it would attempt to evaluate the `ty::Const` inside, which doesn't necessarily
     /// work, as some constants cannot be represented in the type system.
     fn visit_constant(&mut self, constant: &mir::ConstOperand<'tcx>, location: Location) {
+        let start = self.output.len();
         let Ok(const_) = self.monomorphize(constant.const_) else {
             return;
         };
@@ -478,13 +786,18 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
                 const_
             ),
         };
-        collect_const_value(self.tcx, val, self.output);
+        collect_const_value(self.tcx, val, false, self.output);
+        if let mir::Const::Unevaluated(unevaluated, _) = const_ {
+            collect_const_eval_calls(self.tcx, unevaluated, self.output);
+        }
+        self.record_span(start, constant.span);
         MirVisitor::visit_ty(self, const_.ty(), TyContext::Location(location));
     }
 
     fn visit_terminator(&mut self, terminator: &mir::Terminator<'tcx>, location: Location) {
         trace!("visiting terminator {:?} @ {:?}", terminator, location);
         let tcx = self.tcx;
+        let start = self.output.len();
         let push_mono_lang_item = |this: &mut Self, lang_item: LangItem, usage: Usage<'tcx>| {
             let instance = Instance::mono(tcx, tcx.require_lang_item(lang_item, None));
             this.output.push(create_fn_mono_item(instance, usage));
@@ -539,14 +852,7 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
                 }
             }
             mir::TerminatorKind::Assert { ref msg, .. } => {
-                let lang_item = match &**msg {
-                    mir::AssertKind::BoundsCheck { .. } => LangItem::PanicBoundsCheck,
-                    mir::AssertKind::MisalignedPointerDereference { .. } => {
-                        LangItem::PanicMisalignedPointerDereference
-                    }
-                    _ => LangItem::Panic,
-                };
-                push_mono_lang_item(self, lang_item, Usage::Assert);
+                push_mono_lang_item(self, assert_lang_item(&**msg), Usage::Assert);
             }
             mir::TerminatorKind::UnwindTerminate(reason) => {
                 push_mono_lang_item(self, reason.lang_item(), Usage::Unwind);
@@ -556,6 +862,8 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
             | mir::TerminatorKind::UnwindResume
             | mir::TerminatorKind::Return
             | mir::TerminatorKind::Unreachable => {}
+            // Coroutine terminators are lowered away by the coroutine transform, and false edges
+            // are removed before optimized MIR is built, so none of them are ever collected from.
             mir::TerminatorKind::CoroutineDrop
             | mir::TerminatorKind::Yield { .. }
             | mir::TerminatorKind::FalseEdge { .. }
@@ -566,6 +874,7 @@ impl<'a, 'tcx> MirVisitor<'tcx> for MirUsedCollector<'a, 'tcx> {
             push_mono_lang_item(self, reason.lang_item(), Usage::Unwind);
         }
 
+        self.record_span(start, terminator.source_info.span);
         self.super_terminator(terminator, location);
     }
 }
@@ -604,7 +913,9 @@ fn visit_fn_use<'tcx>(
             {
                 instance
             } else {
-                bug!("reachability is not configured to perform partial resolution")
+                // The refiner records such calls as unresolved, nothing to collect here.
+                warn!("failed to resolve {ty}, skipping it during collection");
+                return;
             }
         } else {
             match ty::Instance::resolve_for_fn_ptr(tcx, ty::ParamEnv::reveal_all(), def_id, args) {
@@ -643,7 +954,18 @@ fn visit_instance_use<'tcx>(
     }
 
     match instance.def {
-        ty::InstanceDef::Virtual(..) | ty::InstanceDef::Intrinsic(_) => {
+        ty::InstanceDef::Virtual(def_id, _) => {
+            // A reified trait object method dispatches through the vtable in a shim, which is
+            // what `Instance::resolve_for_fn_ptr` resolves it to.
+            if !is_direct_call {
+                let reify_shim = Instance {
+                    def: ty::InstanceDef::ReifyShim(def_id),
+                    args: instance.args,
+                };
+                output.push(create_fn_mono_item(reify_shim, usage));
+            }
+        }
+        ty::InstanceDef::Intrinsic(_) => {
             if !is_direct_call {
                 bug!("{:?} being reified", instance);
             }
@@ -844,8 +1166,14 @@ fn create_mono_items_for_vtable_methods<'tcx>(
     }
 }
 
-/// Scans the CTFE alloc in order to find function calls, closures, and drop-glue.
-fn collect_alloc<'tcx>(tcx: TyCtxt<'tcx>, alloc_id: AllocId, output: &mut UsedMonoItems<'tcx>) {
+/// Scans the CTFE alloc in order to find function calls, closures, and drop-glue. Functions in an
+/// alloc only used in a const context are recorded as such, as they cannot be called at runtime.
+fn collect_alloc<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    alloc_id: AllocId,
+    const_context: bool,
+    output: &mut UsedMonoItems<'tcx>,
+) {
     match tcx.global_alloc(alloc_id) {
         GlobalAlloc::Static(def_id) => {
             assert!(!tcx.is_thread_local_static(def_id));
@@ -856,7 +1184,7 @@ fn collect_alloc<'tcx>(tcx: TyCtxt<'tcx>, alloc_id: AllocId, output: &mut UsedMo
             trace!("collecting {:?} with {:#?}", alloc_id, alloc);
             for &prov in alloc.inner().provenance().ptrs().values() {
                 rustc_data_structures::stack::ensure_sufficient_stack(|| {
-                    collect_alloc(tcx, prov.alloc_id(), output);
+                    collect_alloc(tcx, prov.alloc_id(), const_context, output);
                 });
             }
         }
@@ -867,11 +1195,16 @@ fn collect_alloc<'tcx>(tcx: TyCtxt<'tcx>, alloc_id: AllocId, output: &mut UsedMo
                     .instantiate(tcx, fn_instance.args),
                 tcx,
             );
-            output.push(create_fn_mono_item(fn_instance, Usage::StaticFn { sig }));
+            let usage = if const_context {
+                Usage::ConstFn { sig }
+            } else {
+                Usage::StaticFn { sig }
+            };
+            output.push(create_fn_mono_item(fn_instance, usage));
         }
         GlobalAlloc::VTable(ty, trait_ref) => {
             let alloc_id = tcx.vtable_allocation((ty, trait_ref));
-            collect_alloc(tcx, alloc_id, output)
+            collect_alloc(tcx, alloc_id, const_context, output)
         }
     }
 }
@@ -899,35 +1232,213 @@ fn collect_used_items<'tcx>(
 fn collect_const_value<'tcx>(
     tcx: TyCtxt<'tcx>,
     value: mir::ConstValue<'tcx>,
+    const_context: bool,
     output: &mut UsedMonoItems<'tcx>,
 ) {
     match value {
         mir::ConstValue::Scalar(Scalar::Ptr(ptr, _size)) => {
-            collect_alloc(tcx, ptr.provenance.alloc_id(), output)
+            collect_alloc(tcx, ptr.provenance.alloc_id(), const_context, output)
+        }
+        mir::ConstValue::Indirect { alloc_id, .. } => {
+            collect_alloc(tcx, alloc_id, const_context, output)
         }
-        mir::ConstValue::Indirect { alloc_id, .. } => collect_alloc(tcx, alloc_id, output),
         mir::ConstValue::Slice { data, meta: _ } => {
             for &prov in data.inner().provenance().ptrs().values() {
-                collect_alloc(tcx, prov.alloc_id(), output);
+                collect_alloc(tcx, prov.alloc_id(), const_context, output);
             }
         }
         _ => {}
     }
 }
 
+/// Collects the functions called directly by the body of a constant, which are evaluated at compile
+/// time instead of being invoked at runtime.
+fn collect_const_eval_calls<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    unevaluated: mir::UnevaluatedConst<'tcx>,
+    output: &mut UsedMonoItems<'tcx>,
+) {
+    if unevaluated.promoted.is_some()
+        || !matches!(
+            tcx.def_kind(unevaluated.def),
+            DefKind::Const | DefKind::AssocConst | DefKind::AnonConst | DefKind::InlineConst
+        )
+    {
+        return;
+    }
+
+    let const_instance = Instance::new(unevaluated.def, unevaluated.args);
+    let body = tcx.mir_for_ctfe(unevaluated.def);
+    for basic_block in body.basic_blocks.iter() {
+        if let mir::TerminatorKind::Call { ref func, .. } = basic_block.terminator().kind
+            && let ty::FnDef(def_id, args) = *func.ty(body, tcx).kind()
+        {
+            let args = const_instance.instantiate_mir_and_normalize_erasing_regions(
+                tcx,
+                ty::ParamEnv::reveal_all(),
+                ty::EarlyBinder::bind(args),
+            );
+            if let Ok(Some(instance)) =
+                Instance::resolve(tcx, ty::ParamEnv::reveal_all(), def_id, args)
+                && !matches!(instance.def, InstanceDef::Intrinsic(..))
+            {
+                output.push(create_fn_mono_item(instance, Usage::ConstEval));
+            }
+        }
+    }
+
+    // The values of the constants used by the body only exist at compile time, while the value of
+    // this constant is collected where it is used at runtime.
+    for constant in body.required_consts.iter() {
+        let const_ = const_instance.instantiate_mir_and_normalize_erasing_regions(
+            tcx,
+            ty::ParamEnv::reveal_all(),
+            ty::EarlyBinder::bind(constant.const_),
+        );
+        if let Ok(val) = const_.eval(tcx, ty::ParamEnv::reveal_all(), None) {
+            collect_const_value(tcx, val, true, output);
+        }
+    }
+}
+
+/// Collects the items reachable from the root. Items from crates matching the `crate_filter` are
+/// boundaries of the graph: they are recorded as used, but nothing below them is collected, so
+/// the graph is incomplete below the boundaries by design.
 pub fn collect_from<'tcx>(
     tcx: TyCtxt<'tcx>,
     root: MonoItem<'tcx>,
-) -> (FxHashSet<Node<'tcx>>, UsageGraph<'tcx>) {
-    let mut visited = FxHashSet::default();
-    let mut usage_map = UsageGraph::new();
-    collect_items_rec(
-        tcx,
-        Node::new(root, Usage::Root),
-        &mut visited,
-        &mut usage_map,
-    );
-    (visited, usage_map)
+    budget: CollectionBudget,
+    crate_filter: &[String],
+) -> Collection<'tcx> {
+    let mut collector = Collector::new(tcx, budget).with_crate_filter(crate_filter.to_vec());
+    let collection = collector.collect(root);
+    // The collector holds the other reference, so the collection is not cloned once it is gone.
+    drop(collector);
+    Rc::unwrap_or_clone(collection)
+}
+
+/// Collects the items reachable from each of the roots. Functions only called by the runtime are
+/// given by [`runtime_roots`](super::runtime_roots) and can be passed as additional roots. The
+/// results are the same as collecting from every root separately, but the MIR of items reachable
+/// from several roots is only walked once.
+pub fn collect_from_many<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    roots: Vec<MonoItem<'tcx>>,
+    budget: CollectionBudget,
+    crate_filter: &[String],
+) -> FxHashMap<MonoItem<'tcx>, Collection<'tcx>> {
+    let mut collector = Collector::new(tcx, budget).with_crate_filter(crate_filter.to_vec());
+    let collections = roots
+        .into_iter()
+        .map(|root| (root, collector.collect(root)))
+        .collect::<Vec<_>>();
+    drop(collector);
+    collections
+        .into_iter()
+        .map(|(root, collection)| (root, Rc::unwrap_or_clone(collection)))
+        .collect()
+}
+
+/// Collects items from several roots, reusing the work done for the previous roots.
+pub struct Collector<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    budget: CollectionBudget,
+    // Crates whose items are not descended into, see `collect_from`.
+    crate_filter: Vec<String>,
+    // Items used by every node visited so far, from any root.
+    used_items: FxHashMap<Node<'tcx>, UsedMonoItems<'tcx>>,
+    // Nodes whose used items were taken from the incremental cache instead of being computed.
+    reused: FxHashSet<Node<'tcx>>,
+    // Nodes and usage graphs reachable from every root collected so far.
+    collected: FxHashMap<MonoItem<'tcx>, Rc<Collection<'tcx>>>,
+}
+
+impl<'tcx> Collector<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>, budget: CollectionBudget) -> Self {
+        Collector {
+            tcx,
+            budget,
+            crate_filter: vec![],
+            used_items: FxHashMap::default(),
+            reused: FxHashSet::default(),
+            collected: FxHashMap::default(),
+        }
+    }
+
+    /// Stops descending into the items from crates matching the filter, see `collect_from`.
+    pub fn with_crate_filter(self, crate_filter: Vec<String>) -> Self {
+        Collector {
+            crate_filter,
+            ..self
+        }
+    }
+
+    /// Reuses the items used by the nodes whose items did not change since the cache was written.
+    pub fn with_incremental_cache(self, cache: &IncrementalCache<'tcx>) -> Self {
+        let used_items = cache.unchanged_uses(self.tcx);
+        Collector {
+            reused: used_items.keys().copied().collect(),
+            used_items,
+            ..self
+        }
+    }
+
+    /// Returns the cache of the items used by every node collected so far.
+    pub fn incremental_cache(&self) -> IncrementalCache<'tcx> {
+        IncrementalCache::new(self.tcx, &self.used_items)
+    }
+
+    /// Returns the nodes whose used items were computed rather than taken from the incremental
+    /// cache.
+    pub fn recomputed(&self) -> Vec<Node<'tcx>> {
+        self.used_items
+            .keys()
+            .filter(|node| !self.reused.contains(node))
+            .copied()
+            .collect()
+    }
+
+    /// Collects the nodes reachable from the root, the same as `collect_from` would. The
+    /// collection is shared with the collector, which returns it again for the same root.
+    pub fn collect(&mut self, root: MonoItem<'tcx>) -> Rc<Collection<'tcx>> {
+        if let Some(collected) = self.collected.get(&root) {
+            return Rc::clone(collected);
+        }
+        let mut visited = FxHashMap::default();
+        let mut usage_map = UsageGraph::new();
+        let tcx = self.tcx;
+        profile(
+            "collection",
+            || tcx.def_path_str(root.def_id()),
+            || {
+                collect_items_rec(
+                    tcx,
+                    Node::new(root, Usage::Root),
+                    &mut visited,
+                    &mut usage_map,
+                    &mut self.used_items,
+                    self.budget,
+                    &self.crate_filter,
+                    0,
+                )
+            },
+        );
+        if let Some(max_depth) = self.budget.max_depth {
+            // The uses of the nodes at the maximum depth are cut off unless they were reached
+            // through a shorter path.
+            usage_map.truncated |= visited.iter().any(|(node, &depth)| {
+                depth == max_depth
+                    && self.used_items.get(node).is_some_and(|used_items| {
+                        used_items
+                            .iter()
+                            .any(|used_item| !visited.contains_key(used_item))
+                    })
+            });
+        }
+        let collected = Rc::new((visited.into_keys().collect(), usage_map));
+        self.collected.insert(root, Rc::clone(&collected));
+        collected
+    }
 }
 
 fn custom_coerce_unsize_info<'tcx>(
@@ -0,0 +1,225 @@
+//! Uses of the collected items persisted between runs, so that only the uses of the items whose
+//! bodies changed since the previous collection are recomputed.
+//!
+//! The uses of an item are reused as long as the fingerprint of the item is unchanged. Uses that
+//! depend on other definitions, such as the values of evaluated constants or the impls put into
+//! vtables, are therefore only recomputed along with the body using them.
+//!
+//! Def ids are encoded by their index, so a cache can only be decoded by the crate that wrote it,
+//! and only as long as the local definitions and the upstream crates stay the same. Each cache is
+//! stored in a file named after the crate and a hash of its definitions, so that caches of other
+//! crates or of other definitions are never decoded.
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use rustc_data_structures::fx::FxHashMap;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use rustc_macros::{TyDecodable, TyEncodable};
+use rustc_middle::{
+    mir::mono::MonoItem,
+    ty::{Instance, TyCtxt},
+};
+use rustc_span::Span;
+
+use crate::{
+    caching::{decode_from_file, encode_to_file},
+    reachability::{Node, Usage},
+    refiner::utils::has_body,
+};
+
+/// Computes a fingerprint of the item that changes whenever its body does.
+pub fn item_fingerprint<'tcx>(tcx: TyCtxt<'tcx>, item: MonoItem<'tcx>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.to_string().hash(&mut hasher);
+    let def_id = item.def_id();
+    match item {
+        // Upstream bodies only change along with their crate.
+        _ if !def_id.is_local() => tcx.crate_hash(def_id.krate).hash(&mut hasher),
+        MonoItem::Fn(instance) if has_body(instance, tcx) => {
+            format!("{:?}", tcx.instance_mir(instance.def)).hash(&mut hasher)
+        }
+        // Foreign items have no body, so they are identified by their path alone.
+        MonoItem::Fn(..) => {}
+        MonoItem::Static(..) | MonoItem::GlobalAsm(..) => tcx
+            .sess
+            .source_map()
+            .span_to_snippet(tcx.source_span(def_id.expect_local()))
+            .ok()
+            .hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// Prefix of the names of the cache files written by the local crate.
+fn cache_file_prefix(tcx: TyCtxt) -> String {
+    format!(
+        "collection-{}-{:016x}-",
+        tcx.crate_name(LOCAL_CRATE),
+        tcx.stable_crate_id(LOCAL_CRATE).as_u64()
+    )
+}
+
+/// Path of the cache file of the local crate, which changes whenever the def ids encoded in it
+/// could point at other items.
+fn cache_file_path(tcx: TyCtxt, cache_dir: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    for cnum in tcx.crates(()) {
+        tcx.stable_crate_id(*cnum).hash(&mut hasher);
+        tcx.crate_hash(*cnum).hash(&mut hasher);
+    }
+    for local_def_id in tcx.iter_local_def_id() {
+        tcx.def_path_hash(local_def_id.to_def_id())
+            .hash(&mut hasher);
+    }
+    cache_dir.join(format!(
+        "{}{:016x}",
+        cache_file_prefix(tcx),
+        hasher.finish()
+    ))
+}
+
+/// Mono item that can be persisted. Global assembly is never persisted, so the items using it are
+/// always recomputed.
+#[derive(Clone, Copy, TyEncodable, TyDecodable)]
+enum CachedMonoItem<'tcx> {
+    Fn(Instance<'tcx>),
+    Static(DefId),
+}
+
+impl<'tcx> CachedMonoItem<'tcx> {
+    fn new(item: MonoItem<'tcx>) -> Option<Self> {
+        match item {
+            MonoItem::Fn(instance) => Some(CachedMonoItem::Fn(instance)),
+            MonoItem::Static(def_id) => Some(CachedMonoItem::Static(def_id)),
+            MonoItem::GlobalAsm(..) => None,
+        }
+    }
+
+    fn item(self) -> MonoItem<'tcx> {
+        match self {
+            CachedMonoItem::Fn(instance) => MonoItem::Fn(instance),
+            CachedMonoItem::Static(def_id) => MonoItem::Static(def_id),
+        }
+    }
+}
+
+#[derive(Clone, Copy, TyEncodable, TyDecodable)]
+struct CachedNode<'tcx> {
+    item: CachedMonoItem<'tcx>,
+    usage: Usage<'tcx>,
+    span: Option<Span>,
+}
+
+impl<'tcx> CachedNode<'tcx> {
+    fn new(node: Node<'tcx>) -> Option<Self> {
+        Some(CachedNode {
+            item: CachedMonoItem::new(node.item())?,
+            usage: node.usage(),
+            span: node.span(),
+        })
+    }
+
+    fn node(self) -> Node<'tcx> {
+        Node::new(self.item.item(), self.usage).with_span(self.span)
+    }
+}
+
+/// Items used by a node, valid as long as the fingerprint of its item is unchanged.
+#[derive(Clone, TyEncodable, TyDecodable)]
+struct CachedUses<'tcx> {
+    node: CachedNode<'tcx>,
+    // Name of the item, so that items of another definition that ended up with the same def id
+    // are not fingerprinted as if they were the cached one.
+    name: String,
+    fingerprint: u64,
+    used_items: Vec<CachedNode<'tcx>>,
+}
+
+/// Uses of every node collected by a previous run.
+#[derive(Clone, Default, TyEncodable, TyDecodable)]
+pub struct IncrementalCache<'tcx> {
+    uses: Vec<CachedUses<'tcx>>,
+}
+
+impl<'tcx> IncrementalCache<'tcx> {
+    /// Loads the cache written by a previous run of the local crate into the directory, which is
+    /// empty if there is none for the current definitions or if it was written by a different
+    /// version.
+    pub fn load(tcx: TyCtxt<'tcx>, cache_dir: impl AsRef<Path>) -> Self {
+        decode_from_file(tcx, cache_file_path(tcx, cache_dir.as_ref())).unwrap_or_default()
+    }
+
+    /// Builds the cache from the uses of every collected node.
+    pub(super) fn new(
+        tcx: TyCtxt<'tcx>,
+        used_items: &FxHashMap<Node<'tcx>, Vec<Node<'tcx>>>,
+    ) -> Self {
+        let uses = used_items
+            .iter()
+            .filter_map(|(node, used_items)| {
+                Some(CachedUses {
+                    node: CachedNode::new(*node)?,
+                    name: node.item().to_string(),
+                    fingerprint: item_fingerprint(tcx, node.item()),
+                    used_items: used_items
+                        .iter()
+                        .map(|used_item| CachedNode::new(*used_item))
+                        .collect::<Option<_>>()?,
+                })
+            })
+            .collect();
+        IncrementalCache { uses }
+    }
+
+    /// Returns the number of nodes whose uses are cached.
+    pub fn len(&self) -> usize {
+        self.uses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uses.is_empty()
+    }
+
+    /// Saves the cache into the directory, replacing the caches written by the local crate for
+    /// other definitions.
+    pub fn save(&self, tcx: TyCtxt<'tcx>, cache_dir: impl AsRef<Path>) {
+        let cache_dir = cache_dir.as_ref();
+        fs::create_dir_all(cache_dir).expect("failed to create cache dir");
+        let prefix = cache_file_prefix(tcx);
+        for entry in fs::read_dir(cache_dir).into_iter().flatten().flatten() {
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+        encode_to_file(tcx, cache_file_path(tcx, cache_dir), self)
+            .expect("failed to write the incremental cache");
+    }
+
+    /// Returns the uses of the nodes whose items did not change since the cache was written.
+    pub(super) fn unchanged_uses(
+        &self,
+        tcx: TyCtxt<'tcx>,
+    ) -> FxHashMap<Node<'tcx>, Vec<Node<'tcx>>> {
+        self.uses
+            .iter()
+            .filter(|cached| {
+                let item = cached.node.item.item();
+                item.to_string() == cached.name && item_fingerprint(tcx, item) == cached.fingerprint
+            })
+            .map(|cached| {
+                (
+                    cached.node.node(),
+                    cached
+                        .used_items
+                        .iter()
+                        .map(|used_item| used_item.node())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
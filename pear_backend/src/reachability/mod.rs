@@ -1,3 +1,9 @@
 mod collector;
+mod incremental;
+mod runtime_roots;
+mod validate;
 
 pub use collector::*;
+pub use incremental::*;
+pub use runtime_roots::*;
+pub use validate::*;
@@ -0,0 +1,38 @@
+use itertools::Itertools;
+use rustc_ast::expand::allocator::{global_fn_name, ALLOCATOR_METHODS};
+use rustc_hir::def::DefKind;
+use rustc_hir::def_id::{DefId, LOCAL_CRATE};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::sym;
+
+/// Returns the local functions that are called by the runtime rather than through ordinary calls,
+/// so they are never reached from the entries: the allocator shims generated for a
+/// `#[global_allocator]`, and the `#[panic_handler]` of a `no_std` crate. Collecting from them as
+/// additional roots makes the graphs reflect the allocation and panic paths.
+pub fn runtime_roots(tcx: TyCtxt) -> Vec<DefId> {
+    let mut roots = vec![];
+    if tcx.has_global_allocator(LOCAL_CRATE) {
+        // Shims are generated with the same names the allocation functions link against.
+        let shim_names = ALLOCATOR_METHODS
+            .iter()
+            .map(|method| global_fn_name(method.name))
+            .collect_vec();
+        roots.extend(
+            tcx.hir()
+                .body_owners()
+                .map(|local_def_id| local_def_id.to_def_id())
+                .filter(|def_id| {
+                    matches!(tcx.def_kind(*def_id), DefKind::Fn)
+                        && tcx.has_attr(*def_id, sym::rustc_std_internal_symbol)
+                        && shim_names.contains(&tcx.item_name(*def_id).to_string())
+                }),
+        );
+    }
+    // The panic handler of crates linking `std` is upstream and reached through ordinary calls.
+    if let Some(panic_impl) = tcx.lang_items().panic_impl()
+        && panic_impl.is_local()
+    {
+        roots.push(panic_impl);
+    }
+    roots
+}
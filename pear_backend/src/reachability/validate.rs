@@ -0,0 +1,96 @@
+use rustc_data_structures::fx::FxHashSet;
+use rustc_middle::mir::mono::MonoItem;
+use rustc_middle::ty::{InstanceDef, TyCtxt};
+use serde::Serialize;
+
+use crate::reachability::Node;
+
+/// How the collected mono items relate to the ones collected by rustc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MonoItemRelation {
+    Equal,
+    /// Some items collected by rustc are missing from our collection.
+    Subset,
+    /// Some collected items are not known to rustc.
+    Superset,
+    Diverging,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonoItemDiscrepancies {
+    /// Items collected by rustc for functions we reached, but not collected by us.
+    pub missing: Vec<String>,
+    /// Items collected by us that rustc would codegen in this crate but did not collect.
+    pub extra: Vec<String>,
+}
+
+impl MonoItemDiscrepancies {
+    pub fn relation(&self) -> MonoItemRelation {
+        match (self.missing.is_empty(), self.extra.is_empty()) {
+            (true, true) => MonoItemRelation::Equal,
+            (false, true) => MonoItemRelation::Subset,
+            (true, false) => MonoItemRelation::Superset,
+            (false, false) => MonoItemRelation::Diverging,
+        }
+    }
+}
+
+/// Compares the collected mono items against the ones rustc collects for codegen of the local
+/// crate. Since rustc only collects the items it codegens locally, our items are restricted to
+/// those, and rustc's items are restricted to the definitions we reached. Dead code is only
+/// collected by rustc with `-Clink-dead-code`.
+pub fn validate_against_monomorphization<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    items: &FxHashSet<Node<'tcx>>,
+) -> MonoItemDiscrepancies {
+    let (_, codegen_units) = tcx.collect_and_partition_mono_items(());
+    let rustc_items: FxHashSet<MonoItem<'tcx>> = codegen_units
+        .iter()
+        .flat_map(|codegen_unit| codegen_unit.items().keys().copied())
+        .collect();
+
+    let our_items: FxHashSet<MonoItem<'tcx>> = items
+        .iter()
+        .map(|node| node.item())
+        .filter(|item| is_codegened_locally(tcx, *item))
+        .collect();
+    let reached_def_ids: FxHashSet<_> = our_items.iter().map(|item| item.def_id()).collect();
+
+    let mut missing = rustc_items
+        .iter()
+        .filter(|item| reached_def_ids.contains(&item.def_id()) && !our_items.contains(item))
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>();
+    let mut extra = our_items
+        .iter()
+        .filter(|item| !rustc_items.contains(item))
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>();
+    missing.sort();
+    extra.sort();
+
+    MonoItemDiscrepancies { missing, extra }
+}
+
+/// Mirrors rustc's decision on whether the item ends up in the codegen units of the local crate.
+fn is_codegened_locally<'tcx>(tcx: TyCtxt<'tcx>, item: MonoItem<'tcx>) -> bool {
+    match item {
+        MonoItem::Fn(instance) => {
+            let def_id = instance.def_id();
+            if matches!(
+                instance.def,
+                InstanceDef::Virtual(..) | InstanceDef::Intrinsic(..)
+            ) || tcx.is_foreign_item(def_id)
+            {
+                false
+            } else if def_id.is_local() {
+                true
+            } else {
+                instance.upstream_monomorphization(tcx).is_none()
+                    && !tcx.is_reachable_non_generic(def_id)
+            }
+        }
+        MonoItem::Static(def_id) => def_id.is_local(),
+        MonoItem::GlobalAsm(..) => true,
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeSet;
+
+use rustc_middle::ty::TyCtxt;
+use serde::Serialize;
+
+use crate::refiner::{
+    portable::{PortableRefinedNode, PortableRefinedUsageGraph},
+    RefinedUsageGraph,
+};
+
+/// Changes of a refined graph against a baseline. Instances are named by their display strings,
+/// and edges by the display strings of the caller and of each callee. Edges to unresolved calls
+/// are named by the unresolved callee or function pointer signature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GraphDiff {
+    pub added_instances: Vec<String>,
+    pub removed_instances: Vec<String>,
+    pub added_edges: Vec<(String, String)>,
+    pub removed_edges: Vec<(String, String)>,
+    /// Edges of calls that are ambiguous (refined to several instances or unresolved) now, but
+    /// were not in the baseline.
+    pub newly_ambiguous: Vec<(String, String)>,
+}
+
+impl GraphDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == GraphDiff::default()
+    }
+}
+
+impl PortableRefinedNode {
+    fn callees(&self) -> Vec<&String> {
+        match self {
+            Self::Concrete { instance, .. } => vec![instance],
+            Self::Refined { instances, .. } => instances.iter().collect(),
+            Self::Unresolved { callee, .. } => vec![callee],
+            Self::UnresolvedFnPtr { sig, .. } => vec![sig],
+        }
+    }
+
+    fn is_ambiguous(&self) -> bool {
+        match self {
+            Self::Concrete { .. } => false,
+            Self::Refined { instances, .. } => instances.len() > 1,
+            Self::Unresolved { .. } | Self::UnresolvedFnPtr { .. } => true,
+        }
+    }
+}
+
+impl PortableRefinedUsageGraph {
+    fn edge_set(&self, ambiguous_only: bool) -> BTreeSet<(String, String)> {
+        self.edges
+            .iter()
+            .filter(|(_, node)| !ambiguous_only || node.is_ambiguous())
+            .flat_map(|(caller, node)| {
+                node.callees()
+                    .into_iter()
+                    .map(|callee| (caller.clone(), callee.clone()))
+            })
+            .collect()
+    }
+
+    fn instance_set(&self) -> BTreeSet<String> {
+        self.analyzed
+            .iter()
+            .map(|(instance, _)| instance.clone())
+            .chain([self.root.clone()])
+            .collect()
+    }
+
+    /// Compares the graph against the baseline.
+    pub fn diff(&self, baseline: &PortableRefinedUsageGraph) -> GraphDiff {
+        let (instances, baseline_instances) = (self.instance_set(), baseline.instance_set());
+        let (edges, baseline_edges) = (self.edge_set(false), baseline.edge_set(false));
+        let (ambiguous, baseline_ambiguous) = (self.edge_set(true), baseline.edge_set(true));
+        GraphDiff {
+            added_instances: instances.difference(&baseline_instances).cloned().collect(),
+            removed_instances: baseline_instances.difference(&instances).cloned().collect(),
+            added_edges: edges.difference(&baseline_edges).cloned().collect(),
+            removed_edges: baseline_edges.difference(&edges).cloned().collect(),
+            newly_ambiguous: ambiguous.difference(&baseline_ambiguous).cloned().collect(),
+        }
+    }
+}
+
+impl<'tcx> RefinedUsageGraph<'tcx> {
+    /// Compares the graph against a baseline stored by an earlier run.
+    pub fn diff(&self, baseline: &PortableRefinedUsageGraph, tcx: TyCtxt<'tcx>) -> GraphDiff {
+        self.to_portable(tcx).diff(baseline)
+    }
+}
@@ -0,0 +1,163 @@
+use std::{collections::VecDeque, hash::Hash};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Returns the number of calls needed to get from the root to every node reachable from it.
+pub(crate) fn depths_from<N, I>(root: N, successors: impl Fn(N) -> I) -> FxHashMap<N, usize>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = N>,
+{
+    let mut depths = FxHashMap::from_iter([(root, 0)]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(node) = queue.pop_front() {
+        let depth = depths[&node];
+        for successor in successors(node) {
+            if !depths.contains_key(&successor) {
+                depths.insert(successor, depth + 1);
+                queue.push_back(successor);
+            }
+        }
+    }
+    depths
+}
+
+/// Returns one of the shortest paths from the root to the target as the nodes after the root, each
+/// paired with the label of the edge leading to it, or `None` if the target is not reachable.
+pub(crate) fn shortest_path_to<N, E, I>(
+    root: N,
+    target: N,
+    successors: impl Fn(N) -> I,
+) -> Option<Vec<(N, E)>>
+where
+    N: Copy + Eq + Hash,
+    E: Copy,
+    I: IntoIterator<Item = (N, E)>,
+{
+    let mut predecessors: FxHashMap<N, (N, E)> = FxHashMap::default();
+    let mut queue = VecDeque::from([root]);
+    while let Some(node) = queue.pop_front() {
+        if node == target {
+            break;
+        }
+        for (successor, edge) in successors(node) {
+            if successor != root && !predecessors.contains_key(&successor) {
+                predecessors.insert(successor, (node, edge));
+                queue.push_back(successor);
+            }
+        }
+    }
+
+    if target != root && !predecessors.contains_key(&target) {
+        return None;
+    }
+    let mut path = vec![];
+    let mut current = target;
+    while let Some((predecessor, edge)) = predecessors.get(&current) {
+        path.push((current, *edge));
+        current = *predecessor;
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// Returns the roots along with every node reachable from any of them.
+pub(crate) fn reachable_from<N, I>(roots: Vec<N>, successors: impl Fn(N) -> I) -> FxHashSet<N>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = N>,
+{
+    let mut reachable = FxHashSet::from_iter(roots.iter().copied());
+    let mut stack = roots;
+    while let Some(node) = stack.pop() {
+        for successor in successors(node) {
+            if reachable.insert(successor) {
+                stack.push(successor);
+            }
+        }
+    }
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adjacency lists of a graph over plain numbers, with the index of each edge as its label.
+    struct TestGraph {
+        edges: FxHashMap<u32, Vec<(u32, usize)>>,
+    }
+
+    impl TestGraph {
+        fn from_edges(edges: &[(u32, u32)]) -> Self {
+            let mut adjacency: FxHashMap<u32, Vec<(u32, usize)>> = FxHashMap::default();
+            for (index, (from, to)) in edges.iter().enumerate() {
+                adjacency.entry(*from).or_default().push((*to, index));
+            }
+            Self { edges: adjacency }
+        }
+
+        fn labeled_successors(&self, node: u32) -> Vec<(u32, usize)> {
+            self.edges.get(&node).cloned().unwrap_or_default()
+        }
+
+        fn successors(&self, node: u32) -> Vec<u32> {
+            self.labeled_successors(node)
+                .into_iter()
+                .map(|(successor, _)| successor)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn depths_use_the_shortest_distance() {
+        // 0 -> 1 -> 2 -> 3 with a shortcut 0 -> 2.
+        let graph = TestGraph::from_edges(&[(0, 1), (1, 2), (2, 3), (0, 2)]);
+        let depths = depths_from(0, |node| graph.successors(node));
+        assert_eq!(depths[&0], 0);
+        assert_eq!(depths[&1], 1);
+        assert_eq!(depths[&2], 1);
+        assert_eq!(depths[&3], 2);
+    }
+
+    #[test]
+    fn depths_terminate_on_cycles() {
+        let graph = TestGraph::from_edges(&[(0, 1), (1, 2), (2, 0)]);
+        let depths = depths_from(0, |node| graph.successors(node));
+        assert_eq!(depths.len(), 3);
+        assert_eq!(depths.values().max(), Some(&2));
+    }
+
+    #[test]
+    fn shortest_path_takes_the_fewest_edges() {
+        // Diamond with a longer detour through 4.
+        let graph = TestGraph::from_edges(&[(0, 1), (0, 4), (4, 2), (1, 3), (2, 3), (0, 2)]);
+        let path = shortest_path_to(0, 3, |node| graph.labeled_successors(node)).unwrap();
+        assert_eq!(path.len(), 2);
+        assert_eq!(path.last(), Some(&(3, 3)));
+        assert_eq!(path[0], (1, 0));
+    }
+
+    #[test]
+    fn shortest_path_to_the_root_is_empty() {
+        let graph = TestGraph::from_edges(&[(0, 1), (1, 0)]);
+        let path = shortest_path_to(0, 0, |node| graph.labeled_successors(node));
+        assert_eq!(path, Some(vec![]));
+    }
+
+    #[test]
+    fn shortest_path_to_an_unreachable_node_is_none() {
+        let graph = TestGraph::from_edges(&[(0, 1), (2, 3)]);
+        assert_eq!(
+            shortest_path_to(0, 3, |node| graph.labeled_successors(node)),
+            None
+        );
+    }
+
+    #[test]
+    fn reachable_covers_every_root() {
+        let graph = TestGraph::from_edges(&[(0, 1), (1, 2), (2, 1), (5, 6), (7, 8)]);
+        let reachable = reachable_from(vec![0, 5], |node| graph.successors(node));
+        assert_eq!(reachable, FxHashSet::from_iter([0, 1, 2, 5, 6]));
+    }
+}
@@ -1,4 +1,9 @@
+mod diff;
+mod graph;
+mod portable;
 mod refiner;
-mod utils;
+pub(crate) mod utils;
 
+pub use diff::GraphDiff;
+pub use portable::PortableRefinedUsageGraph;
 pub use refiner::*;
@@ -0,0 +1,428 @@
+use itertools::Itertools;
+use rustc_data_structures::fingerprint::Fingerprint;
+use rustc_hash::FxHashMap;
+use rustc_hir::{
+    def_id::{DefPathHash, LOCAL_CRATE},
+    LangItem,
+};
+use rustc_middle::{
+    mir::{mono::MonoItem, InlineAsmOperand, TerminatorKind},
+    ty::{EarlyBinder, GenericArgs, Instance, ParamEnv, Ty, TyCtxt, TyKind},
+};
+use rustc_span::{Span, DUMMY_SP};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    reachability::{collect_from, CollectionBudget},
+    refiner::{
+        utils::unwrap_forwarding_shim, AmbiguousFnPtrCall, InstancePath, RefinedNode,
+        RefinedUsageGraph, ResolutionKind,
+    },
+    serialize::SourceLocation,
+    utils::{assert_lang_item, erase_regions_in_sig},
+};
+
+/// Refined usage graph that outlives the compiler session it was built in. Instances are keyed
+/// by their display strings and spans by their source locations, and both are resolved again
+/// against the `TyCtxt` of the session that loads the graph (see
+/// [`RefinedUsageGraph::from_portable`]). Closure captures are not preserved.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortableRefinedUsageGraph {
+    pub(super) root: String,
+    /// Halves of the def path hash of the root, which is stable across sessions.
+    root_def_path_hash: (u64, u64),
+    pub(super) edges: Vec<(String, PortableRefinedNode)>,
+    pub(super) analyzed: Vec<(String, bool)>,
+    ambiguous_fn_ptr_calls: Vec<PortableAmbiguousFnPtrCall>,
+    recursive_edges: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) enum PortableRefinedNode {
+    Concrete {
+        instance: String,
+        span: SourceLocation,
+        terminator_span: SourceLocation,
+    },
+    Refined {
+        instances: Vec<String>,
+        resolution: ResolutionKind,
+        span: SourceLocation,
+        terminator_span: SourceLocation,
+    },
+    Unresolved {
+        callee: String,
+        span: SourceLocation,
+        terminator_span: SourceLocation,
+    },
+    UnresolvedFnPtr {
+        sig: String,
+        span: SourceLocation,
+        terminator_span: SourceLocation,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct PortableAmbiguousFnPtrCall {
+    caller: String,
+    span: SourceLocation,
+    candidates: Vec<String>,
+}
+
+impl PortableRefinedNode {
+    fn new<'tcx>(node: &RefinedNode<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
+        let location = |span: &Span| SourceLocation::new(*span, tcx.sess.source_map());
+        match node {
+            RefinedNode::Concrete {
+                instance,
+                span,
+                terminator_span,
+            } => Self::Concrete {
+                instance: instance.to_string(),
+                span: location(span),
+                terminator_span: location(terminator_span),
+            },
+            RefinedNode::Refined {
+                instances,
+                resolution,
+                span,
+                terminator_span,
+            } => Self::Refined {
+                instances: instances
+                    .iter()
+                    .map(|instance| instance.to_string())
+                    .collect(),
+                resolution: *resolution,
+                span: location(span),
+                terminator_span: location(terminator_span),
+            },
+            RefinedNode::Unresolved {
+                def_id,
+                args,
+                span,
+                terminator_span,
+            } => Self::Unresolved {
+                callee: tcx.def_path_str_with_args(*def_id, *args),
+                span: location(span),
+                terminator_span: location(terminator_span),
+            },
+            RefinedNode::UnresolvedFnPtr {
+                sig,
+                span,
+                terminator_span,
+            } => Self::UnresolvedFnPtr {
+                sig: sig.to_string(),
+                span: location(span),
+                terminator_span: location(terminator_span),
+            },
+        }
+    }
+
+    fn span(&self) -> &SourceLocation {
+        match self {
+            Self::Concrete { span, .. }
+            | Self::Refined { span, .. }
+            | Self::Unresolved { span, .. }
+            | Self::UnresolvedFnPtr { span, .. } => span,
+        }
+    }
+
+    fn terminator_span(&self) -> &SourceLocation {
+        match self {
+            Self::Concrete {
+                terminator_span, ..
+            }
+            | Self::Refined {
+                terminator_span, ..
+            }
+            | Self::Unresolved {
+                terminator_span, ..
+            }
+            | Self::UnresolvedFnPtr {
+                terminator_span, ..
+            } => terminator_span,
+        }
+    }
+
+    /// Resolves the node against the terminator of the caller it was refined from, which recovers
+    /// the spans along with the callee of concrete and unresolved calls. Candidates of refined
+    /// calls are looked up among the instances reachable from the root.
+    fn resolve<'tcx>(
+        &self,
+        caller: Instance<'tcx>,
+        reachable: &FxHashMap<String, Instance<'tcx>>,
+        tcx: TyCtxt<'tcx>,
+    ) -> Result<RefinedNode<'tcx>, String> {
+        let call_site = find_call_site(caller, self.span(), Some(self.terminator_span()), tcx)
+            .ok_or_else(|| format!("no call site of {self:?} in {caller}"))?;
+        let (span, terminator_span) = (call_site.span, call_site.terminator_span);
+        let node = match self {
+            Self::Concrete { instance, .. } => {
+                let TyKind::FnDef(def_id, args) = call_site.callee_ty.kind() else {
+                    return Err(format!("{instance} is not called directly in {caller}"));
+                };
+                let resolved = Instance::resolve(tcx, ParamEnv::reveal_all(), *def_id, *args)
+                    .ok()
+                    .flatten()
+                    .map(|resolved| unwrap_forwarding_shim(resolved, tcx))
+                    .filter(|resolved| resolved.to_string() == *instance)
+                    .ok_or_else(|| format!("{instance} is not the callee in {caller}"))?;
+                RefinedNode::Concrete {
+                    instance: resolved,
+                    span,
+                    terminator_span,
+                }
+            }
+            Self::Refined {
+                instances,
+                resolution,
+                ..
+            } => RefinedNode::Refined {
+                instances: instances
+                    .iter()
+                    .map(|instance| resolve_instance(instance, reachable))
+                    .collect::<Result<_, _>>()?,
+                resolution: *resolution,
+                span,
+                terminator_span,
+            },
+            Self::Unresolved { callee, .. } => match call_site.callee_ty.kind() {
+                TyKind::FnDef(def_id, args)
+                    if tcx.def_path_str_with_args(*def_id, *args) == *callee =>
+                {
+                    RefinedNode::Unresolved {
+                        def_id: *def_id,
+                        args: *args,
+                        span,
+                        terminator_span,
+                    }
+                }
+                _ => return Err(format!("{callee} is not the callee in {caller}")),
+            },
+            Self::UnresolvedFnPtr { sig, .. } => match call_site.callee_ty.kind() {
+                TyKind::FnPtr(poly_fn_sig)
+                    if erase_regions_in_sig(*poly_fn_sig, tcx).to_string() == *sig =>
+                {
+                    RefinedNode::UnresolvedFnPtr {
+                        sig: erase_regions_in_sig(*poly_fn_sig, tcx),
+                        span,
+                        terminator_span,
+                    }
+                }
+                _ => return Err(format!("{sig} is not the callee in {caller}")),
+            },
+        };
+        Ok(node)
+    }
+}
+
+/// Terminator the refiner created a node for, along with the spans it recorded for it.
+struct CallSite<'tcx> {
+    callee_ty: Ty<'tcx>,
+    span: Span,
+    terminator_span: Span,
+}
+
+/// Finds the terminator in the body of the caller whose spans, as recorded by the refiner, are
+/// at the given source locations. The callee type is instantiated with the caller.
+fn find_call_site<'tcx>(
+    caller: Instance<'tcx>,
+    span: &SourceLocation,
+    terminator_span: Option<&SourceLocation>,
+    tcx: TyCtxt<'tcx>,
+) -> Option<CallSite<'tcx>> {
+    let source_map = tcx.sess.source_map();
+    let body = tcx.instance_mir(caller.def);
+    body.basic_blocks
+        .iter()
+        .filter_map(|basic_block| basic_block.terminator.as_ref())
+        .flat_map(|terminator| {
+            let terminator_span = terminator.source_info.span;
+            let callees = match &terminator.kind {
+                TerminatorKind::Call { func, fn_span, .. } => vec![(func.ty(body, tcx), *fn_span)],
+                TerminatorKind::Drop { place, .. } => {
+                    let ty = place.ty(body, tcx).ty;
+                    let def_id = tcx.require_lang_item(LangItem::DropInPlace, None);
+                    let args = tcx.mk_args(&[ty.into()]);
+                    vec![(tcx.type_of(def_id).instantiate(tcx, args), DUMMY_SP)]
+                }
+                TerminatorKind::Assert { msg, .. } => {
+                    let def_id = tcx.require_lang_item(assert_lang_item(&**msg), None);
+                    vec![(tcx.type_of(def_id).instantiate_identity(), terminator_span)]
+                }
+                TerminatorKind::InlineAsm { operands, .. } => operands
+                    .iter()
+                    .filter_map(|operand| match operand {
+                        InlineAsmOperand::SymFn { value } => Some((value.const_.ty(), value.span)),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            callees.into_iter().map(move |(callee_ty, span)| CallSite {
+                callee_ty,
+                span,
+                terminator_span,
+            })
+        })
+        .find(|call_site| {
+            SourceLocation::new(call_site.span, source_map) == *span
+                && terminator_span.map_or(true, |terminator_span| {
+                    SourceLocation::new(call_site.terminator_span, source_map) == *terminator_span
+                })
+        })
+        .map(|call_site| CallSite {
+            callee_ty: caller.instantiate_mir_and_normalize_erasing_regions(
+                tcx,
+                ParamEnv::reveal_all(),
+                EarlyBinder::bind(call_site.callee_ty),
+            ),
+            ..call_site
+        })
+}
+
+fn resolve_instance<'tcx>(
+    instance: &str,
+    instances: &FxHashMap<String, Instance<'tcx>>,
+) -> Result<Instance<'tcx>, String> {
+    instances
+        .get(instance)
+        .copied()
+        .ok_or_else(|| format!("{instance} is not reachable from the root"))
+}
+
+impl<'tcx> RefinedUsageGraph<'tcx> {
+    /// Converts the graph into a form that can be stored and loaded in a later compiler session.
+    pub fn to_portable(&self, tcx: TyCtxt<'tcx>) -> PortableRefinedUsageGraph {
+        let (hash_hi, hash_lo) = tcx.def_path_hash(self.root().def_id()).0.split();
+        PortableRefinedUsageGraph {
+            root: self.root().to_string(),
+            root_def_path_hash: (hash_hi.as_u64(), hash_lo.as_u64()),
+            edges: self
+                .forward_edges
+                .iter()
+                .flat_map(|(caller, nodes)| {
+                    nodes
+                        .iter()
+                        .map(|node| (caller.to_string(), PortableRefinedNode::new(node, tcx)))
+                })
+                .sorted_by_cached_key(|edge| format!("{edge:?}"))
+                .collect(),
+            analyzed: self
+                .analyzed
+                .iter()
+                .map(|(instance, analyzed)| (instance.to_string(), *analyzed))
+                .sorted()
+                .collect(),
+            ambiguous_fn_ptr_calls: self
+                .ambiguous_fn_ptr_calls
+                .iter()
+                .map(|call| PortableAmbiguousFnPtrCall {
+                    caller: call.caller.to_string(),
+                    span: SourceLocation::new(call.span, tcx.sess.source_map()),
+                    candidates: call
+                        .candidates
+                        .iter()
+                        .map(|candidate| candidate.to_string())
+                        .collect(),
+                })
+                .collect(),
+            recursive_edges: self
+                .recursive_edges
+                .iter()
+                .map(|(caller, callee)| (caller.to_string(), callee.to_string()))
+                .sorted()
+                .collect(),
+        }
+    }
+
+    /// Rebuilds the graph from its portable form, resolving the instances again in the current
+    /// session. The root must be the identity instance of a definition of the local crate, and the
+    /// other instances must be reachable from it, or must be callees found at the recorded call
+    /// sites.
+    pub fn from_portable(
+        portable: &PortableRefinedUsageGraph,
+        tcx: TyCtxt<'tcx>,
+    ) -> Result<Self, String> {
+        let (hash_hi, hash_lo) = portable.root_def_path_hash;
+        let root_def_path_hash = DefPathHash(Fingerprint::new(hash_hi, hash_lo));
+        // Looking up a hash that is not defined panics, so the root is searched for among the
+        // local definitions instead, which also rejects graphs written by other crates.
+        if root_def_path_hash.stable_crate_id() != tcx.stable_crate_id(LOCAL_CRATE) {
+            return Err(format!(
+                "{} is not defined in the local crate",
+                portable.root
+            ));
+        }
+        let root_def_id = tcx
+            .iter_local_def_id()
+            .map(|local_def_id| local_def_id.to_def_id())
+            .find(|def_id| tcx.def_path_hash(*def_id) == root_def_path_hash)
+            .ok_or_else(|| format!("{} is not defined in this session", portable.root))?;
+        let root = Instance::new(
+            root_def_id,
+            GenericArgs::identity_for_item(tcx, root_def_id),
+        );
+        if root.to_string() != portable.root {
+            return Err(format!("{} does not resolve to {root}", portable.root));
+        }
+
+        let (items, _) = collect_from(tcx, MonoItem::Fn(root), CollectionBudget::default(), &[]);
+        let reachable: FxHashMap<String, Instance<'tcx>> = items
+            .iter()
+            .filter_map(|node| match node.item() {
+                MonoItem::Fn(instance) => Some(instance),
+                MonoItem::Static(..) | MonoItem::GlobalAsm(..) => None,
+            })
+            .flat_map(|instance| [instance, unwrap_forwarding_shim(instance, tcx)])
+            .chain([root])
+            .map(|instance| (instance.to_string(), instance))
+            .collect();
+
+        let mut graph = Self::new(root);
+        let mut instances = FxHashMap::from_iter([(portable.root.clone(), root)]);
+        for (caller, node) in portable.edges.iter() {
+            let caller = resolve_instance(caller, &instances)
+                .or_else(|_| resolve_instance(caller, &reachable))?;
+            let node = node.resolve(caller, &reachable, tcx)?;
+            instances.extend(
+                node.instances()
+                    .into_iter()
+                    .map(|instance| (instance.to_string(), instance)),
+            );
+            graph.add_edge(&caller, &node);
+        }
+
+        for (instance, analyzed) in portable.analyzed.iter() {
+            graph
+                .analyzed
+                .insert(resolve_instance(instance, &instances)?, *analyzed);
+        }
+        for call in portable.ambiguous_fn_ptr_calls.iter() {
+            let caller = resolve_instance(&call.caller, &instances)?;
+            let call_site = find_call_site(caller, &call.span, None, tcx)
+                .ok_or_else(|| format!("no ambiguous call at {:?} in {caller}", call.span))?;
+            graph.ambiguous_fn_ptr_calls.push(AmbiguousFnPtrCall {
+                caller,
+                span: call_site.span,
+                candidates: call
+                    .candidates
+                    .iter()
+                    .map(|candidate| resolve_instance(candidate, &instances))
+                    .collect::<Result<_, _>>()?,
+            });
+        }
+        for (caller, callee) in portable.recursive_edges.iter() {
+            graph.recursive_edges.insert((
+                resolve_instance(caller, &instances)?,
+                resolve_instance(callee, &instances)?,
+            ));
+        }
+        graph.instance_paths = graph
+            .instances()
+            .into_iter()
+            .map(|instance| (instance, InstancePath::new(instance, tcx)))
+            .collect();
+        Ok(graph)
+    }
+}
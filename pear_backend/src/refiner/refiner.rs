@@ -1,28 +1,54 @@
+use itertools::Itertools;
 use log::warn;
-use std::fs;
+use std::{
+    cell::OnceCell,
+    hash::{Hash, Hasher},
+    iter, mem,
+    rc::Rc,
+};
 
-use rustc_hash::{FxHashMap, FxHashSet};
-use rustc_hir::{def_id::DefId, LangItem};
+use rustc_hash::{FxHashMap, FxHashSet, FxHasher};
+use rustc_hir::{def::DefKind, def_id::DefId, LangItem};
 use rustc_middle::{
-    mir::{visit::Visitor, Body, Location, Terminator, TerminatorKind},
+    mir::{
+        visit::Visitor, Body, CastKind, InlineAsmOperand, Local, Location, Operand, ProjectionElem,
+        Rvalue, Terminator, TerminatorKind,
+    },
     ty::{
-        self, EarlyBinder, FnSig, GenericArgsRef, Instance, InstanceDef, ParamEnv, Ty, TyCtxt,
-        TyKind, TypeFoldable,
+        self, adjustment::PointerCoercion, EarlyBinder, FnSig, GenericArgsRef, Instance,
+        InstanceDef, ParamEnv, Ty, TyCtxt, TyKind, TypeFoldable, TypeVisitableExt,
     },
 };
 use rustc_span::{Span, DUMMY_SP};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    profiling::profile,
     reachability::{ImplType, Node, Usage},
-    refiner::utils::{fn_sig_eq_with_subtyping, is_intrinsic, is_virtual},
+    refiner::graph::{depths_from, reachable_from, shortest_path_to},
+    refiner::utils::{
+        assignments_to, fn_sig_eq_with_subtyping, has_body, is_virtual, unwrap_forwarding_shim,
+    },
     serialize::{
-        serialize_instance, serialize_instance_vec, serialize_refined_edges, serialize_span,
+        serialize_def_id, serialize_generic_args, serialize_instance, serialize_instance_flags,
+        serialize_instance_pairs, serialize_instance_paths, serialize_instance_types,
+        serialize_instance_vec, serialize_refined_edges, serialize_sig, serialize_span,
         serialize_transitive_refined_edges,
     },
-    utils::{erase_regions_in_sig, fn_trait_method_sig},
+    utils::{assert_lang_item, erase_regions_in_sig, fn_trait_method_sig},
 };
 
+/// How the candidates of a refined call were found.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResolutionKind {
+    /// Call through a function pointer, resolved by the signature.
+    FnPtr,
+    /// Call of a trait method through a vtable, resolved by the implementors.
+    Vtable,
+    /// Call of a closure or a function through a `Fn*` trait object, resolved by the signature.
+    FnTrait,
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize)]
 pub enum RefinedNode<'tcx> {
     Concrete {
@@ -36,6 +62,28 @@ pub enum RefinedNode<'tcx> {
     Refined {
         #[serde(serialize_with = "serialize_instance_vec")]
         instances: Vec<Instance<'tcx>>,
+        resolution: ResolutionKind,
+        #[serde(serialize_with = "serialize_span")]
+        span: Span,
+        #[serde(serialize_with = "serialize_span")]
+        terminator_span: Span,
+    },
+    /// Callee that could not be resolved to an instance under the current generic args, or a
+    /// virtual call that no collected implementor could be the target of.
+    Unresolved {
+        #[serde(serialize_with = "serialize_def_id")]
+        def_id: DefId,
+        #[serde(serialize_with = "serialize_generic_args")]
+        args: GenericArgsRef<'tcx>,
+        #[serde(serialize_with = "serialize_span")]
+        span: Span,
+        #[serde(serialize_with = "serialize_span")]
+        terminator_span: Span,
+    },
+    /// Call through a function pointer that no collected function could be the target of.
+    UnresolvedFnPtr {
+        #[serde(serialize_with = "serialize_sig")]
+        sig: FnSig<'tcx>,
         #[serde(serialize_with = "serialize_span")]
         span: Span,
         #[serde(serialize_with = "serialize_span")]
@@ -48,12 +96,16 @@ impl<'tcx> RefinedNode<'tcx> {
         match self {
             RefinedNode::Concrete { instance, .. } => vec![instance.clone()],
             RefinedNode::Refined { instances, .. } => instances.clone(),
+            RefinedNode::Unresolved { .. } | RefinedNode::UnresolvedFnPtr { .. } => vec![],
         }
     }
 
     pub fn span(&self) -> Span {
         match self {
-            Self::Concrete { span, .. } | Self::Refined { span, .. } => *span,
+            Self::Concrete { span, .. }
+            | Self::Refined { span, .. }
+            | Self::Unresolved { span, .. }
+            | Self::UnresolvedFnPtr { span, .. } => *span,
         }
     }
 
@@ -64,6 +116,12 @@ impl<'tcx> RefinedNode<'tcx> {
             }
             | Self::Refined {
                 terminator_span, ..
+            }
+            | Self::Unresolved {
+                terminator_span, ..
+            }
+            | Self::UnresolvedFnPtr {
+                terminator_span, ..
             } => *terminator_span,
         }
     }
@@ -71,6 +129,28 @@ impl<'tcx> RefinedNode<'tcx> {
     pub fn is_refined(&self) -> bool {
         matches!(self, RefinedNode::Refined { .. })
     }
+
+    /// Returns how the call was resolved along with the number of candidates for refined nodes.
+    /// A single candidate means the call is not ambiguous.
+    pub fn resolution(&self) -> Option<(ResolutionKind, usize)> {
+        match self {
+            RefinedNode::Refined {
+                instances,
+                resolution,
+                ..
+            } => Some((*resolution, instances.len())),
+            RefinedNode::Concrete { .. }
+            | RefinedNode::Unresolved { .. }
+            | RefinedNode::UnresolvedFnPtr { .. } => None,
+        }
+    }
+
+    pub fn is_unresolved(&self) -> bool {
+        matches!(
+            self,
+            RefinedNode::Unresolved { .. } | RefinedNode::UnresolvedFnPtr { .. }
+        )
+    }
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, Serialize)]
@@ -149,25 +229,216 @@ impl<'tcx> TransitiveRefinedSubGraph<'tcx> {
     }
 }
 
-#[derive(Debug, Serialize)]
+/// Options controlling how the refiner treats imprecise resolutions.
+#[derive(Clone, Debug, Default)]
+pub struct RefinerConfig {
+    /// Report every function pointer call that cannot be narrowed down to exactly one candidate.
+    pub require_unique_fn_ptr: bool,
+    /// Record the types of the variables captured by every closure in the graph.
+    pub closure_captures: bool,
+    /// Resolve a trait object method call to a single impl when the concrete type behind the
+    /// trait object can be traced back to its unsizing cast.
+    pub narrow_dyn_calls: bool,
+}
+
+/// Function pointer call that resolved to multiple signature-matching candidates.
+#[derive(Clone, Debug, Serialize)]
+pub struct AmbiguousFnPtrCall<'tcx> {
+    #[serde(serialize_with = "serialize_instance")]
+    pub(super) caller: Instance<'tcx>,
+    #[serde(serialize_with = "serialize_span")]
+    pub(super) span: Span,
+    #[serde(serialize_with = "serialize_instance_vec")]
+    pub(super) candidates: Vec<Instance<'tcx>>,
+}
+
+impl<'tcx> AmbiguousFnPtrCall<'tcx> {
+    pub fn caller(&self) -> Instance<'tcx> {
+        self.caller
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn candidates(&self) -> &Vec<Instance<'tcx>> {
+        &self.candidates
+    }
+}
+
+/// Chain of calls from the root of a graph, where every hop is an instance along with the span it
+/// was called at. The root hop carries the span of its definition.
+#[derive(Clone, Debug)]
+pub struct GraphPath<'tcx> {
+    hops: Vec<(Instance<'tcx>, Span)>,
+}
+
+impl<'tcx> GraphPath<'tcx> {
+    pub fn hops(&self) -> &[(Instance<'tcx>, Span)] {
+        &self.hops
+    }
+
+    /// Renders the path as `def_path @ file:line` hops joined by arrows.
+    pub fn display(&self, tcx: TyCtxt<'tcx>) -> String {
+        let source_map = tcx.sess.source_map();
+        self.hops
+            .iter()
+            .map(|(instance, span)| {
+                let def_path_str = tcx.def_path_str(instance.def_id());
+                if span.is_dummy() {
+                    def_path_str
+                } else {
+                    let loc = source_map.lookup_char_pos(span.lo());
+                    format!(
+                        "{def_path_str} @ {}:{}",
+                        loc.file.name.prefer_local(),
+                        loc.line
+                    )
+                }
+            })
+            .join(" -> ")
+    }
+}
+
+/// Why the refiner skipped a call.
+///
+/// Both kinds guard against MIR the refiner does not expect rather than against a known input:
+/// callees of well-typed MIR are function items or pointers, and virtual callees are resolved to
+/// their candidates before this check. No program in the test crate produces either of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum RefinerErrorKind {
+    /// The called operand is neither a function item nor a function pointer.
+    UnexpectedCalleeType,
+    /// The callee resolved to a virtual instance, which has no body to refine.
+    VirtualCallee,
+}
+
+/// Call that could not be refined, along with the call stack leading to it. The edge for the call
+/// is left out of the graph, while the rest of the graph is still refined.
+#[derive(Clone, Debug, Serialize)]
+pub struct RefinerError<'tcx> {
+    kind: RefinerErrorKind,
+    #[serde(serialize_with = "serialize_instance")]
+    caller: Instance<'tcx>,
+    #[serde(serialize_with = "serialize_span")]
+    span: Span,
+    call_stack: Vec<StackItem<'tcx>>,
+}
+
+impl<'tcx> RefinerError<'tcx> {
+    pub fn kind(&self) -> RefinerErrorKind {
+        self.kind
+    }
+
+    pub fn caller(&self) -> Instance<'tcx> {
+        self.caller
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Source-level identity of an instance, which its display string does not fully convey.
+#[derive(Clone, Debug, Serialize)]
+pub struct InstancePath {
+    def_path: String,
+    crate_name: String,
+}
+
+impl InstancePath {
+    pub(super) fn new<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Self {
+        let def_id = instance.def_id();
+        Self {
+            def_path: tcx.def_path_str(def_id),
+            crate_name: tcx.crate_name(def_id.krate).to_string(),
+        }
+    }
+
+    pub fn def_path(&self) -> &str {
+        &self.def_path
+    }
+
+    pub fn crate_name(&self) -> &str {
+        &self.crate_name
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct RefinedUsageGraph<'tcx> {
     #[serde(serialize_with = "serialize_instance")]
     root: Instance<'tcx>,
 
+    // Roots of the graphs merged into this one, see `RefinedUsageGraph::merge`.
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_instance_vec"
+    )]
+    merged_roots: Vec<Instance<'tcx>>,
+
     // Maps every instance to the instances used by it.
     #[serde(serialize_with = "serialize_refined_edges")]
-    forward_edges: FxHashMap<Instance<'tcx>, FxHashSet<RefinedNode<'tcx>>>,
+    pub(super) forward_edges: FxHashMap<Instance<'tcx>, FxHashSet<RefinedNode<'tcx>>>,
 
     #[serde(skip_serializing)]
     backward_edges: FxHashMap<RefinedNode<'tcx>, FxHashSet<Instance<'tcx>>>,
+
+    // Whether the refiner descended into the body of each instance in the graph.
+    #[serde(serialize_with = "serialize_instance_flags")]
+    pub(super) analyzed: FxHashMap<Instance<'tcx>, bool>,
+
+    // Function pointer calls that could not be narrowed down to a single candidate, only recorded
+    // when `RefinerConfig::require_unique_fn_ptr` is set.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) ambiguous_fn_ptr_calls: Vec<AmbiguousFnPtrCall<'tcx>>,
+
+    // Upvar types of every closure in the graph, only recorded when
+    // `RefinerConfig::closure_captures` is set.
+    #[serde(
+        skip_serializing_if = "FxHashMap::is_empty",
+        serialize_with = "serialize_instance_types"
+    )]
+    closure_captures: FxHashMap<Instance<'tcx>, Vec<Ty<'tcx>>>,
+
+    // Def path and crate name of every instance in the graph, keyed by its display string when
+    // serialized so that existing consumers of the instance strings keep working.
+    #[serde(
+        skip_serializing_if = "FxHashMap::is_empty",
+        serialize_with = "serialize_instance_paths"
+    )]
+    pub(super) instance_paths: FxHashMap<Instance<'tcx>, InstancePath>,
+
+    // Calls from a caller to a callee that is already on the call stack, closing a cycle.
+    #[serde(
+        rename = "cycles",
+        skip_serializing_if = "FxHashSet::is_empty",
+        serialize_with = "serialize_instance_pairs"
+    )]
+    pub(super) recursive_edges: FxHashSet<(Instance<'tcx>, Instance<'tcx>)>,
+
+    // Calls that were skipped because they could not be refined.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<RefinerError<'tcx>>,
+
+    // Parents of every child, computed once on the first subgraph query.
+    #[serde(skip_serializing)]
+    parents: OnceCell<FxHashMap<Instance<'tcx>, Vec<TransitiveRefinedNode<'tcx>>>>,
 }
 
 impl<'tcx> RefinedUsageGraph<'tcx> {
-    fn new(root: Instance<'tcx>) -> Self {
+    pub(super) fn new(root: Instance<'tcx>) -> Self {
         Self {
             root,
+            merged_roots: vec![],
             forward_edges: FxHashMap::default(),
             backward_edges: FxHashMap::default(),
+            analyzed: FxHashMap::from_iter([(root, true)]),
+            ambiguous_fn_ptr_calls: vec![],
+            closure_captures: FxHashMap::default(),
+            instance_paths: FxHashMap::default(),
+            recursive_edges: FxHashSet::default(),
+            errors: vec![],
+            parents: OnceCell::new(),
         }
     }
 
@@ -175,14 +446,115 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
         self.root
     }
 
-    pub fn get_forward_edges(&self, instance: &Instance<'tcx>) -> FxHashSet<RefinedNode<'tcx>> {
+    /// Returns the root along with the roots of all graphs merged into this one.
+    pub fn roots(&self) -> Vec<Instance<'tcx>> {
+        iter::once(self.root)
+            .chain(self.merged_roots.iter().copied())
+            .collect()
+    }
+
+    /// Merges another graph into this one, so that it has several roots. Queries relative to the
+    /// root, such as `depth` and `path_to`, keep using the root of this graph.
+    pub fn merge(&mut self, other: RefinedUsageGraph<'tcx>) {
+        // The merged edges invalidate the cached parents.
+        self.parents.take();
+
+        for root in other.roots() {
+            if !self.roots().contains(&root) {
+                self.merged_roots.push(root);
+            }
+        }
+        for (instance, nodes) in other.forward_edges {
+            self.forward_edges
+                .entry(instance)
+                .or_default()
+                .extend(nodes);
+        }
+        for (node, instances) in other.backward_edges {
+            self.backward_edges
+                .entry(node)
+                .or_default()
+                .extend(instances);
+        }
+        for (instance, analyzed) in other.analyzed {
+            *self.analyzed.entry(instance).or_default() |= analyzed;
+        }
+        self.ambiguous_fn_ptr_calls
+            .extend(other.ambiguous_fn_ptr_calls);
+        self.closure_captures.extend(other.closure_captures);
+        self.instance_paths.extend(other.instance_paths);
+        self.recursive_edges.extend(other.recursive_edges);
+        self.errors.extend(other.errors);
+    }
+
+    /// Returns true if the refiner descended into the body of the instance.
+    pub fn is_analyzed(&self, instance: &Instance<'tcx>) -> bool {
+        self.analyzed.get(instance).copied().unwrap_or(false)
+    }
+
+    pub fn ambiguous_fn_ptr_calls(&self) -> &Vec<AmbiguousFnPtrCall<'tcx>> {
+        &self.ambiguous_fn_ptr_calls
+    }
+
+    /// Returns the upvar types of the closure instance if closure captures were recorded.
+    pub fn closure_captures(&self, instance: &Instance<'tcx>) -> Option<&Vec<Ty<'tcx>>> {
+        self.closure_captures.get(instance)
+    }
+
+    /// Returns the def path and crate name of the instance, if it was resolved by the refiner.
+    pub fn instance_path(&self, instance: &Instance<'tcx>) -> Option<&InstancePath> {
+        self.instance_paths.get(instance)
+    }
+
+    /// Returns the calls that closed a cycle, as pairs of the caller and the recursive callee.
+    pub fn recursive_edges(&self) -> Vec<(Instance<'tcx>, Instance<'tcx>)> {
+        self.recursive_edges.iter().copied().collect()
+    }
+
+    /// Returns the calls that were skipped because they could not be refined.
+    pub fn errors(&self) -> &Vec<RefinerError<'tcx>> {
+        &self.errors
+    }
+
+    /// Returns the nodes used by the instance.
+    pub fn get_forward_edges(&self, instance: &Instance<'tcx>) -> Vec<RefinedNode<'tcx>> {
         self.forward_edges
             .get(instance)
-            .cloned()
+            .map(|nodes| nodes.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the instances using the node.
+    pub fn get_backward_edges(&self, node: &RefinedNode<'tcx>) -> Vec<Instance<'tcx>> {
+        self.backward_edges
+            .get(node)
+            .map(|instances| instances.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns every caller of the instance along with the span of the call site.
+    pub fn callers_of(&self, instance: Instance<'tcx>) -> Vec<(Instance<'tcx>, Span)> {
+        self.parents_of(&instance)
+            .iter()
+            .map(|parent| (parent.node(), parent.span()))
+            .unique()
+            .collect()
+    }
+
+    /// Returns the direct parents of the instance, which carry the refinement status of the call.
+    /// The parents of all instances are computed once and cached until the graph changes.
+    fn parents_of(&self, instance: &Instance<'tcx>) -> &[TransitiveRefinedNode<'tcx>] {
+        self.parents
+            .get_or_init(|| self.precalculate_parents())
+            .get(instance)
+            .map(Vec::as_slice)
             .unwrap_or_default()
     }
 
-    fn add_edge(&mut self, from: &Instance<'tcx>, to: &RefinedNode<'tcx>) {
+    pub(super) fn add_edge(&mut self, from: &Instance<'tcx>, to: &RefinedNode<'tcx>) {
+        // Any new edge invalidates the cached parents.
+        self.parents.take();
+
         self.forward_edges
             .entry(from.clone())
             .or_default()
@@ -194,8 +566,62 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
             .insert(from.clone());
     }
 
+    /// Returns every edge of the graph as a pair of the caller and the node it uses.
+    pub fn edges(&self) -> impl Iterator<Item = (Instance<'tcx>, &RefinedNode<'tcx>)> + '_ {
+        self.forward_edges
+            .iter()
+            .flat_map(|(caller, nodes)| nodes.iter().map(move |node| (*caller, node)))
+    }
+
+    /// Returns the instances called by the instance, through any of the nodes it uses.
+    fn callees_of(&self, instance: Instance<'tcx>) -> impl Iterator<Item = Instance<'tcx>> + '_ {
+        self.forward_edges
+            .get(&instance)
+            .into_iter()
+            .flatten()
+            .flat_map(|node| node.instances())
+    }
+
+    /// Returns the largest number of calls needed to get from the root to any instance.
+    pub fn depth(&self) -> usize {
+        depths_from(self.root, |instance| self.callees_of(instance))
+            .into_values()
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns one of the shortest chains of calls from the root to the instance, if it is reachable.
+    pub fn path_to(&self, target: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Option<GraphPath<'tcx>> {
+        let calls = shortest_path_to(self.root, target, |instance| {
+            self.forward_edges
+                .get(&instance)
+                .into_iter()
+                .flatten()
+                .flat_map(|node| {
+                    let span = node.span();
+                    node.instances()
+                        .into_iter()
+                        .map(move |callee| (callee, span))
+                })
+        })?;
+        let hops = iter::once((self.root, tcx.def_span(self.root.def_id())))
+            .chain(calls)
+            .collect();
+        Some(GraphPath { hops })
+    }
+
+    /// Returns one of the shortest chains of calls from the root to any instance of the function
+    /// with the def path, as printed by `def_path_str`, if one is reachable.
+    pub fn reaches(&self, target_def_path: &str, tcx: TyCtxt<'tcx>) -> Option<GraphPath<'tcx>> {
+        self.instances()
+            .into_iter()
+            .filter(|instance| tcx.def_path_str(instance.def_id()) == target_def_path)
+            .filter_map(|instance| self.path_to(instance, tcx))
+            .min_by_key(|path| path.hops().len())
+    }
+
     pub fn instances(&self) -> FxHashSet<Instance<'tcx>> {
-        let mut instances = FxHashSet::from_iter([self.root]);
+        let mut instances = FxHashSet::from_iter(self.roots());
         for refined_nodes in self.forward_edges.values() {
             instances.extend(
                 refined_nodes
@@ -206,6 +632,100 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
         instances
     }
 
+    /// Renders the graph as a DOT digraph, drawing refined edges dashed and concrete edges solid.
+    pub fn to_dot(&self, tcx: TyCtxt<'tcx>) -> String {
+        let dot_id = |label: &str| {
+            let mut hasher = FxHasher::default();
+            label.hash(&mut hasher);
+            format!("n{:016x}", hasher.finish())
+        };
+        let escape = |label: &str| label.replace('\\', "\\\\").replace('"', "\\\"");
+
+        let mut nodes = vec![];
+        let mut edges = vec![];
+        let mut add_node = |label: String, style: &str| {
+            let id = dot_id(&label);
+            nodes.push(format!("    {id} [label=\"{}\"{style}];", escape(&label)));
+            id
+        };
+
+        let root_id = add_node(self.root.to_string(), "");
+        for (caller, refined_nodes) in self.forward_edges.iter() {
+            let caller_id = add_node(caller.to_string(), "");
+            for refined_node in refined_nodes {
+                match refined_node {
+                    RefinedNode::Concrete { instance, .. } => {
+                        let callee_id = add_node(instance.to_string(), "");
+                        edges.push(format!("    {caller_id} -> {callee_id};"));
+                    }
+                    RefinedNode::Refined { instances, .. } => {
+                        for instance in instances {
+                            let callee_id = add_node(instance.to_string(), "");
+                            edges.push(format!("    {caller_id} -> {callee_id} [style=dashed];"));
+                        }
+                    }
+                    RefinedNode::Unresolved { def_id, args, .. } => {
+                        let callee_id =
+                            add_node(tcx.def_path_str_with_args(*def_id, args), ", style=dotted");
+                        edges.push(format!("    {caller_id} -> {callee_id} [style=dotted];"));
+                    }
+                    RefinedNode::UnresolvedFnPtr { sig, .. } => {
+                        let callee_id = add_node(format!("{sig}"), ", style=dotted");
+                        edges.push(format!("    {caller_id} -> {callee_id} [style=dotted];"));
+                    }
+                }
+            }
+        }
+        nodes.push(format!("    {root_id} [shape=box];"));
+
+        nodes.sort();
+        nodes.dedup();
+        edges.sort();
+        edges.dedup();
+        format!(
+            "digraph refined {{\n{}\n{}\n}}\n",
+            nodes.join("\n"),
+            edges.join("\n")
+        )
+    }
+
+    /// Removes all nodes that only use matching instances along with the instances only reachable
+    /// through them.
+    pub fn remove_instances(&mut self, predicate: impl Fn(&Instance<'tcx>) -> bool) {
+        let is_removed = |node: &RefinedNode<'tcx>| {
+            let instances = node.instances();
+            !instances.is_empty() && instances.iter().all(&predicate)
+        };
+        for nodes in self.forward_edges.values_mut() {
+            nodes.retain(|node| !is_removed(node));
+        }
+        self.backward_edges.retain(|node, _| !is_removed(node));
+        self.prune_unreachable();
+    }
+
+    /// Removes all instances and edges that are not reachable from any of the roots.
+    fn prune_unreachable(&mut self) {
+        let reachable = reachable_from(self.roots(), |instance| self.callees_of(instance));
+
+        self.forward_edges
+            .retain(|instance, _| reachable.contains(instance));
+        for callers in self.backward_edges.values_mut() {
+            callers.retain(|caller| reachable.contains(caller));
+        }
+        self.backward_edges.retain(|_, callers| !callers.is_empty());
+        self.analyzed
+            .retain(|instance, _| reachable.contains(instance));
+        self.closure_captures
+            .retain(|instance, _| reachable.contains(instance));
+        self.instance_paths
+            .retain(|instance, _| reachable.contains(instance));
+        self.recursive_edges
+            .retain(|(caller, callee)| reachable.contains(caller) && reachable.contains(callee));
+        self.ambiguous_fn_ptr_calls
+            .retain(|call| reachable.contains(&call.caller));
+        self.parents.take();
+    }
+
     /// Returns a map of children to their parents (callers) such that the direct parents carry the
     /// refinement status of the child.
     fn precalculate_parents(&self) -> FxHashMap<Instance<'tcx>, Vec<TransitiveRefinedNode<'tcx>>> {
@@ -227,23 +747,19 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
         tainted_parents
     }
 
+    /// Traverses the graph up from the child, not recurring into instances rejected by the filter.
     pub fn find_child_subgraph(
         &self,
         instance: &Instance<'tcx>,
-        filter: &Vec<String>,
-        tcx: TyCtxt<'tcx>,
+        filter: &dyn Fn(&Instance<'tcx>) -> bool,
     ) -> TransitiveRefinedSubGraph<'tcx> {
-        let tainted_parents: FxHashMap<Instance<'tcx>, Vec<TransitiveRefinedNode<'tcx>>> =
-            self.precalculate_parents();
         let mut subgraph = TransitiveRefinedSubGraph::new(*instance);
         let mut stack = vec![];
         let mut visited = FxHashSet::default();
         self.find_child_subgraph_rec(
             instance,
-            &filter,
-            tcx,
+            filter,
             false,
-            &tainted_parents,
             &mut stack,
             &mut subgraph,
             &mut visited,
@@ -255,36 +771,25 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
     fn find_child_subgraph_rec(
         &self,
         instance: &Instance<'tcx>,
-        filter: &Vec<String>,
-        tcx: TyCtxt<'tcx>,
+        filter: &dyn Fn(&Instance<'tcx>) -> bool,
         instance_refined: bool,
-        tainted_parents: &FxHashMap<Instance<'tcx>, Vec<TransitiveRefinedNode<'tcx>>>,
         stack: &mut Vec<Instance<'tcx>>,
         subgraph: &mut TransitiveRefinedSubGraph<'tcx>,
         visited: &mut FxHashSet<(Instance<'tcx>, bool, Option<TransitiveRefinedNode<'tcx>>)>,
         crate_edge: Option<TransitiveRefinedNode<'tcx>>,
     ) {
-        // Skip if we've been to this instance.
-        if visited.contains(&(*instance, instance_refined, crate_edge)) {
+        // Skip if we've been to this instance, marking it as visited otherwise.
+        if !visited.insert((*instance, instance_refined, crate_edge)) {
             return;
         }
-        // Mark this instance as visited.
-        visited.insert((*instance, instance_refined, crate_edge));
 
-        // Don't recur into crates that are filtered.
-        if filter.iter().any(|filtered_item| {
-            // Match filtered item's crate.
-            tcx.crate_name(instance.def_id().krate)
-                .to_string()
-                .contains(filtered_item)
-        }) {
+        // Don't recur into instances that are filtered.
+        if filter(instance) {
             return;
         }
 
-        // Since we have precalculated `tainted_parents`, these nodes already reflect the status of
-        // the child's instance.
-        let parents: Vec<TransitiveRefinedNode<'tcx>> =
-            tainted_parents.get(&instance).cloned().unwrap_or(vec![]);
+        // The parents already reflect the status of the child's instance.
+        let parents = self.parents_of(instance);
 
         // Base case reached - at top-level function.
         if parents.is_empty() {
@@ -301,7 +806,7 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
                 _ => {}
             }
         }
-        for parent in parents {
+        for parent in parents.iter().copied() {
             // Each precalculated parent already carries the refinement status of its direct child,
             // but it may need to updated with the refinement status of a grandchild.
             let updated_parent_status = parent.is_refined || instance_refined;
@@ -322,9 +827,7 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
                 self.find_child_subgraph_rec(
                     &updated_parent.node,
                     filter,
-                    tcx,
                     updated_parent.is_refined,
-                    tainted_parents,
                     stack,
                     subgraph,
                     visited,
@@ -339,15 +842,30 @@ impl<'tcx> RefinedUsageGraph<'tcx> {
     pub fn find_reachable_edge_local_instances(
         &self,
         instance: Instance<'tcx>,
-        filter: &Vec<String>,
-        tcx: TyCtxt<'tcx>,
+        filter: &dyn Fn(&Instance<'tcx>) -> bool,
     ) -> Vec<TransitiveRefinedNode<'tcx>> {
-        let subgraph = self.find_child_subgraph(&instance, filter, tcx);
+        let subgraph = self.find_child_subgraph(&instance, filter);
         subgraph.crate_boundaries
     }
 }
 
-#[derive(Debug, Serialize)]
+/// Returns a filter rejecting instances from crates whose name contains any of the filtered items.
+pub fn crate_name_filter<'a, 'tcx>(
+    filter: &'a Vec<String>,
+    tcx: TyCtxt<'tcx>,
+) -> impl Fn(&Instance<'tcx>) -> bool + 'a
+where
+    'tcx: 'a,
+{
+    move |instance| {
+        let crate_name = tcx.crate_name(instance.def_id().krate).to_string();
+        filter
+            .iter()
+            .any(|filtered_item| crate_name.contains(filtered_item))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct StackItem<'tcx> {
     #[serde(serialize_with = "serialize_instance")]
     instance: Instance<'tcx>,
@@ -363,38 +881,73 @@ impl<'tcx> StackItem<'tcx> {
 
 pub struct RefinerVisitor<'tcx> {
     current_instance: Instance<'tcx>,
-    current_body: Body<'tcx>,
+    current_body: Rc<Body<'tcx>>,
+    // Bodies cloned for the instances visited so far, so that each one is only cloned once.
+    bodies: FxHashMap<InstanceDef<'tcx>, Rc<Body<'tcx>>>,
     reachable_indirect: FxHashSet<Node<'tcx>>,
     refined_usage_graph: RefinedUsageGraph<'tcx>,
     call_stack: Vec<StackItem<'tcx>>,
+    // Concrete types behind the trait object arguments of the current body, known from its caller.
+    known_arg_types: Vec<(Local, Ty<'tcx>)>,
+    // Calls that were already refined, along with the caller and the context it was visited in.
+    visited_calls: FxHashSet<(Instance<'tcx>, Vec<(Local, Ty<'tcx>)>, RefinedNode<'tcx>)>,
+    config: RefinerConfig,
     tcx: TyCtxt<'tcx>,
 }
 
 impl<'tcx> RefinerVisitor<'tcx> {
-    pub fn new(root: Instance<'tcx>, reachable: FxHashSet<Node<'tcx>>, tcx: TyCtxt<'tcx>) -> Self {
+    pub fn new(
+        root: Instance<'tcx>,
+        reachable: &FxHashSet<Node<'tcx>>,
+        config: RefinerConfig,
+        tcx: TyCtxt<'tcx>,
+    ) -> Self {
         // We do not instantiate and normalize body just yet but do it lazily instead to support
         // partially parametric instances.
-        let root_body = tcx.instance_mir(root.def).clone();
+        let root_body = Rc::new(tcx.instance_mir(root.def).clone());
 
         // Find all reachable mono items that were not used directly, they will be used when
         // resolving ambiguous calls.
         let reachable_indirect = reachable
-            .into_iter()
+            .iter()
             .filter(|used_mono_item| used_mono_item.is_indirect())
+            .copied()
             .collect();
 
         Self {
             current_instance: root,
+            bodies: FxHashMap::from_iter([(root.def, Rc::clone(&root_body))]),
             current_body: root_body,
             reachable_indirect,
             refined_usage_graph: RefinedUsageGraph::new(root),
             call_stack: vec![StackItem::new(root, tcx.def_span(root.def_id()))],
+            known_arg_types: vec![],
+            visited_calls: FxHashSet::default(),
+            config,
             tcx,
         }
     }
 
+    /// Returns the body of the instance, cloning it on the first visit only.
+    fn body_of(&mut self, instance: Instance<'tcx>) -> Rc<Body<'tcx>> {
+        let tcx = self.tcx;
+        Rc::clone(
+            self.bodies
+                .entry(instance.def)
+                .or_insert_with(|| Rc::new(tcx.instance_mir(instance.def).clone())),
+        )
+    }
+
     pub fn refine(mut self) -> RefinedUsageGraph<'tcx> {
-        self.visit_body(&self.current_body.clone());
+        let root_body = Rc::clone(&self.current_body);
+        self.visit_body(&root_body);
+        let tcx = self.tcx;
+        self.refined_usage_graph.instance_paths = self
+            .refined_usage_graph
+            .instances()
+            .into_iter()
+            .map(|instance| (instance, InstancePath::new(instance, tcx)))
+            .collect();
         self.refined_usage_graph
     }
 
@@ -402,7 +955,7 @@ impl<'tcx> RefinerVisitor<'tcx> {
     /// this signature.
     fn candidates_for_fn_ptr(&self, ambiguous_fn_sig: FnSig<'tcx>) -> Vec<Instance<'tcx>> {
         // Check whether a reachable indirect item could be used to resolve the ambiguous one.
-        let refined_candidates: Vec<Instance<'tcx>> = self
+        let mut refined_candidates: Vec<Instance<'tcx>> = self
             .reachable_indirect
             .iter()
             .filter_map(|reachable_indirect| {
@@ -417,7 +970,7 @@ impl<'tcx> RefinerVisitor<'tcx> {
                     | Usage::StaticClosureShim {
                         sig: indirect_fn_sig,
                     } => {
-                        if fn_sig_eq_with_subtyping(ambiguous_fn_sig, indirect_fn_sig) {
+                        if fn_sig_eq_with_subtyping(ambiguous_fn_sig, indirect_fn_sig, self.tcx) {
                             Some(reachable_indirect.expect_instance())
                         } else {
                             None
@@ -427,6 +980,8 @@ impl<'tcx> RefinerVisitor<'tcx> {
                 }
             })
             .collect();
+        // Reachable items are hashed, so the candidates are sorted to keep the output stable.
+        refined_candidates.sort_by_cached_key(|instance| instance.to_string());
 
         if refined_candidates.is_empty() {
             warn!("found no refined instances for function pointer with signature = {ambiguous_fn_sig:#?}",);
@@ -435,12 +990,15 @@ impl<'tcx> RefinerVisitor<'tcx> {
         refined_candidates
     }
 
+    /// Given a def_id of a trait method called through a vtable, find all vtable items that
+    /// implement it. Vtables are collected with the methods of all supertraits, so calls through
+    /// a trait object upcast to one of its supertraits resolve to the same items.
     fn candidates_for_vtable_call(
         &self,
         virtual_method_def_id: DefId,
         virtual_args: GenericArgsRef<'tcx>,
     ) -> Vec<Instance<'tcx>> {
-        let refined_candidates: Vec<Instance<'tcx>> = self
+        let mut refined_candidates: Vec<Instance<'tcx>> = self
             .reachable_indirect
             .iter()
             .filter(|reachable_indirect| match reachable_indirect.usage() {
@@ -464,6 +1022,7 @@ impl<'tcx> RefinerVisitor<'tcx> {
             })
             .map(|used_mono_item| used_mono_item.expect_instance())
             .collect();
+        refined_candidates.sort_by_cached_key(|instance| instance.to_string());
 
         if refined_candidates.is_empty() {
             warn!(
@@ -480,15 +1039,16 @@ impl<'tcx> RefinerVisitor<'tcx> {
         virtual_args: GenericArgsRef<'tcx>,
     ) -> Vec<Instance<'tcx>> {
         let indirect_sig = fn_trait_method_sig(virtual_method_def_id, virtual_args, self.tcx);
-        let refined_candidates: Vec<Instance<'tcx>> = self
+        let mut refined_candidates: Vec<Instance<'tcx>> = self
             .reachable_indirect
             .iter()
             .filter(|reachable_indirect| match reachable_indirect.usage() {
-                Usage::FnTraitItem { sig } => indirect_sig == sig,
+                Usage::FnTraitItem { sig } => fn_sig_eq_with_subtyping(indirect_sig, sig, self.tcx),
                 _ => false,
             })
             .map(|used_mono_item| used_mono_item.expect_instance())
             .collect();
+        refined_candidates.sort_by_cached_key(|instance| instance.to_string());
 
         if refined_candidates.is_empty() {
             warn!(
@@ -513,6 +1073,14 @@ impl<'tcx> RefinerVisitor<'tcx> {
         }
     }
 
+    fn virtual_resolution_kind(&self, virtual_method_def_id: DefId) -> ResolutionKind {
+        if self.tcx.is_fn_trait(self.tcx.parent(virtual_method_def_id)) {
+            ResolutionKind::FnTrait
+        } else {
+            ResolutionKind::Vtable
+        }
+    }
+
     fn instantiate_with_current_instance<T: TypeFoldable<TyCtxt<'tcx>>>(
         &self,
         v: EarlyBinder<T>,
@@ -521,52 +1089,211 @@ impl<'tcx> RefinerVisitor<'tcx> {
             .instantiate_mir_and_normalize_erasing_regions(self.tcx, ParamEnv::reveal_all(), v)
     }
 
-    fn refine_rec(&mut self, fn_ty: Ty<'tcx>, span: Span, terminator_span: Span) {
+    /// Traces a trait object operand back to the unsizing cast that created it and returns the
+    /// concrete type behind it.
+    fn trace_concrete_pointee(&self, operand: &Operand<'tcx>) -> Option<Ty<'tcx>> {
+        let operand_ty = self.instantiate_with_current_instance(EarlyBinder::bind(
+            operand.ty(&*self.current_body, self.tcx),
+        ));
+        if !operand_ty.builtin_deref(true)?.ty.is_trait() {
+            return None;
+        }
+
+        let mut local = operand.place()?.as_local()?;
+        // Every step follows a distinct assignment, so the trace is bounded by the number of locals.
+        for _ in 0..self.current_body.local_decls.len() {
+            let assignments = assignments_to(&self.current_body, local)?;
+            let rvalue = match assignments.as_slice() {
+                [] => {
+                    return self
+                        .known_arg_types
+                        .iter()
+                        .find(|(arg, _)| *arg == local)
+                        .map(|(_, ty)| *ty);
+                }
+                [rvalue] => rvalue,
+                _ => return None,
+            };
+            match rvalue {
+                Rvalue::Cast(CastKind::PointerCoercion(PointerCoercion::Unsize), source, _) => {
+                    let source_ty = self.instantiate_with_current_instance(EarlyBinder::bind(
+                        source.ty(&*self.current_body, self.tcx),
+                    ));
+                    let pointee = source_ty.builtin_deref(true)?.ty;
+                    return (!pointee.is_trait() && !pointee.has_param()).then_some(pointee);
+                }
+                Rvalue::Use(Operand::Copy(place) | Operand::Move(place)) => {
+                    local = place.as_local()?;
+                }
+                // Reborrowing the trait object keeps the concrete type behind it.
+                Rvalue::Ref(_, _, place)
+                    if matches!(place.projection[..], [ProjectionElem::Deref]) =>
+                {
+                    local = place.local;
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Resolves the virtual call to a single impl if the concrete type of the receiver is known.
+    fn narrow_virtual(
+        &self,
+        virtual_method_def_id: DefId,
+        virtual_args: GenericArgsRef<'tcx>,
+        call_args: &[Operand<'tcx>],
+    ) -> Option<Instance<'tcx>> {
+        if !self.config.narrow_dyn_calls {
+            return None;
+        }
+        let concrete_ty = self.trace_concrete_pointee(call_args.first()?)?;
+        let args = self
+            .tcx
+            .mk_args_from_iter(iter::once(concrete_ty.into()).chain(virtual_args.iter().skip(1)));
+        ty::Instance::resolve(
+            self.tcx,
+            ParamEnv::reveal_all(),
+            virtual_method_def_id,
+            args,
+        )
+        .ok()
+        .flatten()
+        .filter(|instance| !is_virtual(*instance))
+    }
+
+    /// Returns the concrete types behind the trait object arguments passed to the callee.
+    fn known_arg_types_for(
+        &self,
+        callee: Instance<'tcx>,
+        call_args: &[Operand<'tcx>],
+    ) -> Vec<(Local, Ty<'tcx>)> {
+        // Only plain functions take the call arguments as their own argument locals.
+        if !self.config.narrow_dyn_calls
+            || !matches!(callee.def, InstanceDef::Item(..))
+            || !matches!(
+                self.tcx.def_kind(callee.def_id()),
+                DefKind::Fn | DefKind::AssocFn
+            )
+        {
+            return vec![];
+        }
+        call_args
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, arg)| {
+                self.trace_concrete_pointee(arg)
+                    .map(|ty| (Local::from_usize(idx + 1), ty))
+            })
+            .collect()
+    }
+
+    fn refine_rec(
+        &mut self,
+        fn_ty: Ty<'tcx>,
+        call_args: &[Operand<'tcx>],
+        span: Span,
+        terminator_span: Span,
+    ) -> Result<(), RefinerError<'tcx>> {
         // Refine the passed function operand.
         let fn_ty = self.instantiate_with_current_instance(EarlyBinder::bind(fn_ty));
 
         let refined = match fn_ty.kind().clone() {
             TyKind::FnDef(def_id, generic_args) => {
-                let instance = ty::Instance::expect_resolve(
-                    self.tcx,
-                    ParamEnv::reveal_all(),
-                    def_id,
-                    generic_args,
-                );
-                match instance.def {
-                    InstanceDef::Virtual(method_def_id, ..) => RefinedNode::Refined {
-                        instances: self.candidates_for_virtual(method_def_id, instance.args),
-                        span,
-                        terminator_span,
-                    },
-                    _ => RefinedNode::Concrete {
-                        instance,
-                        span,
-                        terminator_span,
+                match ty::Instance::resolve(self.tcx, ParamEnv::reveal_all(), def_id, generic_args)
+                {
+                    Ok(Some(instance)) => match instance.def {
+                        InstanceDef::Virtual(method_def_id, ..) => {
+                            let instances = self
+                                .narrow_virtual(method_def_id, instance.args, call_args)
+                                .map(|narrowed| vec![narrowed])
+                                .unwrap_or_else(|| {
+                                    self.candidates_for_virtual(method_def_id, instance.args)
+                                })
+                                .into_iter()
+                                .map(|instance| unwrap_forwarding_shim(instance, self.tcx))
+                                .unique()
+                                .collect_vec();
+                            if instances.is_empty() {
+                                RefinedNode::Unresolved {
+                                    def_id: method_def_id,
+                                    args: instance.args,
+                                    span,
+                                    terminator_span,
+                                }
+                            } else {
+                                RefinedNode::Refined {
+                                    resolution: self.virtual_resolution_kind(method_def_id),
+                                    instances,
+                                    span,
+                                    terminator_span,
+                                }
+                            }
+                        }
+                        _ => RefinedNode::Concrete {
+                            instance: unwrap_forwarding_shim(instance, self.tcx),
+                            span,
+                            terminator_span,
+                        },
                     },
+                    Ok(None) | Err(..) => {
+                        warn!(
+                            "failed to resolve {} in {}",
+                            self.tcx.def_path_str_with_args(def_id, generic_args),
+                            self.current_instance
+                        );
+                        RefinedNode::Unresolved {
+                            def_id,
+                            args: generic_args,
+                            span,
+                            terminator_span,
+                        }
+                    }
                 }
             }
             TyKind::FnPtr(poly_fn_sig) => {
                 let fn_sig = erase_regions_in_sig(poly_fn_sig, self.tcx);
-                RefinedNode::Refined {
-                    instances: self.candidates_for_fn_ptr(fn_sig),
-                    span,
-                    terminator_span,
+                let instances = self
+                    .candidates_for_fn_ptr(fn_sig)
+                    .into_iter()
+                    .map(|instance| unwrap_forwarding_shim(instance, self.tcx))
+                    .unique()
+                    .collect_vec();
+                if self.config.require_unique_fn_ptr && instances.len() > 1 {
+                    self.record_ambiguous_fn_ptr_call(span, &instances);
+                }
+                if instances.is_empty() {
+                    RefinedNode::UnresolvedFnPtr {
+                        sig: fn_sig,
+                        span,
+                        terminator_span,
+                    }
+                } else {
+                    RefinedNode::Refined {
+                        resolution: ResolutionKind::FnPtr,
+                        instances,
+                        span,
+                        terminator_span,
+                    }
                 }
             }
-            _ => self.panic_and_dump_call_stack(
-                "unexpected callee type encountered when performing refinement",
-            ),
+            _ => {
+                return Err(self.error(RefinerErrorKind::UnexpectedCalleeType, span));
+            }
         };
 
-        // Skip the function if it is already in the usage graph.
-        if self
-            .refined_usage_graph
-            .forward_edges
-            .get(&self.current_instance)
-            .is_some_and(|s| s.contains(&refined))
-        {
-            return;
+        // Resolved callee should not be virtual.
+        if refined.instances().into_iter().any(is_virtual) {
+            return Err(self.error(RefinerErrorKind::VirtualCallee, span));
+        }
+
+        // Skip the function if it was already refined from the same caller in the same context.
+        if !self.visited_calls.insert((
+            self.current_instance,
+            self.known_arg_types.clone(),
+            refined.clone(),
+        )) {
+            return Ok(());
         }
 
         // Add the edge to the refined graph.
@@ -574,51 +1301,115 @@ impl<'tcx> RefinerVisitor<'tcx> {
             .add_edge(&self.current_instance, &refined);
 
         for callee in refined.instances() {
-            // Resolved callee should not be virtual.
-            if is_virtual(callee) {
-                self.panic_and_dump_call_stack(
-                    "resolved to a virtual callee when performing refinement",
-                );
+            if self.config.closure_captures {
+                self.record_closure_captures(callee);
+            }
+
+            if self.call_stack.iter().any(|item| item.instance == callee) {
+                self.refined_usage_graph
+                    .recursive_edges
+                    .insert((self.current_instance, callee));
             }
 
             // Skip recurring into the item if the item does not have a body.
-            if self.tcx.is_foreign_item(callee.def_id()) || is_intrinsic(callee) {
+            let has_body = has_body(callee, self.tcx);
+            self.refined_usage_graph.analyzed.insert(callee, has_body);
+            if !has_body {
                 continue;
             }
 
             // We do not instantiate and normalize body just yet but do it lazily instead to support
             // partially parametric instances.
-            let callee_body = self.tcx.instance_mir(callee.def).clone();
+            let callee_body = self.body_of(callee);
+
+            let callee_known_arg_types = self.known_arg_types_for(callee, call_args);
 
-            // Save previous instance and previous body to swap in later.
+            // Save previous instance, body, and known argument types to swap in later.
             let previous_instance = self.current_instance;
-            let previous_body = self.current_body.clone();
+            let previous_body = Rc::clone(&self.current_body);
+            let previous_known_arg_types =
+                mem::replace(&mut self.known_arg_types, callee_known_arg_types);
 
             // Swap root & body for the refined instance.
             self.current_instance = callee;
-            self.current_body = callee_body;
+            self.current_body = Rc::clone(&callee_body);
 
             // Add callee to the call stack.
             self.call_stack
                 .push(StackItem::new(callee, self.tcx.def_span(callee.def_id())));
 
             // Continue collection.
-            self.visit_body(&self.current_body.clone());
+            self.visit_body(&callee_body);
 
             // Swap the root back.
             self.current_instance = previous_instance;
             self.current_body = previous_body;
+            self.known_arg_types = previous_known_arg_types;
 
             // Remove callee from the call stack.
             self.call_stack.pop();
         }
+        Ok(())
+    }
+
+    /// Records the upvar types of the instance if it is a closure.
+    fn record_closure_captures(&mut self, instance: Instance<'tcx>) {
+        let TyKind::Closure(..) = self.tcx.type_of(instance.def_id()).skip_binder().kind() else {
+            return;
+        };
+        let upvar_tys = instance.args.as_closure().upvar_tys().iter().collect();
+        self.refined_usage_graph
+            .closure_captures
+            .insert(instance, upvar_tys);
+    }
+
+    /// Records a function pointer call that could not be narrowed down to a single candidate.
+    fn record_ambiguous_fn_ptr_call(&mut self, span: Span, candidates: &Vec<Instance<'tcx>>) {
+        warn!(
+            "function pointer call at {span:?} in {} resolved to {} candidates: {:?}",
+            self.current_instance,
+            candidates.len(),
+            candidates
+                .iter()
+                .map(|candidate| candidate.to_string())
+                .collect::<Vec<_>>()
+        );
+        let ambiguous_call = AmbiguousFnPtrCall {
+            caller: self.current_instance,
+            span,
+            candidates: candidates.clone(),
+        };
+        // The same call site is visited again for every path reaching its body.
+        if !self
+            .refined_usage_graph
+            .ambiguous_fn_ptr_calls
+            .iter()
+            .any(|call| call.caller == ambiguous_call.caller && call.span == ambiguous_call.span)
+        {
+            self.refined_usage_graph
+                .ambiguous_fn_ptr_calls
+                .push(ambiguous_call);
+        }
+    }
+
+    fn error(&self, kind: RefinerErrorKind, span: Span) -> RefinerError<'tcx> {
+        RefinerError {
+            kind,
+            caller: self.current_instance,
+            span,
+            call_stack: self.call_stack.clone(),
+        }
     }
 
-    fn panic_and_dump_call_stack(&self, msg: &str) -> ! {
-        const CALL_STACK_FILE: &str = "call_stack.log";
-        fs::write(CALL_STACK_FILE, format!("{:#?}", self.call_stack))
-            .expect("failed to save call stack before panicking");
-        bug!("{msg}; wrote call stack to {CALL_STACK_FILE}");
+    /// Records the call that could not be refined, so that refinement can continue past it.
+    fn record_error(&mut self, result: Result<(), RefinerError<'tcx>>) {
+        if let Err(error) = result {
+            warn!(
+                "skipped refining a call at {:?} in {}: {:?}",
+                error.span, error.caller, error.kind
+            );
+            self.refined_usage_graph.errors.push(error);
+        }
     }
 }
 
@@ -626,25 +1417,56 @@ impl<'tcx> Visitor<'tcx> for RefinerVisitor<'tcx> {
     fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
         let terminator_span = terminator.source_info.span;
         match &terminator.kind {
-            TerminatorKind::Call { func, fn_span, .. } => {
-                self.refine_rec(
-                    func.ty(&self.current_body, self.tcx),
+            TerminatorKind::Call {
+                func,
+                args,
+                fn_span,
+                ..
+            } => {
+                let result = self.refine_rec(
+                    func.ty(&*self.current_body, self.tcx),
+                    args,
                     *fn_span,
                     terminator_span,
                 );
+                self.record_error(result);
             }
             TerminatorKind::Drop { ref place, .. } => {
-                let ty = place.ty(&self.current_body, self.tcx).ty;
+                let ty = place.ty(&*self.current_body, self.tcx).ty;
                 let def_id = self.tcx.require_lang_item(LangItem::DropInPlace, None);
                 let args = self.tcx.mk_args(&[ty.into()]);
-                self.refine_rec(
+                let result = self.refine_rec(
                     self.tcx.type_of(def_id).instantiate(self.tcx, args),
+                    &[],
                     DUMMY_SP,
                     terminator_span,
                 );
+                self.record_error(result);
+            }
+            TerminatorKind::Assert { msg, .. } => {
+                let def_id = self
+                    .tcx
+                    .require_lang_item(assert_lang_item(&**msg), Some(terminator_span));
+                let result = self.refine_rec(
+                    self.tcx.type_of(def_id).instantiate_identity(),
+                    &[],
+                    terminator_span,
+                    terminator_span,
+                );
+                self.record_error(result);
+            }
+            TerminatorKind::InlineAsm { operands, .. } => {
+                // Functions referenced by inline assembly may be called by it.
+                for operand in operands {
+                    if let InlineAsmOperand::SymFn { value } = operand {
+                        let result =
+                            self.refine_rec(value.const_.ty(), &[], value.span, terminator_span);
+                        self.record_error(result);
+                    }
+                }
             }
             _ => {
-                // TODO: visit other terminators, such as `Assert`.
+                // TODO: visit other terminators, such as `UnwindTerminate`.
             }
         }
         self.super_terminator(terminator, location);
@@ -653,8 +1475,13 @@ impl<'tcx> Visitor<'tcx> for RefinerVisitor<'tcx> {
 
 pub fn refine_from<'tcx>(
     root: Instance<'tcx>,
-    reachable: FxHashSet<Node<'tcx>>,
+    reachable: &FxHashSet<Node<'tcx>>,
+    config: RefinerConfig,
     tcx: TyCtxt<'tcx>,
 ) -> RefinedUsageGraph<'tcx> {
-    RefinerVisitor::new(root, reachable, tcx).refine()
+    profile(
+        "refinement",
+        || tcx.def_path_str(root.def_id()),
+        || RefinerVisitor::new(root, reachable, config, tcx).refine(),
+    )
 }
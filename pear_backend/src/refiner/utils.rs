@@ -1,4 +1,12 @@
-use rustc_middle::ty::{self, FnSig, Instance, InstanceDef, Ty};
+use rustc_middle::{
+    mir::{
+        visit::{MutatingUseContext, PlaceContext, Visitor},
+        Body, Local, Location, Place, Rvalue,
+    },
+    ty::{
+        self, FnSig, Instance, InstanceDef, Ty, TyCtxt, TypeFoldable, TypeFolder, TypeSuperFoldable,
+    },
+};
 
 pub fn is_virtual<'tcx>(instance: Instance<'tcx>) -> bool {
     matches!(instance.def, InstanceDef::Virtual(..))
@@ -8,30 +16,209 @@ pub fn is_intrinsic<'tcx>(instance: Instance<'tcx>) -> bool {
     matches!(instance.def, InstanceDef::Intrinsic(..))
 }
 
-/// Checks if type a is equivalent to type b taking into account subtyping relations.
-pub fn ty_eq_with_subtyping<'tcx>(ty_a: Ty<'tcx>, ty_b: Ty<'tcx>) -> bool {
-    ty_a.walk().any(|generic_arg| {
-        generic_arg
-            .as_type()
-            .map(|ty| matches!(ty.kind(), ty::Foreign(..)))
-            .unwrap_or(false)
-    }) || ty_a == ty_b
+/// Returns true if the instance has MIR to refine. Shims, such as `CloneShim`, `FnPtrShim`, and
+/// `ClosureOnceShim`, have MIR built by `tcx.mir_shims` and are refined like any other body.
+pub fn has_body<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    !(tcx.is_foreign_item(instance.def_id()) || is_intrinsic(instance))
+}
+
+/// Returns the underlying function instance for reify and vtable shims, which only forward the call
+/// to it (injecting the caller location for `#[track_caller]` functions). Reify shims of trait
+/// methods are kept, as they dispatch the call through the vtable.
+pub fn unwrap_forwarding_shim<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> Instance<'tcx> {
+    match instance.def {
+        InstanceDef::ReifyShim(def_id) if tcx.trait_of_item(def_id).is_some() => instance,
+        InstanceDef::ReifyShim(def_id) | InstanceDef::VTableShim(def_id) => {
+            Instance::new(def_id, instance.args)
+        }
+        _ => instance,
+    }
+}
+
+/// Replaces all foreign types with the first one it encounters. Foreign types are opaque and the
+/// same one may be declared in multiple crates, so they are not told apart.
+struct ForeignTypeUnifier<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    canonical: Option<Ty<'tcx>>,
+}
+
+impl<'tcx> TypeFolder<TyCtxt<'tcx>> for ForeignTypeUnifier<'tcx> {
+    fn interner(&self) -> TyCtxt<'tcx> {
+        self.tcx
+    }
+
+    fn fold_ty(&mut self, ty: Ty<'tcx>) -> Ty<'tcx> {
+        match ty.kind() {
+            ty::Foreign(..) => *self.canonical.get_or_insert(ty),
+            _ => ty.super_fold_with(self),
+        }
+    }
 }
 
 /// Checks if function signature a is equivalent to function signature b taking into account
-/// subtyping relations.
-pub fn fn_sig_eq_with_subtyping<'tcx>(fn_sig_a: FnSig<'tcx>, fn_sig_b: FnSig<'tcx>) -> bool {
-    let ty_eq_with_subtyping = {
-        let inputs_and_output_a = fn_sig_a.inputs_and_output;
-        let inputs_and_output_b = fn_sig_b.inputs_and_output;
-        inputs_and_output_a.len() == inputs_and_output_b.len()
-            && inputs_and_output_a
-                .iter()
-                .zip(inputs_and_output_b.iter())
-                .all(|(ty_a, ty_b)| ty_eq_with_subtyping(ty_a, ty_b))
+/// subtyping relations. Both signatures need to have the same arity, unsafety, ABI, and
+/// variadicity. Their input and output types are compared after erasing regions, so signatures
+/// that only differ in lifetimes are equivalent, while everything else (e.g., the mutability of
+/// references) has to match exactly. All foreign types are considered equivalent.
+pub fn fn_sig_eq_with_subtyping<'tcx>(
+    fn_sig_a: FnSig<'tcx>,
+    fn_sig_b: FnSig<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> bool {
+    if fn_sig_a.inputs().len() != fn_sig_b.inputs().len()
+        || fn_sig_a.unsafety != fn_sig_b.unsafety
+        || fn_sig_a.abi != fn_sig_b.abi
+        || fn_sig_a.c_variadic != fn_sig_b.c_variadic
+    {
+        return false;
+    }
+    let mut foreign_type_unifier = ForeignTypeUnifier {
+        tcx,
+        canonical: None,
     };
-    ty_eq_with_subtyping
-        && fn_sig_a.unsafety == fn_sig_b.unsafety
-        && fn_sig_a.c_variadic == fn_sig_b.c_variadic
-        && fn_sig_a.abi == fn_sig_b.abi
+    fn_sig_a
+        .inputs_and_output
+        .iter()
+        .zip(fn_sig_b.inputs_and_output.iter())
+        .all(|(ty_a, ty_b)| {
+            let ty_a = tcx.erase_regions(ty_a).fold_with(&mut foreign_type_unifier);
+            let ty_b = tcx.erase_regions(ty_b).fold_with(&mut foreign_type_unifier);
+            ty_a == ty_b
+        })
+}
+
+struct LocalAssignments<'tcx> {
+    local: Local,
+    assignments: Vec<Rvalue<'tcx>>,
+    modified_otherwise: bool,
+}
+
+impl<'tcx> Visitor<'tcx> for LocalAssignments<'tcx> {
+    fn visit_assign(&mut self, place: &Place<'tcx>, rvalue: &Rvalue<'tcx>, location: Location) {
+        if place.as_local() == Some(self.local) {
+            self.assignments.push(rvalue.clone());
+        }
+        self.super_assign(place, rvalue, location);
+    }
+
+    fn visit_local(&mut self, local: Local, context: PlaceContext, _location: Location) {
+        if local == self.local
+            && context.is_mutating_use()
+            && !matches!(
+                context,
+                PlaceContext::MutatingUse(MutatingUseContext::Store | MutatingUseContext::Drop)
+            )
+        {
+            self.modified_otherwise = true;
+        }
+    }
+}
+
+/// Returns all whole-local assignments to the local, or None if the local could also be modified
+/// in some other way (e.g. via a mutable borrow or a partial write).
+pub fn assignments_to<'tcx>(body: &Body<'tcx>, local: Local) -> Option<Vec<Rvalue<'tcx>>> {
+    let mut visitor = LocalAssignments {
+        local,
+        assignments: vec![],
+        modified_otherwise: false,
+    };
+    visitor.visit_body(body);
+    (!visitor.modified_otherwise).then_some(visitor.assignments)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::utils::erase_regions_in_sig;
+
+    const SOURCE: &str = r#"
+        pub fn first<'a>(a: &'a str, b: &'a str) -> &'a str { a }
+        pub fn second(a: &'static str, b: &'static str) -> &'static str { b }
+        pub fn elided(a: &str, b: &str) -> &'static str { "" }
+        pub fn first_mut<'a>(a: &'a mut str, b: &'a str) -> &'a str { b }
+        pub unsafe fn first_unsafe<'a>(a: &'a str, b: &'a str) -> &'a str { a }
+        pub extern "C" fn first_extern<'a>(a: &'a str, b: &'a str) -> &'a str { a }
+        pub fn first_unary<'a>(a: &'a str) -> &'a str { a }
+        pub fn invariant_named<'a>(a: &'a mut &'static str) {}
+        pub fn invariant_elided(a: &mut &str) {}
+        pub fn callback_a(f: for<'a> fn(&'a u32) -> &'a u32) {}
+        pub fn callback_b(f: for<'b> fn(&'b u32) -> &'b u32) {}
+        pub fn callback_static(f: fn(&'static u32) -> &'static u32) {}
+    "#;
+
+    // Pairs of the functions above and whether their signatures should be considered equal.
+    const PAIRS: &[(&str, &str, bool)] = &[
+        ("first", "second", true),
+        ("first", "elided", true),
+        ("invariant_named", "invariant_elided", true),
+        ("callback_a", "callback_b", true),
+        ("first", "first_mut", false),
+        ("first", "first_unsafe", false),
+        ("first", "first_extern", false),
+        ("first", "first_unary", false),
+        ("callback_a", "callback_static", false),
+    ];
+
+    struct CheckCallbacks<F> {
+        check: Option<F>,
+    }
+
+    impl<F: for<'tcx> FnOnce(TyCtxt<'tcx>) + Send> rustc_driver::Callbacks for CheckCallbacks<F> {
+        fn after_analysis<'tcx>(
+            &mut self,
+            _compiler: &rustc_interface::interface::Compiler,
+            queries: &'tcx rustc_interface::Queries<'tcx>,
+        ) -> rustc_driver::Compilation {
+            let check = self.check.take().unwrap();
+            queries.global_ctxt().unwrap().enter(check);
+            rustc_driver::Compilation::Stop
+        }
+    }
+
+    /// Compiles the source as a library and runs the check on its type context.
+    fn compile(source: &str, check: impl for<'tcx> FnOnce(TyCtxt<'tcx>) + Send) {
+        let path = std::env::temp_dir().join(format!("pear_fn_sigs_{}.rs", std::process::id()));
+        fs::write(&path, source).unwrap();
+        let args = vec![
+            "rustc".to_string(),
+            path.to_string_lossy().to_string(),
+            "--crate-type=lib".to_string(),
+            "--edition=2021".to_string(),
+        ];
+        let mut callbacks = CheckCallbacks { check: Some(check) };
+        let result = rustc_driver::RunCompiler::new(&args, &mut callbacks).run();
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+    }
+
+    fn sig_of<'tcx>(name: &str, tcx: TyCtxt<'tcx>) -> FnSig<'tcx> {
+        let def_id = tcx
+            .hir()
+            .body_owners()
+            .map(|local_def_id| local_def_id.to_def_id())
+            .find(|def_id| tcx.item_name(*def_id).as_str() == name)
+            .unwrap();
+        erase_regions_in_sig(tcx.fn_sig(def_id).instantiate_identity(), tcx)
+    }
+
+    #[test]
+    fn fn_sig_pairs() {
+        compile(SOURCE, |tcx| {
+            for (a, b, expected) in PAIRS {
+                let (sig_a, sig_b) = (sig_of(a, tcx), sig_of(b, tcx));
+                assert_eq!(
+                    fn_sig_eq_with_subtyping(sig_a, sig_b, tcx),
+                    *expected,
+                    "comparing {a} with {b}"
+                );
+                assert_eq!(
+                    fn_sig_eq_with_subtyping(sig_b, sig_a, tcx),
+                    *expected,
+                    "comparing {b} with {a}"
+                );
+            }
+        });
+    }
 }
@@ -1,13 +1,20 @@
+use std::cell::RefCell;
+
+use rustc_data_structures::sync::Lrc;
 use rustc_hash::{FxHashMap, FxHashSet};
 use rustc_hir::def_id::DefId;
 use rustc_middle::{
     mir::mono::MonoItem,
-    ty::{FnSig, Instance},
+    ty::{FnSig, GenericArgsRef, Instance, Ty, TyCtxt},
 };
-use rustc_span::Span;
-use serde::Serializer;
+use rustc_span::{source_map::SourceMap, Span};
+use serde::{Deserialize, Serialize, Serializer};
 
-use crate::{reachability::Node, refiner::RefinedNode, TransitiveRefinedNode};
+use crate::{
+    reachability::Node,
+    refiner::{InstancePath, RefinedNode},
+    TransitiveRefinedNode,
+};
 
 pub fn serialize_def_id<S>(def_id: &DefId, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -23,6 +30,31 @@ where
     serializer.serialize_str(mono_item.to_string().as_str())
 }
 
+/// Sorts the nodes by their serialized form so that the output does not depend on hash order.
+fn sorted_nodes<'a, T: Serialize + 'a>(nodes: impl IntoIterator<Item = &'a T>) -> Vec<&'a T> {
+    let mut nodes = nodes
+        .into_iter()
+        .map(|node| {
+            (
+                serde_json::to_string(node).expect("failed to serialize a node"),
+                node,
+            )
+        })
+        .collect::<Vec<_>>();
+    nodes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    nodes.into_iter().map(|(_, node)| node).collect()
+}
+
+/// Sorts the map entries by their stringified keys.
+fn sorted_by_key<K, V>(entries: impl IntoIterator<Item = (K, V)>) -> Vec<(K, V)>
+where
+    K: Ord,
+{
+    let mut entries = entries.into_iter().collect::<Vec<_>>();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries
+}
+
 pub fn serialize_edges<'tcx, S>(
     edges: &FxHashMap<MonoItem<'tcx>, FxHashSet<Node<'tcx>>>,
     serializer: S,
@@ -30,7 +62,9 @@ pub fn serialize_edges<'tcx, S>(
 where
     S: Serializer,
 {
-    serializer.collect_map(edges.iter().map(|(k, v)| (k.to_string(), v)))
+    serializer.collect_map(sorted_by_key(
+        edges.iter().map(|(k, v)| (k.to_string(), sorted_nodes(v))),
+    ))
 }
 
 pub fn serialize_refined_edges<'tcx, S>(
@@ -40,7 +74,9 @@ pub fn serialize_refined_edges<'tcx, S>(
 where
     S: Serializer,
 {
-    serializer.collect_map(edges.iter().map(|(k, v)| (k.to_string(), v)))
+    serializer.collect_map(sorted_by_key(
+        edges.iter().map(|(k, v)| (k.to_string(), sorted_nodes(v))),
+    ))
 }
 
 pub fn serialize_transitive_refined_edges<'tcx, S>(
@@ -50,7 +86,59 @@ pub fn serialize_transitive_refined_edges<'tcx, S>(
 where
     S: Serializer,
 {
-    serializer.collect_map(edges.iter().map(|(k, v)| (k.to_string(), v)))
+    serializer.collect_map(sorted_by_key(
+        edges.iter().map(|(k, v)| (k.to_string(), sorted_nodes(v))),
+    ))
+}
+
+pub fn serialize_instance_flags<'tcx, S>(
+    flags: &FxHashMap<Instance<'tcx>, bool>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(sorted_by_key(flags.iter().map(|(k, v)| (k.to_string(), v))))
+}
+
+pub fn serialize_instance_paths<'tcx, S>(
+    paths: &FxHashMap<Instance<'tcx>, InstancePath>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(sorted_by_key(paths.iter().map(|(k, v)| (k.to_string(), v))))
+}
+
+pub fn serialize_instance_pairs<'tcx, S>(
+    pairs: &FxHashSet<(Instance<'tcx>, Instance<'tcx>)>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut pairs = pairs
+        .iter()
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+        .collect::<Vec<_>>();
+    pairs.sort();
+    serializer.collect_seq(pairs)
+}
+
+pub fn serialize_instance_types<'tcx, S>(
+    types: &FxHashMap<Instance<'tcx>, Vec<Ty<'tcx>>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(sorted_by_key(types.iter().map(|(k, v)| {
+        (
+            k.to_string(),
+            v.iter().map(|ty| ty.to_string()).collect::<Vec<_>>(),
+        )
+    })))
 }
 
 pub fn serialize_instance<S>(instance: &Instance, serializer: S) -> Result<S::Ok, S::Error>
@@ -67,14 +155,76 @@ pub fn serialize_instance_vec<S>(
 where
     S: Serializer,
 {
-    serializer.collect_seq(instances.iter().map(|instance| instance.to_string()))
+    let mut instances = instances
+        .iter()
+        .map(|instance| instance.to_string())
+        .collect::<Vec<_>>();
+    instances.sort();
+    serializer.collect_seq(instances)
+}
+
+pub fn serialize_generic_args<S>(args: &GenericArgsRef, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(format!("{args:?}").as_str())
 }
 
+thread_local! {
+    static SOURCE_MAP: RefCell<Option<Lrc<SourceMap>>> = RefCell::new(None);
+}
+
+/// Resolves all spans serialized within the closure against the source map of the session.
+pub fn with_source_map<R>(tcx: TyCtxt<'_>, f: impl FnOnce() -> R) -> R {
+    let previous = SOURCE_MAP.replace(Some(tcx.sess.parse_sess.clone_source_map()));
+    let result = f();
+    SOURCE_MAP.set(previous);
+    result
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SourceLocation {
+    file: String,
+    line_lo: usize,
+    col_lo: usize,
+    line_hi: usize,
+    col_hi: usize,
+}
+
+impl SourceLocation {
+    pub(crate) fn new(span: Span, source_map: &SourceMap) -> Self {
+        let lo = source_map.lookup_char_pos(span.lo());
+        let hi = source_map.lookup_char_pos(span.hi());
+        Self {
+            file: lo.file.name.prefer_local().to_string(),
+            line_lo: lo.line,
+            col_lo: lo.col.0 + 1,
+            line_hi: hi.line,
+            col_hi: hi.col.0 + 1,
+        }
+    }
+}
+
+/// Serializes the span as a source location if a source map is available (see
+/// [`with_source_map`]), falling back to its `Debug` representation otherwise.
 pub fn serialize_span<S>(span: &Span, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    serializer.serialize_str(format!("{span:?}").as_str())
+    SOURCE_MAP.with_borrow(|source_map| match source_map {
+        Some(source_map) => SourceLocation::new(*span, source_map).serialize(serializer),
+        None => serializer.serialize_str(format!("{span:?}").as_str()),
+    })
+}
+
+pub fn serialize_optional_span<S>(span: &Option<Span>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match span {
+        Some(span) => serialize_span(span, serializer),
+        None => serializer.serialize_none(),
+    }
 }
 
 pub fn serialize_sig<S>(sig: &FnSig, serializer: S) -> Result<S::Ok, S::Error>
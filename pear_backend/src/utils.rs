@@ -1,6 +1,9 @@
 use itertools::Itertools;
-use rustc_hir::{def_id::DefId, Unsafety};
-use rustc_middle::ty::{self, FnSig, GenericArgsRef, PolyFnSig, TyCtxt};
+use rustc_hir::{def_id::DefId, LangItem, Unsafety};
+use rustc_middle::{
+    mir::AssertKind,
+    ty::{self, FnSig, GenericArgsRef, Instance, InstanceDef, PolyFnSig, TyCtxt},
+};
 use rustc_target::spec::abi::Abi;
 
 /// Erases all regions in the signature since we do not care about them when performing matching.
@@ -8,6 +11,57 @@ pub fn erase_regions_in_sig<'tcx>(poly_fn_sig: PolyFnSig<'tcx>, tcx: TyCtxt<'tcx
     tcx.instantiate_bound_regions_with_erased(tcx.erase_regions(poly_fn_sig))
 }
 
+/// Returns the signature of the instance with all regions erased. For closures, this is the
+/// signature of the closure itself, taking the arguments as a tuple.
+pub fn instance_sig<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> FnSig<'tcx> {
+    if tcx.is_closure_or_coroutine(instance.def_id()) {
+        if tcx.is_coroutine(instance.def_id()) {
+            bug!("coroutines do not have a conventional signature");
+        }
+        erase_regions_in_sig(instance.args.as_closure().sig(), tcx)
+    } else {
+        erase_regions_in_sig(
+            tcx.fn_sig(instance.def_id())
+                .instantiate(tcx, instance.args),
+            tcx,
+        )
+    }
+}
+
+/// Returns the number of argument locals in the body of the instance, which includes the closure
+/// environment for closures.
+pub fn num_args_for_instance<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> usize {
+    tcx.instance_mir(instance.def).arg_count
+}
+
+/// Returns the name of the `InstanceDef` variant, used to tell shims apart in the output.
+pub fn instance_def_kind(instance_def: InstanceDef) -> &'static str {
+    match instance_def {
+        InstanceDef::Item(..) => "Item",
+        InstanceDef::Intrinsic(..) => "Intrinsic",
+        InstanceDef::VTableShim(..) => "VTableShim",
+        InstanceDef::ReifyShim(..) => "ReifyShim",
+        InstanceDef::FnPtrShim(..) => "FnPtrShim",
+        InstanceDef::Virtual(..) => "Virtual",
+        InstanceDef::ClosureOnceShim { .. } => "ClosureOnceShim",
+        InstanceDef::ThreadLocalShim(..) => "ThreadLocalShim",
+        InstanceDef::DropGlue(..) => "DropGlue",
+        InstanceDef::CloneShim(..) => "CloneShim",
+        InstanceDef::FnPtrAddrShim(..) => "FnPtrAddrShim",
+    }
+}
+
+/// Returns the panic lang item invoked when the assertion fails.
+pub fn assert_lang_item<O>(msg: &AssertKind<O>) -> LangItem {
+    match msg {
+        AssertKind::BoundsCheck { .. } => LangItem::PanicBoundsCheck,
+        AssertKind::MisalignedPointerDereference { .. } => {
+            LangItem::PanicMisalignedPointerDereference
+        }
+        _ => LangItem::Panic,
+    }
+}
+
 /// Computes function signature of a method of Fn-like trait.
 pub fn fn_trait_method_sig<'tcx>(
     item_def_id: DefId,
@@ -1,12 +1,11 @@
 use pear_backend::LocalAnalysis;
 use rustc_hir::def_id::LocalDefId;
 use rustc_macros::{TyDecodable, TyEncodable};
-use rustc_middle::{
-    mir::{Body, ClearCrossCrate, StatementKind},
-    ty::TyCtxt,
-};
+use rustc_middle::{mir::Body, ty::TyCtxt};
 use rustc_utils::mir::borrowck_facts::get_body_with_borrowck_facts;
 
+use crate::analysis::utils::clean_undecodable_data_from_body;
+
 pub struct CachedBodyAnalysis {}
 
 impl<'tcx> LocalAnalysis<'tcx> for CachedBodyAnalysis {
@@ -31,31 +30,99 @@ impl<'tcx> CachedBody<'tcx> {
     pub fn retrieve(tcx: TyCtxt<'tcx>, local_def_id: LocalDefId) -> Self {
         let body_with_facts = get_body_with_borrowck_facts(tcx, local_def_id);
         let mut body = body_with_facts.body.clone();
-        Self::clean_undecodable_data_from_body(&mut body);
+        clean_undecodable_data_from_body(&mut body);
 
         Self { body }
     }
 
-    /// Some data in a [Body] is not cross-crate compatible. Usually because it
-    /// involves storing a [LocalDefId]. This function makes sure to sanitize those
-    /// out.
-    fn clean_undecodable_data_from_body(body: &mut Body) {
-        for scope in body.source_scopes.iter_mut() {
-            scope.local_data = ClearCrossCrate::Clear;
-        }
+    pub fn owned_body(self) -> Body<'tcx> {
+        self.body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use itertools::Itertools;
+    use pear_backend::{decode_from_file, encode_to_file};
+    use rustc_utils::mir::borrowck_facts;
+
+    use super::*;
 
-        for stmt in body
-            .basic_blocks_mut()
-            .iter_mut()
-            .flat_map(|bb| bb.statements.iter_mut())
-        {
-            if matches!(stmt.kind, StatementKind::FakeRead(_)) {
-                stmt.make_nop()
+    const SOURCE: &str = r#"
+        pub fn sum(values: &[u32]) -> u32 {
+            let mut total = 0;
+            for value in values {
+                total += value;
             }
+            total
+        }
+    "#;
+
+    /// Encodes the body of `sum` to a file and decodes it back, with and without compression.
+    struct RoundTripCallbacks;
+
+    impl rustc_driver::Callbacks for RoundTripCallbacks {
+        fn config(&mut self, config: &mut rustc_interface::Config) {
+            borrowck_facts::enable_mir_simplification();
+            config.override_queries = Some(borrowck_facts::override_queries);
+        }
+
+        fn after_expansion<'tcx>(
+            &mut self,
+            _compiler: &rustc_interface::interface::Compiler,
+            queries: &'tcx rustc_interface::Queries<'tcx>,
+        ) -> rustc_driver::Compilation {
+            queries.global_ctxt().unwrap().enter(|tcx| {
+                let local_def_id = tcx
+                    .hir()
+                    .body_owners()
+                    .find(|local_def_id| tcx.item_name(local_def_id.to_def_id()).as_str() == "sum")
+                    .unwrap();
+                let cached_body = CachedBody::retrieve(tcx, local_def_id);
+                let path = env::temp_dir().join(format!("pear_cached_body_{}", process::id()));
+                for compressed in [false, true] {
+                    if compressed {
+                        env::set_var("PEAR_CACHE_COMPRESSION", "1");
+                    } else {
+                        env::remove_var("PEAR_CACHE_COMPRESSION");
+                    }
+                    encode_to_file(tcx, &path, &cached_body).unwrap();
+                    let is_gzip = fs::read(&path).unwrap().starts_with(&[0x1f, 0x8b]);
+                    assert_eq!(is_gzip, compressed);
+
+                    let decoded: CachedBody = decode_from_file(tcx, &path).unwrap();
+                    let (expected, actual) = (&cached_body.body, &decoded.body);
+                    assert_eq!(
+                        format!("{:?}", expected.basic_blocks.iter().collect_vec()),
+                        format!("{:?}", actual.basic_blocks.iter().collect_vec())
+                    );
+                    assert_eq!(
+                        format!("{:?}", expected.local_decls),
+                        format!("{:?}", actual.local_decls)
+                    );
+                    assert_eq!(expected.arg_count, actual.arg_count);
+                }
+                env::remove_var("PEAR_CACHE_COMPRESSION");
+                fs::remove_file(&path).unwrap();
+            });
+            rustc_driver::Compilation::Stop
         }
     }
 
-    pub fn owned_body(self) -> Body<'tcx> {
-        self.body
+    #[test]
+    fn cached_body_round_trip() {
+        let path = env::temp_dir().join(format!("pear_cached_body_{}.rs", process::id()));
+        fs::write(&path, SOURCE).unwrap();
+        let args = vec![
+            "rustc".to_string(),
+            path.to_string_lossy().to_string(),
+            "--crate-type=lib".to_string(),
+            "--edition=2021".to_string(),
+        ];
+        let result = rustc_driver::RunCompiler::new(&args, &mut RoundTripCallbacks).run();
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
     }
 }
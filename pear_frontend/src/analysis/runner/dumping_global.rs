@@ -1,27 +1,115 @@
-use std::fs;
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use colored::Colorize;
 use itertools::Itertools;
 use regex::Regex;
-use rustc_hir::ItemKind;
+use rustc_hir::{def::DefKind, def_id::DefId};
 use rustc_middle::{
     mir::mono::MonoItem,
     ty::{self, FnSig, Ty, TyCtxt},
 };
 use rustc_span::Symbol;
+use serde::Serialize;
 
-use pear_backend::{collect_from, refine_from, GlobalAnalysis, RefinedUsageGraph};
+use pear_backend::{
+    instance_sig, print_profile, refine_from, runtime_roots, validate_against_monomorphization,
+    with_source_map, CollectionBudget, Collector, GlobalAnalysis, IncrementalCache,
+    MonoItemDiscrepancies, Node, PortableRefinedUsageGraph, RefinedNode, RefinedUsageGraph,
+    RefinerConfig, Usage, UsageGraph,
+};
 use rustc_utils::BodyExt;
 
-use crate::analysis::utils::instance_sig;
+use crate::{
+    analysis::utils::{matches_filters, named_entries, strip_spans},
+    watchdog::{write_atomically, write_atomically_with},
+};
+
+/// Settings of a dumping run, built from the plugin arguments.
+pub struct DumpingOptions {
+    pub filter: Vec<Regex>,
+    pub refiner_config: RefinerConfig,
+    pub strip_spans: bool,
+    pub public_api: bool,
+    pub include_panic_machinery: bool,
+    pub emit_dot: bool,
+    pub entries_file: Option<String>,
+    pub named_entries: Vec<String>,
+    pub validate_mono: bool,
+    pub out_dir: PathBuf,
+    /// Directory holding the refined graphs that later runs are diffed against.
+    pub baseline_dir: PathBuf,
+    pub collection_budget: CollectionBudget,
+    pub crate_filter: Vec<String>,
+    pub incremental: bool,
+    pub runtime_roots: bool,
+    pub write_baseline: bool,
+}
 
 pub struct DumpingGlobalAnalysis {
-    filter: Option<Regex>,
+    options: DumpingOptions,
 }
 
 impl<'tcx> DumpingGlobalAnalysis {
-    pub fn new(filter: Option<Regex>) -> Self {
-        Self { filter }
+    pub fn new(options: DumpingOptions) -> Self {
+        Self { options }
+    }
+
+    /// Streams the graph into the file as pretty-printed JSON.
+    fn write_graph<T: Serialize>(&self, graph: &T, path: &Path, tcx: TyCtxt<'_>) -> io::Result<()> {
+        with_source_map(tcx, || {
+            write_atomically_with(path, |writer| {
+                if self.options.strip_spans {
+                    let mut value = serde_json::to_value(graph)?;
+                    strip_spans(&mut value);
+                    serde_json::to_writer_pretty(writer, &value)?;
+                } else {
+                    serde_json::to_writer_pretty(writer, graph)?;
+                }
+                Ok(())
+            })
+        })
+    }
+}
+
+/// Sizes of the graphs built for an entry.
+#[derive(Serialize)]
+struct EntrySummary {
+    /// Mono items collected from the entry.
+    reachable_items: usize,
+    /// Edges of the refined graph, split by how the callee was resolved below.
+    refined_edges: usize,
+    concrete: usize,
+    refined: usize,
+    unresolved: usize,
+    /// Largest number of calls needed to get from the entry to any instance.
+    max_depth: usize,
+}
+
+impl EntrySummary {
+    fn new(reachable_items: usize, refined_usage_graph: &RefinedUsageGraph) -> Self {
+        let mut summary = Self {
+            reachable_items,
+            refined_edges: 0,
+            concrete: 0,
+            refined: 0,
+            unresolved: 0,
+            max_depth: refined_usage_graph.depth(),
+        };
+        for (_, node) in refined_usage_graph.edges() {
+            summary.refined_edges += 1;
+            match node {
+                RefinedNode::Concrete { .. } => summary.concrete += 1,
+                RefinedNode::Refined { .. } => summary.refined += 1,
+                RefinedNode::Unresolved { .. } | RefinedNode::UnresolvedFnPtr { .. } => {
+                    summary.unresolved += 1
+                }
+            }
+        }
+        summary
     }
 }
 
@@ -46,117 +134,1085 @@ impl<'tcx> GlobalAnalysis<'tcx> for DumpingGlobalAnalysis {
 
         println!("{}", "Starting PEAR analysis.".blue().bold());
 
-        let pear_entry_attribute = [Symbol::intern("pear"), Symbol::intern("analysis_entry")];
-        let hir = tcx.hir();
+        let entries = if let Some(entries_file) = &self.options.entries_file {
+            file_entries(entries_file, tcx)
+        } else if self.options.public_api {
+            public_api_entries(tcx)
+        } else {
+            annotated_entries(tcx)
+        };
+
+        let entries = entries
+            .into_iter()
+            .chain(named_entries(&self.options.named_entries, tcx))
+            .chain(if self.options.runtime_roots {
+                runtime_roots(tcx)
+            } else {
+                vec![]
+            })
+            .unique()
+            .filter(|def_id| {
+                matches_filters(&self.options.filter, tcx.def_path_str(*def_id).as_str())
+            })
+            .filter_map(|def_id| Some((def_id, entry_instance(def_id, tcx)?)))
+            .collect_vec();
+        // Shared by all entries, so that items reachable from several of them are only walked once.
+        let mut collector = Collector::new(tcx, self.options.collection_budget)
+            .with_crate_filter(self.options.crate_filter.clone());
+        let incremental_cache_dir = self.options.out_dir.join("cache");
+        if self.options.incremental {
+            collector = collector
+                .with_incremental_cache(&IncrementalCache::load(tcx, &incremental_cache_dir));
+        }
+
+        let collected_entries = entries
+            .into_iter()
+            .map(|(def_id, entry_instance)| {
+                let collection = collector.collect(MonoItem::Fn(entry_instance));
+                if collection.1.is_truncated() {
+                    println!(
+                        "WARNING: collection from {} ran out of budget, the graph is incomplete.",
+                        tcx.def_path_str(def_id)
+                    );
+                }
+                (def_id, entry_instance, collection)
+            })
+            .collect_vec();
+
+        if self.options.incremental {
+            let incremental_cache = collector.incremental_cache();
+            println!(
+                "Recomputed the uses of {} of {} items.",
+                collector.recomputed().len(),
+                incremental_cache.len()
+            );
+            incremental_cache.save(tcx, &incremental_cache_dir);
+        }
+
+        // In the public API mode, skip the entries that are already analyzed as a part of another
+        // entry. If two entries reach each other, the first one is kept.
+        let is_redundant = |i: usize| {
+            let (_, instance, collection) = &collected_entries[i];
+            self.options.public_api
+                && collected_entries.iter().enumerate().any(
+                    |(j, (_, other_instance, other_collection))| {
+                        j != i
+                            && reaches(&other_collection.0, *instance)
+                            && (j < i || !reaches(&collection.0, *other_instance))
+                    },
+                )
+        };
+        let redundant = (0..collected_entries.len()).map(is_redundant).collect_vec();
+
+        let collected_entries = collected_entries
+            .into_iter()
+            .zip(redundant)
+            .filter_map(|(collected_entry, redundant)| (!redundant).then_some(collected_entry))
+            .collect_vec();
+
+        if self.options.public_api
+            && let Ok(bytes) = fs::read("expected/public_api.entries.pear.expected")
+        {
+            let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+            let entries = collected_entries
+                .iter()
+                .map(|(def_id, ..)| tcx.def_path_str(*def_id))
+                .collect_vec();
+            run_entries_test("public API", &entries, &expected);
+        }
+
+        if self.options.entries_file.is_some()
+            && let Ok(bytes) = fs::read("expected/entries_file.entries.pear.expected")
+        {
+            let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+            let entries = collected_entries
+                .iter()
+                .map(|(def_id, ..)| tcx.def_path_str(*def_id))
+                .collect_vec();
+            run_entries_test("entries file", &entries, &expected);
+        }
+
+        // Pruning the graph merged from all entries back to one entry drops the others' items.
+        for (def_id, entry_instance, collection) in collected_entries.iter() {
+            let def_path_str = tcx.def_path_str(*def_id);
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.pruned.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                let mut merged_usage_map = collection.1.clone();
+                for (.., other_collection) in collected_entries.iter() {
+                    merged_usage_map.merge(other_collection.1.clone());
+                }
+                merged_usage_map.prune_unreachable_from(MonoItem::Fn(*entry_instance));
+                run_pruned_test(&def_path_str, &merged_usage_map, &expected);
+            }
+        }
+
+        let bodies_dir = self.options.out_dir.join("bodies");
+        fs::create_dir_all(&bodies_dir).expect("failed to create bodies dir");
 
-        for item_id in tcx.hir().items() {
-            let item = hir.item(item_id);
-            let def_id = item.owner_id.to_def_id();
+        let mut refinement_results_paths = HashMap::new();
+        // The graphs of all entries are only merged if the merged graph is tested, which needs all
+        // annotated entries to be analyzed.
+        let selects_entries = !self.options.filter.is_empty()
+            || self.options.entries_file.is_some()
+            || !self.options.named_entries.is_empty()
+            || self.options.public_api;
+        let merged_expected = if selects_entries {
+            None
+        } else {
+            fs::read("expected/all_entries.merged.pear.expected").ok()
+        };
+        let mut merged_refined_usage_graph: Option<RefinedUsageGraph> = None;
+        for (def_id, entry_instance, collection) in collected_entries {
             let def_path_str = tcx.def_path_str(def_id);
+            let (items, usage_map) = &*collection;
 
-            if tcx
-                .get_attrs_by_path(def_id, &pear_entry_attribute)
-                .next()
-                .is_none()
-            {
-                continue;
+            for item in items.iter() {
+                if let MonoItem::Fn(instance) = item.item()
+                    && tcx.is_mir_available(instance.def_id())
+                {
+                    let body = tcx.instance_mir(instance.def);
+                    write_atomically(
+                        bodies_dir.join(format!("{}.mir.rs", tcx.def_path_str(instance.def_id()))),
+                        body.to_string(tcx).unwrap(),
+                    )
+                    .expect("failed to write body into a file");
+                }
             }
 
-            if !self
-                .filter
-                .as_ref()
-                .map(|filter| filter.is_match(def_path_str.as_str()))
-                .unwrap_or(true)
+            // Lang items invoked by the compiler-inserted assertions and unwinding.
+            let panic_machinery: HashSet<MonoItem> = usage_map
+                .nodes()
+                .into_iter()
+                .filter(|node| is_panic_machinery(node.usage()))
+                .map(|node| node.item())
+                .collect();
+
+            let collection_results_path = self
+                .options
+                .out_dir
+                .join(format!("{def_path_str}.pear.json"));
+            if self.options.include_panic_machinery {
+                self.write_graph(usage_map, &collection_results_path, tcx)
+            } else {
+                let mut usage_map = usage_map.clone();
+                usage_map.remove_usages(MonoItem::Fn(entry_instance), is_panic_machinery);
+                self.write_graph(&usage_map, &collection_results_path, tcx)
+            }
+            .expect("failed to write collection results to a file");
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.collected.pear.expected"))
             {
-                continue;
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_collected_test(&def_path_str, usage_map, &expected);
             }
 
-            if let ItemKind::Fn(..) = &item.kind {
-                let instance =
-                    ty::Instance::new(def_id, ty::GenericArgs::identity_for_item(tcx, def_id));
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.kinds.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_kind_test(&def_path_str, usage_map, &expected);
+            }
 
-                let instance_sig: FnSig = instance_sig(instance, tcx);
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.usages.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_usage_test(&def_path_str, usage_map, &expected);
+            }
 
-                if instance_sig
-                    .inputs_and_output
-                    .iter()
-                    .any(|ty| contains_non_concrete_type(ty))
-                {
-                    println!("WARNING: the function passed to analysis contains dynamic types; MCG construction might be incomplete.")
-                }
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.reachable.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_reachable_test(
+                    &def_path_str,
+                    usage_map,
+                    MonoItem::Fn(entry_instance),
+                    &expected,
+                );
+            }
 
-                let entry_instance = match tcx.asyncness(def_id) {
-                    ty::Asyncness::Yes => {
-                        let intermediate_instance = ty::Instance::new(
-                            def_id,
-                            ty::GenericArgs::identity_for_item(tcx, def_id),
-                        );
-                        let intermediate_body = tcx.instance_mir(intermediate_instance.def);
-                        let inner_coroutine_type = intermediate_body.return_ty();
-                        let ty::TyKind::Coroutine(inner_coroutine_def_id, ..) =
-                            inner_coroutine_type.kind().clone()
-                        else {
-                            unreachable!()
-                        };
-                        ty::Instance::new(
-                            inner_coroutine_def_id,
-                            ty::GenericArgs::identity_for_item(tcx, inner_coroutine_def_id),
-                        )
-                    }
-                    ty::Asyncness::No => {
-                        ty::Instance::new(def_id, ty::GenericArgs::identity_for_item(tcx, def_id))
-                    }
-                };
-
-                let (items, usage_map) = collect_from(tcx, MonoItem::Fn(entry_instance));
-
-                for item in items.iter() {
-                    if let MonoItem::Fn(instance) = item.item()
-                        && tcx.is_mir_available(instance.def_id())
-                    {
-                        let body = tcx.instance_mir(instance.def);
-                        fs::create_dir_all("bodies").expect("failed to create bodies dir");
-                        fs::write(
-                            format!("bodies/{}.mir.rs", tcx.def_path_str(instance.def_id())),
-                            body.to_string(tcx).unwrap(),
-                        )
-                        .expect("failed to write body into a file");
-                    }
+            if self.options.validate_mono {
+                let discrepancies = validate_against_monomorphization(tcx, items);
+                for item in discrepancies.missing.iter() {
+                    println!("WARNING: {def_path_str} does not collect {item} collected by rustc.");
                 }
+                for item in discrepancies.extra.iter() {
+                    println!("WARNING: {def_path_str} collects {item} not collected by rustc.");
+                }
+                if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.mono.pear.expected")) {
+                    let expected =
+                        String::from_utf8(bytes).expect("failed to parse the expected file");
+                    run_mono_test(&def_path_str, &discrepancies, &expected);
+                }
+            }
 
-                let serialized_collection_results = serde_json::to_string_pretty(&usage_map)
-                    .expect("failed to serialize collection results");
-                fs::write(
-                    format!("{def_path_str}.pear.json"),
-                    serialized_collection_results,
+            let reachable_items = items.len();
+            // Refine the collection made above rather than collecting from the entry again.
+            let refined_usage_graph = refine_from(
+                entry_instance,
+                items,
+                self.options.refiner_config.clone(),
+                tcx,
+            );
+
+            let summary = EntrySummary::new(reachable_items, &refined_usage_graph);
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.summary.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_summary_test(&def_path_str, &summary, &expected);
+            }
+            write_atomically_with(
+                self.options
+                    .out_dir
+                    .join(format!("{def_path_str}.summary.json")),
+                |writer| {
+                    serde_json::to_writer_pretty(writer, &summary)?;
+                    Ok(())
+                },
+            )
+            .expect("failed to write the summary to a file");
+
+            let refinement_results_path = self
+                .out_dir
+                .join(format!("{def_path_str}.refined.pear.json"));
+            if self.options.include_panic_machinery {
+                self.write_graph(&refined_usage_graph, &refinement_results_path, tcx)
+            } else {
+                let mut refined_usage_graph = refined_usage_graph.clone();
+                refined_usage_graph.remove_instances(|instance| {
+                    panic_machinery.contains(&MonoItem::Fn(*instance))
+                });
+                self.write_graph(&refined_usage_graph, &refinement_results_path, tcx)
+            }
+            .expect("failed to write refinement results to a file");
+
+            if !self.options.include_panic_machinery
+                && let Ok(bytes) = fs::read(format!("expected/{def_path_str}.hidden.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_hidden_test(
+                    &def_path_str,
+                    &[
+                        &read_results(&collection_results_path),
+                        &read_results(&refinement_results_path),
+                    ],
+                    &expected,
+                );
+            }
+
+            report_ambiguous_fn_ptr_calls(&def_path_str, &refined_usage_graph);
+            report_refiner_errors(&def_path_str, &refined_usage_graph);
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.refined.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_test(&def_path_str, &refined_usage_graph, &expected);
+            }
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.resolution.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_resolution_test(&def_path_str, &refined_usage_graph, &expected);
+            }
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.unresolved.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_unresolved_test(&def_path_str, &refined_usage_graph, &expected, tcx);
+            }
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.absent.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_absent_test(
+                    "absent",
+                    &def_path_str,
+                    &refined_usage_graph,
+                    &expected,
+                    tcx,
+                );
+            }
+
+            let baseline_path = self
+                .options
+                .baseline_dir
+                .join(format!("{def_path_str}.portable.pear.json"));
+            if self.options.write_baseline {
+                fs::create_dir_all(&self.options.baseline_dir)
+                    .expect("failed to create baseline dir");
+                write_atomically_with(&baseline_path, |writer| {
+                    serde_json::to_writer_pretty(writer, &refined_usage_graph.to_portable(tcx))?;
+                    Ok(())
+                })
+                .expect("failed to write the baseline to a file");
+            } else if let Ok(bytes) = fs::read(&baseline_path) {
+                let baseline: PortableRefinedUsageGraph =
+                    serde_json::from_slice(&bytes).expect("failed to parse the baseline file");
+                let diff = refined_usage_graph.diff(&baseline, tcx);
+                if !diff.is_empty() {
+                    println!(
+                        "Graph of {def_path_str} differs from the baseline: {} added and {} removed edges, {} newly ambiguous.",
+                        diff.added_edges.len(),
+                        diff.removed_edges.len(),
+                        diff.newly_ambiguous.len()
+                    );
+                }
+                write_atomically_with(
+                    self.options
+                        .out_dir
+                        .join(format!("{def_path_str}.diff.json")),
+                    |writer| {
+                        serde_json::to_writer_pretty(writer, &diff)?;
+                        Ok(())
+                    },
                 )
-                .expect("failed to write collection results to a file");
+                .expect("failed to write the diff to a file");
+            }
 
-                let refined_usage_graph = refine_from(entry_instance, items, tcx);
-                let serialized_refinement_results =
-                    serde_json::to_string_pretty(&refined_usage_graph)
-                        .expect("failed to serialize refinement results");
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.portable.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_portable_test(&def_path_str, &refined_usage_graph, &expected, tcx);
+            }
 
-                if let Ok(bytes) =
-                    fs::read(format!("expected/{def_path_str}.refined.pear.expected"))
-                {
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.callsites.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_call_sites_test(&def_path_str, &refined_usage_graph, &expected);
+            }
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.reaches.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_reaches_test(&def_path_str, &refined_usage_graph, &expected, tcx);
+            }
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.cycles.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_cycles_test(&def_path_str, &refined_usage_graph, &expected);
+            }
+
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.unanalyzed.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_unanalyzed_test(&def_path_str, &refined_usage_graph, &expected);
+            }
+
+            if self.options.refiner_config.require_unique_fn_ptr
+                && let Ok(bytes) =
+                    fs::read(format!("expected/{def_path_str}.ambiguous.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_ambiguity_test(&def_path_str, &refined_usage_graph, &expected);
+            }
+
+            if self.options.refiner_config.narrow_dyn_calls
+                && let Ok(bytes) =
+                    fs::read(format!("expected/{def_path_str}.narrowed.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_absent_test(
+                    "narrowed",
+                    &def_path_str,
+                    &refined_usage_graph,
+                    &expected,
+                    tcx,
+                );
+            }
+
+            if self.options.refiner_config.closure_captures
+                && let Ok(bytes) =
+                    fs::read(format!("expected/{def_path_str}.captures.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_captures_test(&def_path_str, &refined_usage_graph, &expected);
+            }
+
+            if self.options.strip_spans
+                && let Ok(bytes) =
+                    fs::read(format!("expected/{def_path_str}.stripped.pear.expected"))
+            {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_stripped_test(
+                    &def_path_str,
+                    &read_results(&refinement_results_path),
+                    &expected,
+                );
+            }
+
+            refinement_results_paths.insert(def_path_str.clone(), refinement_results_path);
+
+            if merged_expected.is_some() {
+                match merged_refined_usage_graph.as_mut() {
+                    Some(merged) => merged.merge(refined_usage_graph.clone()),
+                    None => merged_refined_usage_graph = Some(refined_usage_graph.clone()),
+                }
+            }
+
+            if self.options.emit_dot {
+                let dot = refined_usage_graph.to_dot(tcx);
+                if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.dot.pear.expected")) {
                     let expected =
                         String::from_utf8(bytes).expect("failed to parse the expected file");
-                    run_test(&def_path_str, &refined_usage_graph, &expected);
+                    run_dot_test(&def_path_str, &dot, &expected);
                 }
-
-                fs::write(
-                    format!("{def_path_str}.refined.pear.json"),
-                    serialized_refinement_results,
+                write_atomically(
+                    self.options
+                        .out_dir
+                        .join(format!("{def_path_str}.refined.dot")),
+                    dot,
                 )
-                .expect("failed to write refinement results to a file");
+                .expect("failed to write refined graph in DOT format to a file");
+            }
+        }
+
+        if let (Some(bytes), Some(merged_refined_usage_graph)) =
+            (merged_expected, merged_refined_usage_graph)
+        {
+            let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+            run_test("all entries", &merged_refined_usage_graph, &expected);
+        }
+
+        // Nodes reached from several entries must be serialized identically in each of them.
+        for def_path_str in refinement_results_paths.keys().sorted() {
+            if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.shared.pear.expected")) {
+                let expected = String::from_utf8(bytes).expect("failed to parse the expected file");
+                run_shared_node_test(def_path_str, &refinement_results_paths, &expected);
             }
         }
+        print_profile();
         colored::control::unset_override();
         rustc_driver::Compilation::Continue
     }
 }
 
+/// Returns all functions and methods (inherent, trait impl, or trait default ones) annotated with
+/// `#[pear::analysis_entry]`.
+fn annotated_entries(tcx: TyCtxt) -> Vec<DefId> {
+    let pear_entry_attribute = [Symbol::intern("pear"), Symbol::intern("analysis_entry")];
+
+    tcx.hir()
+        .body_owners()
+        .filter(|local_def_id| {
+            matches!(tcx.def_kind(*local_def_id), DefKind::Fn | DefKind::AssocFn)
+        })
+        .map(|local_def_id| local_def_id.to_def_id())
+        .filter(|def_id| {
+            tcx.get_attrs_by_path(*def_id, &pear_entry_attribute)
+                .next()
+                .is_some()
+        })
+        .collect()
+}
+
+/// Returns all functions and methods that are reachable from outside of the crate.
+fn public_api_entries(tcx: TyCtxt) -> Vec<DefId> {
+    let effective_visibilities = tcx.effective_visibilities(());
+
+    tcx.hir()
+        .body_owners()
+        .filter(|local_def_id| {
+            matches!(tcx.def_kind(*local_def_id), DefKind::Fn | DefKind::AssocFn)
+                && effective_visibilities.is_exported(*local_def_id)
+        })
+        .map(|local_def_id| local_def_id.to_def_id())
+        .filter(|def_id| {
+            let is_generic = tcx.generics_of(*def_id).requires_monomorphization(tcx);
+            if is_generic {
+                println!(
+                    "WARNING: skipping generic public function {}.",
+                    tcx.def_path_str(*def_id)
+                );
+            }
+            !is_generic
+        })
+        .collect()
+}
+
+/// Returns the functions listed in the entries file, which is either a JSON array or a text file with
+/// one entry per line. Each entry is a def path or a `file:line` location inside the function body.
+fn file_entries(path: &str, tcx: TyCtxt) -> Vec<DefId> {
+    let contents = fs::read_to_string(path).expect("failed to read the entries file");
+    let entries = if path.ends_with(".json") {
+        serde_json::from_str::<Vec<String>>(&contents).expect("failed to parse the entries file")
+    } else {
+        contents
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect()
+    };
+
+    let candidates = tcx
+        .hir()
+        .body_owners()
+        .filter(|local_def_id| {
+            matches!(tcx.def_kind(*local_def_id), DefKind::Fn | DefKind::AssocFn)
+        })
+        .collect_vec();
+    let source_map = tcx.sess.source_map();
+
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| !entry.is_empty() && !entry.starts_with('#'))
+        .filter_map(|(index, entry)| {
+            let location = entry
+                .rsplit_once(':')
+                .and_then(|(file, line)| Some((file, line.parse::<usize>().ok()?)));
+            let resolved = match location {
+                // Pick the innermost function containing the line.
+                Some((file, line)) => candidates
+                    .iter()
+                    .filter(|local_def_id| {
+                        let span = tcx.source_span(**local_def_id);
+                        let lo = source_map.lookup_char_pos(span.lo());
+                        let hi = source_map.lookup_char_pos(span.hi());
+                        lo.file.name.prefer_local().to_string().ends_with(file)
+                            && (lo.line..=hi.line).contains(&line)
+                    })
+                    .min_by_key(|local_def_id| {
+                        let span = tcx.source_span(**local_def_id);
+                        span.hi() - span.lo()
+                    }),
+                None => candidates
+                    .iter()
+                    .find(|local_def_id| tcx.def_path_str(local_def_id.to_def_id()) == *entry),
+            };
+            if resolved.is_none() {
+                println!(
+                    "{}",
+                    format!(
+                        "ERROR: {path}:{}: failed to resolve entry {entry}.",
+                        index + 1
+                    )
+                    .red()
+                );
+            }
+            resolved.map(|local_def_id| local_def_id.to_def_id())
+        })
+        .collect()
+}
+
+/// Constructs the instance to start the analysis from, which is the inner coroutine for async
+/// functions. Returns `None` if the coroutine of an async function cannot be found.
+fn entry_instance<'tcx>(def_id: DefId, tcx: TyCtxt<'tcx>) -> Option<ty::Instance<'tcx>> {
+    let instance = ty::Instance::new(def_id, ty::GenericArgs::identity_for_item(tcx, def_id));
+
+    let instance_sig: FnSig = instance_sig(instance, tcx);
+
+    if instance_sig
+        .inputs_and_output
+        .iter()
+        .any(|ty| contains_non_concrete_type(ty))
+    {
+        println!("WARNING: the function passed to analysis contains dynamic types; MCG construction might be incomplete.")
+    }
+
+    match tcx.asyncness(def_id) {
+        ty::Asyncness::Yes => {
+            let intermediate_body = tcx.instance_mir(instance.def);
+            let Some(inner_coroutine_def_id) = inner_coroutine(intermediate_body.return_ty(), tcx)
+            else {
+                println!(
+                    "{}",
+                    format!(
+                        "ERROR: failed to find the coroutine returned by async fn {}, skipping it.",
+                        tcx.def_path_str(def_id)
+                    )
+                    .red()
+                );
+                return None;
+            };
+            Some(ty::Instance::new(
+                inner_coroutine_def_id,
+                ty::GenericArgs::identity_for_item(tcx, inner_coroutine_def_id),
+            ))
+        }
+        ty::Asyncness::No => Some(instance),
+    }
+}
+
+/// Returns the coroutine within the type, looking through pins, boxes, references, and opaque
+/// types.
+fn inner_coroutine<'tcx>(ty: Ty<'tcx>, tcx: TyCtxt<'tcx>) -> Option<DefId> {
+    match ty.kind() {
+        ty::Coroutine(def_id, ..) => Some(*def_id),
+        ty::Adt(adt_def, args)
+            if adt_def.is_box() || tcx.lang_items().pin_type() == Some(adt_def.did()) =>
+        {
+            inner_coroutine(args.type_at(0), tcx)
+        }
+        ty::Ref(_, ty, _) => inner_coroutine(*ty, tcx),
+        ty::Alias(ty::Opaque, alias_ty) => inner_coroutine(
+            tcx.type_of(alias_ty.def_id).instantiate(tcx, alias_ty.args),
+            tcx,
+        ),
+        _ => None,
+    }
+}
+
+/// Returns true if the instance is among the collected items.
+fn reaches<'a, 'tcx: 'a>(
+    items: impl IntoIterator<Item = &'a Node<'tcx>>,
+    instance: ty::Instance<'tcx>,
+) -> bool {
+    items
+        .into_iter()
+        .any(|node| node.item() == MonoItem::Fn(instance))
+}
+
+fn run_entries_test(label: &str, entries: &Vec<String>, expected: &str) {
+    println!("{}", format!("  [{label} entries]").blue().bold());
+    let expected_entries = expected.lines().map(String::from).collect_vec();
+    for entry in expected_entries.iter() {
+        if !entries.contains(entry) {
+            println!("{}", "    Test failed.".red().bold());
+            println!("{}", format!("      {entry} is not analyzed.").red());
+            return;
+        }
+    }
+    for entry in entries.iter() {
+        if !expected_entries.contains(entry) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {entry} is unexpectedly analyzed.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line has the form `<InstanceDef kind> <instance substring>`.
+fn run_kind_test(def_path_str: &str, usage_map: &UsageGraph, expected: &str) {
+    println!("{}", format!("  [{def_path_str} kinds]").blue().bold());
+    let nodes = usage_map
+        .nodes()
+        .into_iter()
+        .filter_map(|node| node.kind().map(|kind| (kind, node.item().to_string())))
+        .collect_vec();
+    for line in expected.lines() {
+        let (kind, item) = line.split_once(' ').unwrap_or((line, ""));
+        if !nodes
+            .iter()
+            .any(|(node_kind, node_item)| *node_kind == kind && node_item.contains(item))
+        {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      no {kind} node matching {item} is present in the usage graph.")
+                    .red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is a substring of items that must have been pruned from the usage graph.
+fn run_pruned_test(def_path_str: &str, usage_map: &UsageGraph, expected: &str) {
+    println!("{}", format!("  [{def_path_str} pruned]").blue().bold());
+    let items = usage_map
+        .items()
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect_vec();
+    for line in expected.lines() {
+        if let Some(item) = items.iter().find(|item| item.contains(line)) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {item} is still present in the pruned graph.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line has the form `<Usage variant> <item substring>`.
+fn run_usage_test(def_path_str: &str, usage_map: &UsageGraph, expected: &str) {
+    println!("{}", format!("  [{def_path_str} usages]").blue().bold());
+    let nodes = usage_map
+        .nodes()
+        .into_iter()
+        .map(|node| (format!("{:?}", node.usage()), node.item().to_string()))
+        .collect_vec();
+    for line in expected.lines() {
+        let (usage, item) = line.split_once(' ').unwrap_or((line, ""));
+        if !nodes.iter().any(|(node_usage, node_item)| {
+            node_usage.split([' ', '{']).next() == Some(usage) && node_item.contains(item)
+        }) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      no {usage} node matching {item} is present in the usage graph.")
+                    .red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is a substring of a mono item transitively used by the entry, which in turn
+/// has to be among the transitive users of that item.
+fn run_reachable_test<'tcx>(
+    def_path_str: &str,
+    usage_map: &UsageGraph<'tcx>,
+    entry: MonoItem<'tcx>,
+    expected: &str,
+) {
+    println!("{}", format!("  [{def_path_str} reachable]").blue().bold());
+    let reachable = usage_map.reachable_from(entry);
+    for line in expected.lines() {
+        let Some(item) = reachable
+            .iter()
+            .find(|item| item.to_string().contains(line))
+        else {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {line} is not reachable from the entry.").red()
+            );
+            return;
+        };
+        if !usage_map.callers_of(*item).contains(&entry) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      the entry is not among the callers of {line}.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is "<callee> <caller> <span>", where the callee must be called from the
+/// caller at a call site whose span starts with the given location.
+fn run_call_sites_test(
+    def_path_str: &str,
+    refined_usage_graph: &RefinedUsageGraph,
+    expected: &str,
+) {
+    println!("{}", format!("  [{def_path_str} call sites]").blue().bold());
+    let instances = refined_usage_graph.instances();
+    for line in expected.lines() {
+        let mut parts = line.split(' ');
+        let (Some(callee), Some(caller), Some(span)) = (parts.next(), parts.next(), parts.next())
+        else {
+            println!("{}", "    Test failed.".red().bold());
+            println!("{}", format!("      malformed expected line {line}.").red());
+            return;
+        };
+        let found = instances
+            .iter()
+            .filter(|instance| instance.to_string().contains(callee))
+            .flat_map(|instance| refined_usage_graph.callers_of(*instance))
+            .any(|(call_site_caller, call_site_span)| {
+                call_site_caller.to_string().contains(caller)
+                    && format!("{call_site_span:?}").starts_with(span)
+            });
+        if !found {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {callee} is not called from {caller} at {span}.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is the def path of a function that must be reachable from the entry.
+fn run_reaches_test<'tcx>(
+    def_path_str: &str,
+    refined_usage_graph: &RefinedUsageGraph<'tcx>,
+    expected: &str,
+    tcx: TyCtxt<'tcx>,
+) {
+    println!("{}", format!("  [{def_path_str} reaches]").blue().bold());
+    for line in expected.lines() {
+        match refined_usage_graph.reaches(line, tcx) {
+            Some(path) => println!("    {line} is reached via {}", path.display(tcx)),
+            None => {
+                println!("{}", "    Test failed.".red().bold());
+                println!("{}", format!("      {line} is not reached.").red());
+                return;
+            }
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is "<caller> <callee>", where the call from the caller to the callee must
+/// close a cycle. No other cycles may be detected.
+fn run_cycles_test(def_path_str: &str, refined_usage_graph: &RefinedUsageGraph, expected: &str) {
+    println!("{}", format!("  [{def_path_str} cycles]").blue().bold());
+    let recursive_edges = refined_usage_graph.recursive_edges();
+    for line in expected.lines() {
+        let Some((caller, callee)) = line.split_once(' ') else {
+            println!("{}", "    Test failed.".red().bold());
+            println!("{}", format!("      malformed expected line {line}.").red());
+            return;
+        };
+        if !recursive_edges
+            .iter()
+            .any(|(recursive_caller, recursive_callee)| {
+                recursive_caller.to_string().contains(caller)
+                    && recursive_callee.to_string().contains(callee)
+            })
+        {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      the call from {caller} to {callee} is not a cycle.").red()
+            );
+            return;
+        }
+    }
+    if recursive_edges.len() != expected.lines().count() {
+        println!("{}", "    Test failed.".red().bold());
+        println!(
+            "{}",
+            format!(
+                "      expected {} cycles, detected {}.",
+                expected.lines().count(),
+                recursive_edges.len()
+            )
+            .red()
+        );
+        return;
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is "<other entry> <instance substring>". The matching instance must be
+/// serialized as the same node in both entries, while the spans it is reached from differ.
+fn run_shared_node_test(
+    def_path_str: &str,
+    refinement_results_paths: &HashMap<String, PathBuf>,
+    expected: &str,
+) {
+    println!(
+        "{}",
+        format!("  [{def_path_str} shared nodes]").blue().bold()
+    );
+    // Collects the serialized concrete nodes of the instance, split into identity and spans.
+    let nodes_of = |serialized: &str, instance: &str| {
+        let value: serde_json::Value =
+            serde_json::from_str(serialized).expect("failed to parse refinement results");
+        let mut identities = HashSet::new();
+        let mut spans = HashSet::new();
+        for node in value["forward_edges"]
+            .as_object()
+            .into_iter()
+            .flat_map(|edges| edges.values())
+            .filter_map(|nodes| nodes.as_array())
+            .flatten()
+        {
+            let Some(concrete) = node.get("Concrete") else {
+                continue;
+            };
+            let Some(node_instance) = concrete["instance"].as_str() else {
+                continue;
+            };
+            if node_instance.contains(instance) {
+                identities.insert(node_instance.to_string());
+                spans.insert(concrete["span"].to_string());
+            }
+        }
+        (identities, spans)
+    };
+    for line in expected.lines() {
+        let (other_def_path_str, instance) = line.split_once(' ').unwrap_or((line, ""));
+        let Some(other_refinement_results_path) = refinement_results_paths.get(other_def_path_str)
+        else {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {other_def_path_str} is not analyzed.").red()
+            );
+            return;
+        };
+        let (identities, spans) = nodes_of(
+            &read_results(&refinement_results_paths[def_path_str]),
+            instance,
+        );
+        let (other_identities, other_spans) =
+            nodes_of(&read_results(other_refinement_results_path), instance);
+        if identities.is_empty() || identities != other_identities {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {instance} is not serialized identically in {other_def_path_str}.")
+                    .red()
+            );
+            return;
+        }
+        if !spans.is_disjoint(&other_spans) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {instance} is reached from the same span in {other_def_path_str}.")
+                    .red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// The expected file holds the relation of the collected items to the ones collected by rustc.
+fn run_mono_test(def_path_str: &str, discrepancies: &MonoItemDiscrepancies, expected: &str) {
+    println!("{}", format!("  [{def_path_str} mono]").blue().bold());
+    let relation = format!("{:?}", discrepancies.relation());
+    if relation != expected.trim() {
+        println!("{}", "    Test failed.".red().bold());
+        println!(
+            "{}",
+            format!(
+                "      expected relation {} but got {relation}.",
+                expected.trim()
+            )
+            .red()
+        );
+        return;
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line must be present in the DOT output.
+fn run_dot_test(def_path_str: &str, dot: &str, expected: &str) {
+    println!("{}", format!("  [{def_path_str} dot]").blue().bold());
+    for line in expected.lines() {
+        if !dot.contains(line) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {line} is not present in the DOT output.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+fn is_panic_machinery(usage: Usage) -> bool {
+    matches!(usage, Usage::Assert | Usage::Unwind)
+}
+
+/// Each expected line is a summary field followed by its value.
+fn run_summary_test(def_path_str: &str, summary: &EntrySummary, expected: &str) {
+    println!("{}", format!("  [{def_path_str} summary]").blue().bold());
+    let summary = serde_json::to_value(summary).expect("failed to serialize the summary");
+    for line in expected.lines() {
+        let (field, value) = line.split_once(' ').unwrap_or((line, ""));
+        if summary[field].to_string() != value {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!(
+                    "      expected {field} to be {value} but got {}.",
+                    summary[field]
+                )
+                .red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Reads back the serialized results written for a test.
+fn read_results(path: &Path) -> String {
+    fs::read_to_string(path).expect("failed to read the serialized results")
+}
+
+/// Each expected line must not appear anywhere in the serialized graphs.
+fn run_hidden_test(def_path_str: &str, serialized_graphs: &[&String], expected: &str) {
+    println!("{}", format!("  [{def_path_str} hidden]").blue().bold());
+    for line in expected.lines() {
+        if serialized_graphs
+            .iter()
+            .any(|serialized_graph| serialized_graph.contains(line))
+        {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {line} is present in the serialized graphs.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Compares the span-free refined graph against the expected one verbatim.
+fn run_stripped_test(def_path_str: &str, serialized_refinement_results: &str, expected: &str) {
+    println!("{}", format!("  [{def_path_str} stripped]").blue().bold());
+    if serialized_refinement_results.trim() != expected.trim() {
+        println!("{}", "    Test failed.".red().bold());
+        println!(
+            "{}",
+            "      stripped refined graph does not match the expected one.".red()
+        );
+        return;
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+fn report_ambiguous_fn_ptr_calls(def_path_str: &str, refined_usage_graph: &RefinedUsageGraph) {
+    for ambiguous_call in refined_usage_graph.ambiguous_fn_ptr_calls() {
+        println!(
+            "{}",
+            format!(
+                "WARNING: function pointer call in {def_path_str} at {:?} is ambiguous; candidates = [{}]",
+                ambiguous_call.span(),
+                ambiguous_call
+                    .candidates()
+                    .iter()
+                    .map(|candidate| candidate.to_string())
+                    .join(", ")
+            )
+            .yellow()
+        );
+    }
+}
+
+fn report_refiner_errors(def_path_str: &str, refined_usage_graph: &RefinedUsageGraph) {
+    for error in refined_usage_graph.errors() {
+        println!(
+            "{}",
+            format!(
+                "WARNING: skipped refining a call in {} at {:?} reached from {def_path_str}; kind = {:?}",
+                error.caller(),
+                error.span(),
+                error.kind()
+            )
+            .yellow()
+        );
+    }
+}
+
+fn run_ambiguity_test(def_path_str: &str, refined_usage_graph: &RefinedUsageGraph, expected: &str) {
+    println!(
+        "{}",
+        format!("  [{def_path_str} ambiguities]").blue().bold()
+    );
+    let candidates = refined_usage_graph
+        .ambiguous_fn_ptr_calls()
+        .iter()
+        .flat_map(|ambiguous_call| ambiguous_call.candidates())
+        .map(|candidate| candidate.to_string())
+        .collect_vec();
+    for line in expected.lines() {
+        if !candidates.contains(&String::from(line)) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {line} is not reported as an ambiguous candidate.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
 fn run_test(def_path_str: &str, refined_usage_graph: &RefinedUsageGraph, expected: &str) {
     println!("{}", format!("  [{def_path_str}]").blue().bold(),);
     let instances = refined_usage_graph
@@ -164,12 +1220,253 @@ fn run_test(def_path_str: &str, refined_usage_graph: &RefinedUsageGraph, expecte
         .into_iter()
         .map(|instance| instance.to_string())
         .collect_vec();
+    check_all_present("refined graph", &instances, expected);
+}
+
+/// Each expected line is a mono item of the usage graph, so that the collector is checked
+/// independently of the refiner. Functions are named by their instance, without the `fn` prefix of
+/// mono items.
+fn run_collected_test(def_path_str: &str, usage_map: &UsageGraph, expected: &str) {
+    println!("{}", format!("  [{def_path_str} collected]").blue().bold());
+    let items = usage_map
+        .items()
+        .into_iter()
+        .map(|item| match item {
+            MonoItem::Fn(instance) => instance.to_string(),
+            item => item.to_string(),
+        })
+        .collect_vec();
+    check_all_present("usage graph", &items, expected);
+}
+
+/// Checks that each expected line is one of the items. Every line is checked, so that all outdated
+/// expectations are reported at once.
+fn check_all_present(graph: &str, items: &[String], expected: &str) {
+    let (present, missing): (Vec<_>, Vec<_>) = expected
+        .lines()
+        .partition(|line| items.iter().any(|item| item == line));
+    if !missing.is_empty() {
+        println!("{}", "    Test failed.".red().bold());
+        for line in missing.iter() {
+            println!(
+                "{}",
+                format!("      {line} is not present in the {graph}.").red()
+            );
+        }
+        for line in present.iter() {
+            println!("      {line} is present in the {graph}.");
+        }
+        println!(
+            "{}",
+            format!(
+                "    {} of {} expected items are present.",
+                present.len(),
+                present.len() + missing.len()
+            )
+            .red()
+        );
+        return;
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is a substring of an instance whose body was not analyzed.
+fn run_unanalyzed_test(
+    def_path_str: &str,
+    refined_usage_graph: &RefinedUsageGraph,
+    expected: &str,
+) {
+    println!("{}", format!("  [{def_path_str} unanalyzed]").blue().bold());
+    let unanalyzed = refined_usage_graph
+        .instances()
+        .into_iter()
+        .filter(|instance| !refined_usage_graph.is_analyzed(instance))
+        .map(|instance| instance.to_string())
+        .collect_vec();
+    for line in expected.lines() {
+        if !unanalyzed.iter().any(|instance| instance.contains(line)) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      no unanalyzed instance matching {line} is present.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is a resolution kind followed by the number of candidates of some refined
+/// call in the graph.
+fn run_resolution_test(
+    def_path_str: &str,
+    refined_usage_graph: &RefinedUsageGraph,
+    expected: &str,
+) {
+    println!("{}", format!("  [{def_path_str} resolution]").blue().bold());
+    let resolutions = refined_usage_graph
+        .instances()
+        .into_iter()
+        .flat_map(|instance| refined_usage_graph.get_forward_edges(&instance))
+        .filter_map(|refined_node| refined_node.resolution())
+        .map(|(resolution, candidates)| format!("{resolution:?} {candidates}"))
+        .collect_vec();
+    for line in expected.lines() {
+        if !resolutions.contains(&String::from(line)) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      no refined call is resolved as {line}.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is a substring of a callee (path or signature) that was left unresolved.
+fn run_unresolved_test<'tcx>(
+    def_path_str: &str,
+    refined_usage_graph: &RefinedUsageGraph<'tcx>,
+    expected: &str,
+    tcx: TyCtxt<'tcx>,
+) {
+    println!("{}", format!("  [{def_path_str} unresolved]").blue().bold());
+    let unresolved = refined_usage_graph
+        .instances()
+        .into_iter()
+        .flat_map(|instance| refined_usage_graph.get_forward_edges(&instance))
+        .filter_map(|refined_node| match refined_node {
+            RefinedNode::Unresolved { def_id, args, .. } => {
+                Some(tcx.def_path_str_with_args(def_id, args))
+            }
+            RefinedNode::UnresolvedFnPtr { sig, .. } => Some(sig.to_string()),
+            RefinedNode::Concrete { .. } | RefinedNode::Refined { .. } => None,
+        })
+        .collect_vec();
+    for line in expected.lines() {
+        if !unresolved.iter().any(|callee| callee.contains(line)) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      no unresolved callee matching {line} is present.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is an instance that must not be present in the refined graph. The function
+/// it instantiates must be in the crate, so that an expectation about a renamed function fails.
+fn run_absent_test<'tcx>(
+    label: &str,
+    def_path_str: &str,
+    refined_usage_graph: &RefinedUsageGraph<'tcx>,
+    expected: &str,
+    tcx: TyCtxt<'tcx>,
+) {
+    println!("{}", format!("  [{def_path_str} {label}]").blue().bold());
+    let instances = refined_usage_graph
+        .instances()
+        .into_iter()
+        .map(|instance| instance.to_string())
+        .collect_vec();
+    let local_def_paths = tcx
+        .hir()
+        .body_owners()
+        .map(|local_def_id| tcx.def_path_str(local_def_id.to_def_id()))
+        .collect::<HashSet<_>>();
+    for line in expected.lines() {
+        // Shims are printed after the path of the function they wrap.
+        let def_path = line.split(" - ").next().unwrap_or(line);
+        if !local_def_paths.contains(def_path) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {line} is not a function of the crate.").red()
+            );
+            return;
+        }
+        if instances.contains(&String::from(line)) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {line} is present in the refined graph.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Round-trips the refined graph through its portable form and JSON. The reloaded graph must
+/// convert back to the same portable form, and each expected line is an instance in it.
+fn run_portable_test<'tcx>(
+    def_path_str: &str,
+    refined_usage_graph: &RefinedUsageGraph<'tcx>,
+    expected: &str,
+    tcx: TyCtxt<'tcx>,
+) {
+    println!("{}", format!("  [{def_path_str} portable]").blue().bold());
+    let portable = refined_usage_graph.to_portable(tcx);
+    let serialized =
+        serde_json::to_string(&portable).expect("failed to serialize the portable graph");
+    let deserialized: PortableRefinedUsageGraph =
+        serde_json::from_str(&serialized).expect("failed to deserialize the portable graph");
+    let reloaded = match RefinedUsageGraph::from_portable(&deserialized, tcx) {
+        Ok(reloaded) => reloaded,
+        Err(err) => {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      failed to reload the graph: {err}.").red()
+            );
+            return;
+        }
+    };
+    if reloaded.to_portable(tcx) != portable {
+        println!("{}", "    Test failed.".red().bold());
+        println!(
+            "{}",
+            "      the reloaded graph differs from the original one.".red()
+        );
+        return;
+    }
+    let instances = reloaded
+        .instances()
+        .into_iter()
+        .map(|instance| instance.to_string())
+        .collect_vec();
     for line in expected.lines() {
         if !instances.contains(&String::from(line)) {
             println!("{}", "    Test failed.".red().bold());
             println!(
                 "{}",
-                format!("      {line} is not present in the refined graph.").red()
+                format!("      {line} is not present in the reloaded graph.").red()
+            );
+            return;
+        }
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is a type captured by some closure in the refined graph.
+fn run_captures_test(def_path_str: &str, refined_usage_graph: &RefinedUsageGraph, expected: &str) {
+    println!("{}", format!("  [{def_path_str} captures]").blue().bold());
+    let captures = refined_usage_graph
+        .instances()
+        .into_iter()
+        .filter_map(|instance| refined_usage_graph.closure_captures(&instance).cloned())
+        .flatten()
+        .map(|ty| ty.to_string())
+        .collect_vec();
+    for line in expected.lines() {
+        if !captures.contains(&String::from(line)) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      {line} is not captured by any closure.").red()
             );
             return;
         }
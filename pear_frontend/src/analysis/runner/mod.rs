@@ -1,6 +1,5 @@
 mod caching_local;
 mod dumping_global;
-mod util;
 
 pub use caching_local::CachedBodyAnalysis;
-pub use dumping_global::DumpingGlobalAnalysis;
+pub use dumping_global::{DumpingGlobalAnalysis, DumpingOptions};
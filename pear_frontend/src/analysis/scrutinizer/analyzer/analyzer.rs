@@ -1,30 +1,45 @@
-use std::fs;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use itertools::Itertools;
-use pear_backend::RefinedUsageGraph;
-use regex::Regex;
-use rustc_middle::mir::{Local, Mutability, VarDebugInfoContents};
-use rustc_middle::ty::{Instance, TyCtxt};
-use rustc_span::symbol::Symbol;
+use pear_backend::{instance_sig, profile, RefinedUsageGraph};
+use rustc_middle::mir::{Local, Mutability};
+use rustc_middle::ty::{Instance, TyCtxt, TyKind};
+use rustc_span::Symbol;
 use rustc_utils::BodyExt;
 
 use super::result::ImpurityReason;
 use crate::analysis::scrutinizer::analyzer::{
-    heuristics::{HasRawPtrDeref, HasTransmuteAndCopy},
-    result::{FunctionWithMetadata, PurityAnalysisResult},
+    heuristics::{HasAtomicOp, HasRawPtrDeref, HasStaticMutAccess, HasTransmuteAndCopy},
+    matcher::ItemMatcher,
+    result::{FunctionWithMetadata, ImpurityWitness, PurityAnalysisResult},
 };
 use crate::analysis::scrutinizer::important::compute_dependent_terminators;
 use crate::analysis::scrutinizer::scrutinizer_local::{
     substituted_mir, ScrutinizerBody, SubstitutedMirErrorKind,
 };
+use crate::analysis::utils::is_ignored;
 
 pub struct ScrutinizerAnalysis<'tcx> {
     passing_calls: Vec<FunctionWithMetadata<'tcx>>,
     failing_calls: Vec<FunctionWithMetadata<'tcx>>,
+    witnesses: Vec<ImpurityWitness<'tcx>>,
     storage: RefinedUsageGraph<'tcx>,
-    allowlist: Vec<Regex>,
-    trusted_stdlib: Vec<Regex>,
+    allowlist: ItemMatcher,
+    trusted_stdlib: ItemMatcher,
     stack: Vec<Instance<'tcx>>,
+    treat_recursion_as_impure: bool,
+    // Calls nested deeper than this are trusted without being analyzed.
+    max_depth: Option<usize>,
+    // Whether a recursive call was encountered and treated as impure.
+    recursion_detected: bool,
+    // Whether a function writing through a raw pointer was encountered.
+    raw_pointer_write_detected: bool,
+    // Whether a call to a foreign function was encountered.
+    foreign_call_detected: bool,
+    bodies_dir: PathBuf,
     tcx: TyCtxt<'tcx>,
 }
 
@@ -34,20 +49,33 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
         item: Instance<'tcx>,
         maybe_body_with_facts: Option<ScrutinizerBody<'tcx>>,
         important_args: Vec<Local>,
+        depth: usize,
     ) -> bool {
         // Check if allowlisted.
-        let is_allowlisted = {
-            let def_path_str = format!("{:?}", item.def_id());
-            self.allowlist.iter().any(|lib| lib.is_match(&def_path_str))
-        };
+        let is_allowlisted = self.allowlist.is_match(item.def_id(), self.tcx);
         if is_allowlisted {
-            let info_with_metadata = FunctionWithMetadata::new(
-                item.to_owned(),
-                false,
-                is_allowlisted,
-                false,
-                important_args,
+            let info_with_metadata =
+                FunctionWithMetadata::new(item.to_owned(), important_args).with_allowlisted();
+            self.passing_calls.push(info_with_metadata);
+            return true;
+        }
+
+        // Check if audited and assumed pure by an attribute.
+        if is_assumed_pure(item, self.tcx) {
+            let info_with_metadata =
+                FunctionWithMetadata::new(item.to_owned(), important_args).with_assumed_pure();
+            self.passing_calls.push(info_with_metadata);
+            return true;
+        }
+
+        // Check if excluded from the analysis by an attribute.
+        if is_ignored(self.tcx, item.def_id()) {
+            println!(
+                "  Skipping {}, it is annotated with #[pear::ignore].",
+                self.tcx.def_path_str(item.def_id())
             );
+            let info_with_metadata =
+                FunctionWithMetadata::new(item.to_owned(), important_args).with_ignored();
             self.passing_calls.push(info_with_metadata);
             return true;
         }
@@ -55,12 +83,16 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
         // Check if has no body (i.e. intrinsic or foreign).
         let body_with_facts = match maybe_body_with_facts {
             Some(body) => {
-                dump_body(item, body.clone(), self.tcx);
+                dump_body(item, body.clone(), &self.bodies_dir, self.tcx);
                 body
             }
             None => {
-                let info_with_metadata =
-                    FunctionWithMetadata::new(item.to_owned(), false, false, false, important_args);
+                let mut info_with_metadata =
+                    FunctionWithMetadata::new(item.to_owned(), important_args);
+                if self.tcx.is_foreign_item(item.def_id()) {
+                    self.foreign_call_detected = true;
+                    info_with_metadata = info_with_metadata.with_foreign();
+                }
                 self.failing_calls.push(info_with_metadata);
                 return false;
             }
@@ -70,61 +102,35 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
         let optimized_mir = self.tcx.instance_mir(item.def);
 
         // Check if conditionally trusted as an std member.
-        let is_trusted = {
-            let def_path_str = format!("{:?}", item.def_id());
-            let trusted_stdlib_member = self
-                .trusted_stdlib
-                .iter()
-                .any(|lib| lib.is_match(&def_path_str));
-            let self_ty = {
-                optimized_mir
-                    .var_debug_info
-                    .iter()
-                    .find(|dbg_info| dbg_info.name == Symbol::intern("self"))
-                    .and_then(|self_dbg_info| match self_dbg_info.value {
-                        VarDebugInfoContents::Place(place) => Some(place),
-                        _ => None,
-                    })
-                    .and_then(|self_place| Some(self_place.ty(optimized_mir, self.tcx).ty))
-            };
-            let has_immut_self_ref = self_ty
-                .and_then(|self_ty| {
-                    Some(
-                        self_ty
-                            .ref_mutability()
-                            .and_then(|mutability| Some(mutability == Mutability::Not))
-                            .unwrap_or(false),
-                    )
-                })
-                .unwrap_or(false);
-            trusted_stdlib_member && !has_immut_self_ref
-        };
+        let is_trusted = self.trusted_stdlib.is_match(item.def_id(), self.tcx)
+            && has_trusted_receiver(item, self.tcx);
 
         // Compute raw pointer dereference and transmute heuristics.
         let has_raw_pointer_deref = optimized_mir.has_raw_ptr_deref(self.tcx);
+        let has_raw_pointer_write = optimized_mir.has_raw_ptr_write(self.tcx);
         let has_transmute_or_copy =
             optimized_mir.has_transmute_or_copy(self.tcx, important_args.clone());
+        let has_static_mut_access = optimized_mir.has_static_mut_access(self.tcx);
+        let has_atomic_op = optimized_mir.has_atomic_op(self.tcx);
+        let info_with_metadata = FunctionWithMetadata::new(item.to_owned(), important_args.clone())
+            .with_raw_pointer_deref(has_raw_pointer_deref)
+            .with_raw_pointer_write(has_raw_pointer_write)
+            .with_transmute_or_copy(has_transmute_or_copy)
+            .with_static_mut_access(has_static_mut_access)
+            .with_atomic_op(has_atomic_op);
 
         // Check if trusted.
         if is_trusted {
-            let info_with_metadata = FunctionWithMetadata::new(
-                item.to_owned(),
-                has_raw_pointer_deref,
-                is_allowlisted,
-                has_transmute_or_copy,
-                important_args,
-            );
             self.passing_calls.push(info_with_metadata);
             true
         } else {
-            if has_raw_pointer_deref || has_transmute_or_copy {
-                let info_with_metadata = FunctionWithMetadata::new(
-                    item.to_owned(),
-                    has_raw_pointer_deref,
-                    is_allowlisted,
-                    has_transmute_or_copy,
-                    important_args,
-                );
+            // Reads through raw pointers are not side effects on their own.
+            if has_raw_pointer_write
+                || has_transmute_or_copy
+                || has_static_mut_access
+                || has_atomic_op
+            {
+                self.raw_pointer_write_detected |= has_raw_pointer_write;
                 self.failing_calls.push(info_with_metadata);
                 return false;
             }
@@ -164,56 +170,65 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
                             });
 
                         if let Some(dependent_terminator) = important_terminator {
-                            child_node.instances().into_iter().all(|child_item| {
-                                if self.stack.contains(&child_item) {
-                                    return true;
-                                } else {
-                                    self.analyze_child(
-                                        child_item,
-                                        dependent_terminator.dependent_arg_indices.clone(),
-                                    )
-                                }
-                            })
+                            // Conservatively assume that unresolved callees are impure.
+                            let is_pure = !child_node.is_unresolved()
+                                && child_node.instances().into_iter().all(|child_item| {
+                                    if self.stack.contains(&child_item) {
+                                        return self.accept_recursive_call();
+                                    } else {
+                                        self.analyze_child(
+                                            child_item,
+                                            dependent_terminator.dependent_arg_indices.clone(),
+                                            depth + 1,
+                                        )
+                                    }
+                                });
+                            if !is_pure {
+                                self.witnesses
+                                    .push(ImpurityWitness::new(item, child_node.terminator_span()));
+                            }
+                            is_pure
                         } else {
                             true
                         }
                     });
 
             if has_no_leaking_calls {
-                let info_with_metadata = FunctionWithMetadata::new(
-                    item.to_owned(),
-                    has_raw_pointer_deref,
-                    is_allowlisted,
-                    has_transmute_or_copy,
-                    important_args,
-                );
                 self.passing_calls.push(info_with_metadata);
                 true
             } else {
-                let info_with_metadata = FunctionWithMetadata::new(
-                    item.to_owned(),
-                    has_raw_pointer_deref,
-                    is_allowlisted,
-                    has_transmute_or_copy,
-                    important_args,
-                );
                 self.failing_calls.push(info_with_metadata);
                 false
             }
         }
     }
 
-    fn analyze_child(&mut self, instance: Instance<'tcx>, important_arg_idx: Vec<usize>) -> bool {
-        let maybe_body_with_facts = substituted_mir(instance, self.tcx);
+    fn analyze_child(
+        &mut self,
+        instance: Instance<'tcx>,
+        important_arg_idx: Vec<usize>,
+        depth: usize,
+    ) -> bool {
         let important_args = important_arg_idx
             .iter()
             .map(|arg_num| Local::from_usize(arg_num + 1))
             .collect_vec();
 
+        // Check if nested too deep to be analyzed.
+        if self.max_depth.is_some_and(|max_depth| depth > max_depth) {
+            let info_with_metadata =
+                FunctionWithMetadata::new(instance, important_args).with_trusted_by_depth();
+            self.passing_calls.push(info_with_metadata);
+            return true;
+        }
+
+        let maybe_body_with_facts = substituted_mir(instance, self.tcx);
+
         match maybe_body_with_facts.clone() {
             Ok(body_with_facts) => {
                 self.stack.push(instance);
-                let result = self.analyze_item(instance, Some(body_with_facts), important_args);
+                let result =
+                    self.analyze_item(instance, Some(body_with_facts), important_args, depth);
                 self.stack.pop();
                 result
             }
@@ -231,9 +246,13 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
                                 .into_iter()
                                 .map(|child_item| {
                                     if self.stack.contains(&child_item) {
-                                        return true;
+                                        return self.accept_recursive_call();
                                     } else {
-                                        self.analyze_child(child_item, important_arg_idx.clone())
+                                        self.analyze_child(
+                                            child_item,
+                                            important_arg_idx.clone(),
+                                            depth + 1,
+                                        )
                                     }
                                 })
                                 .collect_vec()
@@ -244,7 +263,7 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
                 }
                 SubstitutedMirErrorKind::NoCallableMir | SubstitutedMirErrorKind::NoMirFound => {
                     self.stack.push(instance);
-                    let result = self.analyze_item(instance, None, important_args);
+                    let result = self.analyze_item(instance, None, important_args, depth);
                     self.stack.pop();
                     result
                 }
@@ -252,12 +271,25 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
         }
     }
 
+    /// Recursive calls are assumed to be pure unless recursion is treated as impure.
+    fn accept_recursive_call(&mut self) -> bool {
+        if self.treat_recursion_as_impure {
+            self.recursion_detected = true;
+            false
+        } else {
+            true
+        }
+    }
+
     pub fn run(
         functions: RefinedUsageGraph<'tcx>,
         important_args: Vec<Local>,
         annotated_pure: bool,
-        allowlist: Vec<Regex>,
-        trusted_stdlib: Vec<Regex>,
+        allowlist: ItemMatcher,
+        trusted_stdlib: ItemMatcher,
+        treat_recursion_as_impure: bool,
+        max_depth: Option<usize>,
+        bodies_dir: PathBuf,
         tcx: TyCtxt<'tcx>,
     ) -> PurityAnalysisResult<'tcx> {
         let origin = functions.root();
@@ -265,15 +297,28 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
         let mut analysis = Self {
             passing_calls: vec![],
             failing_calls: vec![],
+            witnesses: vec![],
             storage: functions,
             allowlist,
             trusted_stdlib,
             stack: vec![origin],
+            treat_recursion_as_impure,
+            max_depth,
+            recursion_detected: false,
+            raw_pointer_write_detected: false,
+            foreign_call_detected: false,
+            bodies_dir,
             tcx,
         };
 
-        let body = substituted_mir(origin, tcx).ok();
-        let pure = analysis.analyze_item(origin, body, important_args);
+        let pure = profile(
+            "purity analysis",
+            || tcx.def_path_str(origin.def_id()),
+            || {
+                let body = substituted_mir(origin, tcx).ok();
+                analysis.analyze_item(origin, body, important_args, 0)
+            },
+        );
 
         if pure {
             PurityAnalysisResult::new(
@@ -285,23 +330,73 @@ impl<'tcx> ScrutinizerAnalysis<'tcx> {
                 analysis.failing_calls,
             )
         } else {
+            let reason = if analysis.recursion_detected {
+                ImpurityReason::Recursion
+            } else if analysis.raw_pointer_write_detected {
+                ImpurityReason::RawPointerWrite
+            } else if analysis.foreign_call_detected {
+                ImpurityReason::ForeignCall
+            } else {
+                ImpurityReason::ImpureInnerFunction
+            };
+            // Failing calls are recorded innermost first, so the first one is where impurity starts.
+            let impurity_path = analysis
+                .failing_calls
+                .first()
+                .and_then(|failing_call| analysis.storage.path_to(failing_call.function(), tcx))
+                .map(|path| path.display(tcx));
             PurityAnalysisResult::new(
                 origin.def_id(),
                 annotated_pure,
                 false,
-                Some(ImpurityReason::ImpureInnerFunction),
+                Some(reason),
                 analysis.passing_calls,
                 analysis.failing_calls,
             )
+            .with_witnesses(analysis.witnesses)
+            .with_impurity_path(impurity_path)
         }
     }
 }
 
-fn dump_body<'tcx>(item: Instance<'tcx>, body: ScrutinizerBody<'tcx>, tcx: TyCtxt<'tcx>) {
+/// Returns true if the method takes its receiver as `&mut Self` or `Pin<&mut Self>`, so that
+/// trusting it only trusts the changes to the receiver. Receivers taken by value, including
+/// `Box<Self>`, and shared references are not trusted. Functions without a receiver are.
+fn has_trusted_receiver<'tcx>(item: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    let has_receiver = tcx
+        .opt_associated_item(item.def_id())
+        .is_some_and(|assoc_item| assoc_item.fn_has_self_parameter);
+    if !has_receiver {
+        return true;
+    }
+    let receiver = instance_sig(item, tcx).inputs()[0];
+    let receiver = match receiver.kind() {
+        TyKind::Adt(adt_def, args) if tcx.lang_items().pin_type() == Some(adt_def.did()) => {
+            args.type_at(0)
+        }
+        _ => receiver,
+    };
+    receiver.ref_mutability() == Some(Mutability::Mut)
+}
+
+/// Returns true if the function is annotated with `#[pear::assume_pure]`, so that it is trusted
+/// without descending into it.
+fn is_assumed_pure<'tcx>(item: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> bool {
+    let assume_pure_attribute = [Symbol::intern("pear"), Symbol::intern("assume_pure")];
+    tcx.get_attrs_by_path(item.def_id(), &assume_pure_attribute)
+        .next()
+        .is_some()
+}
+
+fn dump_body<'tcx>(
+    item: Instance<'tcx>,
+    body: ScrutinizerBody<'tcx>,
+    bodies_dir: &Path,
+    tcx: TyCtxt<'tcx>,
+) {
     let body = body.split().0;
-    fs::create_dir_all("bodies").expect("failed to create bodies dir");
     fs::write(
-        format!("bodies/{}.mir.rs", tcx.def_path_str(item.def_id())),
+        bodies_dir.join(format!("{}.mir.rs", tcx.def_path_str(item.def_id()))),
         body.to_string(tcx).unwrap(),
     )
     .expect("failed to write body into a file");
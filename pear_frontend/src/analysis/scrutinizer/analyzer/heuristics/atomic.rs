@@ -0,0 +1,68 @@
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::{visit::Visitor, Body, Location, Terminator, TerminatorKind};
+use rustc_middle::ty::{TyCtxt, TyKind};
+use rustc_span::symbol::sym;
+
+struct AtomicOpVisitor<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    body: &'a Body<'tcx>,
+    has_atomic_op: bool,
+}
+
+pub trait HasAtomicOp<'tcx> {
+    fn has_atomic_op(&self, tcx: TyCtxt<'tcx>) -> bool;
+}
+
+impl<'tcx> HasAtomicOp<'tcx> for Body<'tcx> {
+    fn has_atomic_op(&self, tcx: TyCtxt<'tcx>) -> bool {
+        let mut atomic_op_visitor = AtomicOpVisitor {
+            tcx,
+            body: self,
+            has_atomic_op: false,
+        };
+        atomic_op_visitor.visit_body(self);
+        atomic_op_visitor.has_atomic_op
+    }
+}
+
+impl<'a, 'tcx> AtomicOpVisitor<'a, 'tcx> {
+    /// Atomic operations are the methods of `core::sync::atomic` that write to the atomic
+    /// (`store`, `swap`, `fetch_*` and `compare_exchange*`) or the atomic intrinsics they bottom
+    /// out in. Loads and fences do not modify any state, so they are not counted.
+    fn is_atomic_op(&self, def_id: DefId) -> bool {
+        let name = self.tcx.item_name(def_id);
+        let name = name.as_str();
+        let is_atomic_intrinsic = self.tcx.is_intrinsic(def_id)
+            && name.starts_with("atomic_")
+            && !name.starts_with("atomic_load")
+            && !name.starts_with("atomic_fence")
+            && !name.starts_with("atomic_singlethreadfence");
+        let is_atomic_method = self.tcx.crate_name(def_id.krate) == sym::core
+            && self
+                .tcx
+                .def_path(def_id)
+                .data
+                .iter()
+                .filter_map(|data| data.data.get_opt_name())
+                .take(2)
+                .map(|name| name.to_string())
+                .eq(["sync", "atomic"])
+            && (name == "store"
+                || name == "swap"
+                || name.starts_with("fetch_")
+                || name.starts_with("compare_exchange"));
+        is_atomic_intrinsic || is_atomic_method
+    }
+}
+
+impl<'a, 'tcx> Visitor<'tcx> for AtomicOpVisitor<'a, 'tcx> {
+    fn visit_terminator(&mut self, terminator: &Terminator<'tcx>, location: Location) {
+        if let TerminatorKind::Call { func, .. } = &terminator.kind
+            && let TyKind::FnDef(def_id, _) = func.ty(self.body, self.tcx).kind()
+            && self.is_atomic_op(*def_id)
+        {
+            self.has_atomic_op = true;
+        }
+        self.super_terminator(terminator, location);
+    }
+}
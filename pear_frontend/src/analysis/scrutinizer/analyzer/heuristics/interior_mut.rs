@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+
+use rustc_middle::mir::Mutability;
+use rustc_middle::ty::{self, FnSig, Ty, TyCtxt};
+
+pub trait HasInteriorMutArg<'tcx> {
+    /// Returns true if any important argument is a shared reference to a type with interior
+    /// mutability (i.e., containing an `UnsafeCell`, such as `Cell`, `RefCell`, or `Mutex`), which
+    /// can be mutated without a mutable reference. Arguments are numbered from 1, and all of them
+    /// are important if none are given.
+    fn has_interior_mut_arg(&self, tcx: TyCtxt<'tcx>, important_args: Option<&[usize]>) -> bool;
+}
+
+impl<'tcx> HasInteriorMutArg<'tcx> for FnSig<'tcx> {
+    fn has_interior_mut_arg(&self, tcx: TyCtxt<'tcx>, important_args: Option<&[usize]>) -> bool {
+        self.inputs().iter().enumerate().any(|(index, ty)| {
+            important_args.map_or(true, |important_args| important_args.contains(&(index + 1)))
+                && matches!(ty.kind(), ty::Ref(_, pointee, Mutability::Not)
+                    if contains_unsafe_cell(*pointee, tcx, &mut HashSet::default()))
+        })
+    }
+}
+
+/// Returns true if the type holds an `UnsafeCell` inline, i.e., in the fields of its ADTs, tuples,
+/// and arrays. Pointers are not followed, and trait objects, generic parameters, and other types
+/// whose contents are unknown are not considered interior-mutable.
+fn contains_unsafe_cell<'tcx>(
+    ty: Ty<'tcx>,
+    tcx: TyCtxt<'tcx>,
+    visited: &mut HashSet<Ty<'tcx>>,
+) -> bool {
+    if !visited.insert(ty) {
+        return false;
+    }
+    match ty.kind() {
+        ty::Adt(adt_def, args) => {
+            adt_def.is_unsafe_cell()
+                || adt_def
+                    .all_fields()
+                    .any(|field| contains_unsafe_cell(field.ty(tcx, args), tcx, visited))
+        }
+        ty::Array(element_ty, _) | ty::Slice(element_ty) => {
+            contains_unsafe_cell(*element_ty, tcx, visited)
+        }
+        ty::Tuple(tys) => tys.iter().any(|ty| contains_unsafe_cell(ty, tcx, visited)),
+        _ => false,
+    }
+}
@@ -1,5 +1,11 @@
+mod atomic;
+mod interior_mut;
 mod raw_ptr;
+mod static_mut;
 mod transmute_and_copy;
 
+pub use atomic::HasAtomicOp;
+pub use interior_mut::HasInteriorMutArg;
 pub use raw_ptr::HasRawPtrDeref;
+pub use static_mut::HasStaticMutAccess;
 pub use transmute_and_copy::HasTransmuteAndCopy;
@@ -1,13 +1,14 @@
 use rustc_middle::mir::{
-    visit::Visitor, Body, Location, Mutability, Place, ProjectionElem, Rvalue, Statement,
-    StatementKind,
+    visit::{PlaceContext, Visitor},
+    Body, Location, Mutability, Place, ProjectionElem, Rvalue, Statement, StatementKind,
 };
 use rustc_middle::ty::TyCtxt;
 
 struct RawPtrDerefVisitor<'a, 'tcx> {
     tcx: TyCtxt<'tcx>,
     body: &'a Body<'tcx>,
-    has_raw_ptr_deref: bool,
+    has_raw_ptr_read: bool,
+    has_raw_ptr_write: bool,
 }
 
 struct PlaceWithBody<'a, 'tcx> {
@@ -16,29 +17,46 @@ struct PlaceWithBody<'a, 'tcx> {
 }
 
 pub trait HasRawPtrDeref<'tcx> {
+    /// Returns true if there is a read or a write through a raw pointer.
     fn has_raw_ptr_deref(&self, tcx: TyCtxt<'tcx>) -> bool;
+    /// Returns true if there is a write through a raw mutable pointer, or a mutable borrow of its
+    /// pointee.
+    fn has_raw_ptr_write(&self, tcx: TyCtxt<'tcx>) -> bool;
+}
+
+fn visit_raw_ptr_derefs<'a, 'tcx>(
+    body: &'a Body<'tcx>,
+    tcx: TyCtxt<'tcx>,
+) -> RawPtrDerefVisitor<'a, 'tcx> {
+    let mut ptr_deref_visitor = RawPtrDerefVisitor {
+        tcx,
+        body,
+        has_raw_ptr_read: false,
+        has_raw_ptr_write: false,
+    };
+    ptr_deref_visitor.visit_body(body);
+    ptr_deref_visitor
 }
 
 impl<'tcx> HasRawPtrDeref<'tcx> for Body<'tcx> {
     fn has_raw_ptr_deref(&self, tcx: TyCtxt<'tcx>) -> bool {
-        let mut ptr_deref_visitor = RawPtrDerefVisitor {
-            tcx,
-            body: self,
-            has_raw_ptr_deref: false,
-        };
-        ptr_deref_visitor.visit_body(self);
-        ptr_deref_visitor.has_raw_ptr_deref
+        let ptr_deref_visitor = visit_raw_ptr_derefs(self, tcx);
+        ptr_deref_visitor.has_raw_ptr_read || ptr_deref_visitor.has_raw_ptr_write
+    }
+
+    fn has_raw_ptr_write(&self, tcx: TyCtxt<'tcx>) -> bool {
+        visit_raw_ptr_derefs(self, tcx).has_raw_ptr_write
     }
 }
 
-impl<'a, 'tcx> HasRawPtrDeref<'tcx> for PlaceWithBody<'a, 'tcx> {
-    fn has_raw_ptr_deref(&self, tcx: TyCtxt<'tcx>) -> bool {
+impl<'a, 'tcx> PlaceWithBody<'a, 'tcx> {
+    fn derefs_raw_ptr(&self, tcx: TyCtxt<'tcx>, mutable_only: bool) -> bool {
         self.place
             .iter_projections()
             .any(|(place_ref, projection)| {
                 if let ProjectionElem::Deref = projection {
                     let ty = place_ref.ty(self.body, tcx).ty;
-                    ty.is_unsafe_ptr() && ty.is_mutable_ptr()
+                    ty.is_unsafe_ptr() && (!mutable_only || ty.is_mutable_ptr())
                 } else {
                     false
                 }
@@ -46,6 +64,16 @@ impl<'a, 'tcx> HasRawPtrDeref<'tcx> for PlaceWithBody<'a, 'tcx> {
     }
 }
 
+impl<'a, 'tcx> HasRawPtrDeref<'tcx> for PlaceWithBody<'a, 'tcx> {
+    fn has_raw_ptr_deref(&self, tcx: TyCtxt<'tcx>) -> bool {
+        self.derefs_raw_ptr(tcx, false)
+    }
+
+    fn has_raw_ptr_write(&self, tcx: TyCtxt<'tcx>) -> bool {
+        self.derefs_raw_ptr(tcx, true)
+    }
+}
+
 impl<'a, 'tcx> Visitor<'tcx> for RawPtrDerefVisitor<'a, 'tcx> {
     fn visit_statement(&mut self, statement: &Statement<'tcx>, location: Location) {
         if let StatementKind::Assign(assignment) = &statement.kind {
@@ -57,8 +85,8 @@ impl<'a, 'tcx> Visitor<'tcx> for RawPtrDerefVisitor<'a, 'tcx> {
                 body: self.body,
             };
 
-            if place_ext.has_raw_ptr_deref(self.tcx) {
-                self.has_raw_ptr_deref = true;
+            if place_ext.has_raw_ptr_write(self.tcx) {
+                self.has_raw_ptr_write = true;
             } else {
                 if let Rvalue::Ref(_, borrow_kind, borrow_place) = rvalue {
                     let borrow_place_ext = PlaceWithBody {
@@ -66,8 +94,8 @@ impl<'a, 'tcx> Visitor<'tcx> for RawPtrDerefVisitor<'a, 'tcx> {
                         body: self.body,
                     };
                     if let Mutability::Mut = borrow_kind.mutability() {
-                        if borrow_place_ext.has_raw_ptr_deref(self.tcx) {
-                            self.has_raw_ptr_deref = true;
+                        if borrow_place_ext.has_raw_ptr_write(self.tcx) {
+                            self.has_raw_ptr_write = true;
                         }
                     }
                 }
@@ -75,4 +103,18 @@ impl<'a, 'tcx> Visitor<'tcx> for RawPtrDerefVisitor<'a, 'tcx> {
         };
         self.super_statement(statement, location);
     }
+
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, location: Location) {
+        // Writes are only detected through the assignments above.
+        if let PlaceContext::NonMutatingUse(..) = context {
+            let place_ext = PlaceWithBody {
+                place,
+                body: self.body,
+            };
+            if place_ext.has_raw_ptr_deref(self.tcx) {
+                self.has_raw_ptr_read = true;
+            }
+        }
+        self.super_place(place, context, location);
+    }
 }
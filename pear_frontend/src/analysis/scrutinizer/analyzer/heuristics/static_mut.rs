@@ -0,0 +1,55 @@
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::interpret::{GlobalAlloc, Scalar};
+use rustc_middle::mir::{
+    visit::Visitor, Body, Const, ConstOperand, ConstValue, Location, Mutability, Rvalue,
+};
+use rustc_middle::ty::TyCtxt;
+
+struct StaticMutAccessVisitor<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    has_static_mut_access: bool,
+}
+
+pub trait HasStaticMutAccess<'tcx> {
+    fn has_static_mut_access(&self, tcx: TyCtxt<'tcx>) -> bool;
+}
+
+impl<'tcx> HasStaticMutAccess<'tcx> for Body<'tcx> {
+    fn has_static_mut_access(&self, tcx: TyCtxt<'tcx>) -> bool {
+        let mut static_mut_access_visitor = StaticMutAccessVisitor {
+            tcx,
+            has_static_mut_access: false,
+        };
+        static_mut_access_visitor.visit_body(self);
+        static_mut_access_visitor.has_static_mut_access
+    }
+}
+
+impl<'tcx> StaticMutAccessVisitor<'tcx> {
+    fn is_static_mut(&self, def_id: DefId) -> bool {
+        self.tcx.static_mutability(def_id) == Some(Mutability::Mut)
+    }
+}
+
+impl<'tcx> Visitor<'tcx> for StaticMutAccessVisitor<'tcx> {
+    fn visit_constant(&mut self, constant: &ConstOperand<'tcx>, location: Location) {
+        // Places rooted at a static are accessed through a pointer constant to its allocation.
+        if let Const::Val(ConstValue::Scalar(Scalar::Ptr(ptr, _)), _) = constant.const_
+            && let Some(GlobalAlloc::Static(def_id)) =
+                self.tcx.try_get_global_alloc(ptr.provenance.alloc_id())
+            && self.is_static_mut(def_id)
+        {
+            self.has_static_mut_access = true;
+        }
+        self.super_constant(constant, location);
+    }
+
+    fn visit_rvalue(&mut self, rvalue: &Rvalue<'tcx>, location: Location) {
+        if let Rvalue::ThreadLocalRef(def_id) = rvalue
+            && self.is_static_mut(*def_id)
+        {
+            self.has_static_mut_access = true;
+        }
+        self.super_rvalue(rvalue, location);
+    }
+}
@@ -0,0 +1,34 @@
+use regex::Regex;
+use rustc_hir::def_id::DefId;
+use rustc_middle::ty::TyCtxt;
+
+/// Matches items by regexes over the `Debug` output of their `DefId`, by exact def paths, or by
+/// prefixes of the names of their crates. Def paths and crate names do not depend on the `DefId`
+/// format, so they stay stable across toolchain bumps.
+#[derive(Clone, Debug, Default)]
+pub struct ItemMatcher {
+    regexes: Vec<Regex>,
+    def_paths: Vec<String>,
+    crate_prefixes: Vec<String>,
+}
+
+impl ItemMatcher {
+    pub fn new(regexes: Vec<Regex>, def_paths: Vec<String>, crate_prefixes: Vec<String>) -> Self {
+        ItemMatcher {
+            regexes,
+            def_paths,
+            crate_prefixes,
+        }
+    }
+
+    pub fn is_match(&self, def_id: DefId, tcx: TyCtxt<'_>) -> bool {
+        let def_id_str = format!("{def_id:?}");
+        self.regexes.iter().any(|re| re.is_match(&def_id_str))
+            || (!self.def_paths.is_empty() && self.def_paths.contains(&tcx.def_path_str(def_id)))
+            || self.crate_prefixes.iter().any(|crate_prefix| {
+                tcx.crate_name(def_id.krate)
+                    .as_str()
+                    .starts_with(crate_prefix.as_str())
+            })
+    }
+}
@@ -1,5 +1,10 @@
 mod analyzer;
 mod heuristics;
+mod matcher;
 mod result;
 
-pub use {analyzer::ScrutinizerAnalysis, result::ImpurityReason, result::PurityAnalysisResult};
+pub use {
+    analyzer::ScrutinizerAnalysis, heuristics::HasInteriorMutArg, matcher::ItemMatcher,
+    result::ImpurityReason, result::ImpurityWitness, result::IoEffect,
+    result::PurityAnalysisResult,
+};
@@ -1,5 +1,7 @@
+use pear_backend::serialize_span;
 use rustc_hir::def_id::DefId;
 use rustc_middle::{mir::Local, ty::Instance};
+use rustc_span::Span;
 use serde::{
     ser::{SerializeSeq, SerializeStruct, SerializeTuple},
     Serialize, Serializer,
@@ -30,36 +32,153 @@ where
 pub struct FunctionWithMetadata<'tcx> {
     #[serde(serialize_with = "serialize_instance")]
     function: Instance<'tcx>,
+    /// Any read or write through a raw pointer.
     raw_pointer_deref: bool,
+    raw_pointer_write: bool,
     allowlisted: bool,
     has_transmute_or_copy: bool,
+    static_mut_access: bool,
+    atomic_op: bool,
+    /// Trusted without analysis for being nested too deep in shallow mode.
+    trusted_by_depth: bool,
+    /// Declared in an `extern` block, so its side effects are unknown.
+    foreign: bool,
+    /// Trusted without analysis for being annotated with `#[pear::assume_pure]`.
+    assumed_pure: bool,
+    /// Skipped for being annotated with `#[pear::ignore]`, so the analysis is incomplete there.
+    ignored: bool,
     #[serde(serialize_with = "serialize_vec_local")]
     important_args: Vec<Local>,
 }
 
 impl<'tcx> FunctionWithMetadata<'tcx> {
-    pub fn new(
-        function: Instance<'tcx>,
-        raw_pointer_deref: bool,
-        allowlisted: bool,
-        has_transmute_or_copy: bool,
-        important_args: Vec<Local>,
-    ) -> Self {
+    pub fn new(function: Instance<'tcx>, important_args: Vec<Local>) -> Self {
         FunctionWithMetadata {
             function,
+            raw_pointer_deref: false,
+            raw_pointer_write: false,
+            allowlisted: false,
+            has_transmute_or_copy: false,
+            static_mut_access: false,
+            atomic_op: false,
+            trusted_by_depth: false,
+            foreign: false,
+            assumed_pure: false,
+            ignored: false,
+            important_args,
+        }
+    }
+
+    pub fn function(&self) -> Instance<'tcx> {
+        self.function
+    }
+
+    pub fn with_raw_pointer_deref(self, raw_pointer_deref: bool) -> Self {
+        Self {
             raw_pointer_deref,
-            allowlisted,
+            ..self
+        }
+    }
+
+    pub fn with_raw_pointer_write(self, raw_pointer_write: bool) -> Self {
+        Self {
+            raw_pointer_write,
+            ..self
+        }
+    }
+
+    pub fn with_allowlisted(mut self) -> Self {
+        self.allowlisted = true;
+        self
+    }
+
+    pub fn with_transmute_or_copy(self, has_transmute_or_copy: bool) -> Self {
+        Self {
             has_transmute_or_copy,
-            important_args,
+            ..self
         }
     }
+
+    pub fn with_static_mut_access(self, static_mut_access: bool) -> Self {
+        Self {
+            static_mut_access,
+            ..self
+        }
+    }
+
+    pub fn with_atomic_op(self, atomic_op: bool) -> Self {
+        Self { atomic_op, ..self }
+    }
+
+    pub fn with_trusted_by_depth(mut self) -> Self {
+        self.trusted_by_depth = true;
+        self
+    }
+
+    pub fn is_foreign(&self) -> bool {
+        self.foreign
+    }
+
+    pub fn with_foreign(mut self) -> Self {
+        self.foreign = true;
+        self
+    }
+
+    pub fn with_assumed_pure(mut self) -> Self {
+        self.assumed_pure = true;
+        self
+    }
+
+    pub fn with_ignored(mut self) -> Self {
+        self.ignored = true;
+        self
+    }
+}
+
+/// Call site within the caller that led to an impure function.
+#[derive(Serialize)]
+pub struct ImpurityWitness<'tcx> {
+    #[serde(serialize_with = "serialize_instance")]
+    caller: Instance<'tcx>,
+    #[serde(serialize_with = "serialize_span")]
+    span: Span,
+}
+
+impl<'tcx> ImpurityWitness<'tcx> {
+    pub fn new(caller: Instance<'tcx>, span: Span) -> Self {
+        ImpurityWitness { caller, span }
+    }
+
+    pub fn caller(&self) -> Instance<'tcx> {
+        self.caller
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
 }
 
 #[derive(Serialize, Debug, Clone, Copy)]
 pub enum ImpurityReason {
     MutableArguments,
+    InteriorMutableArguments,
     UnresolvedGenerics,
     ImpureInnerFunction,
+    AmbiguousFnPtr,
+    LazyInitialization,
+    IoEffects,
+    Recursion,
+    RawPointerWrite,
+    ForeignCall,
+    RefinementError,
+}
+
+/// Category of I/O performed by a reachable function.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IoEffect {
+    ReadIO,
+    WriteIO,
+    Network,
 }
 
 pub struct PurityAnalysisResult<'tcx> {
@@ -69,6 +188,10 @@ pub struct PurityAnalysisResult<'tcx> {
     reason: Option<ImpurityReason>,
     passing: Vec<FunctionWithMetadata<'tcx>>,
     failing: Vec<FunctionWithMetadata<'tcx>>,
+    io_effects: Vec<IoEffect>,
+    witnesses: Vec<ImpurityWitness<'tcx>>,
+    // Chain of calls from the analyzed function to the first impure function found.
+    impurity_path: Option<String>,
 }
 
 impl<'tcx> PurityAnalysisResult<'tcx> {
@@ -87,6 +210,9 @@ impl<'tcx> PurityAnalysisResult<'tcx> {
             reason,
             passing,
             failing,
+            io_effects: vec![],
+            witnesses: vec![],
+            impurity_path: None,
         }
     }
 
@@ -102,17 +228,63 @@ impl<'tcx> PurityAnalysisResult<'tcx> {
         self.reason
     }
 
+    pub fn io_effects(&self) -> &Vec<IoEffect> {
+        &self.io_effects
+    }
+
+    pub fn with_io_effects(self, io_effects: Vec<IoEffect>) -> Self {
+        Self { io_effects, ..self }
+    }
+
+    pub fn witnesses(&self) -> &Vec<ImpurityWitness<'tcx>> {
+        &self.witnesses
+    }
+
+    pub fn with_witnesses(self, witnesses: Vec<ImpurityWitness<'tcx>>) -> Self {
+        Self { witnesses, ..self }
+    }
+
+    /// Returns the failing calls to foreign functions.
+    pub fn foreign_calls(&self) -> impl Iterator<Item = Instance<'tcx>> + '_ {
+        self.failing
+            .iter()
+            .filter(|call| call.is_foreign())
+            .map(|call| call.function())
+    }
+
+    pub fn impurity_path(&self) -> Option<&String> {
+        self.impurity_path.as_ref()
+    }
+
+    pub fn with_impurity_path(self, impurity_path: Option<String>) -> Self {
+        Self {
+            impurity_path,
+            ..self
+        }
+    }
+
     pub fn error(def_id: DefId, reason: Option<ImpurityReason>, annotated_pure: bool) -> Self {
         Self::new(def_id, annotated_pure, false, reason, vec![], vec![])
     }
 }
 
+/// Version of the shape of the serialized `PurityAnalysisResult`, emitted as `schema_version`.
+/// Bump it whenever a field is added, removed, renamed, or changes its type.
+///
+/// Version 1 has `def_id`, `annotated_pure`, `status`, `reason` (only if impure), `passing`,
+/// `failing`, and optionally `io_effects`, `witnesses`, and `impurity_path`.
+/// Version 2 adds `foreign` to the `passing` and `failing` calls.
+/// Version 3 adds `assumed_pure` to the `passing` and `failing` calls.
+/// Version 4 adds `ignored` to the `passing` and `failing` calls.
+pub const PURITY_RESULT_SCHEMA_VERSION: u32 = 4;
+
 impl<'tcx> Serialize for PurityAnalysisResult<'tcx> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("PurityAnalysisResult", 8)?;
+        let mut state = serializer.serialize_struct("PurityAnalysisResult", 12)?;
+        state.serialize_field("schema_version", &PURITY_RESULT_SCHEMA_VERSION)?;
         state.serialize_field("def_id", format!("{:?}", self.def_id).as_str())?;
         state.serialize_field("annotated_pure", &self.annotated_pure)?;
         state.serialize_field("status", &self.status)?;
@@ -121,6 +293,15 @@ impl<'tcx> Serialize for PurityAnalysisResult<'tcx> {
         }
         state.serialize_field("passing", &self.passing)?;
         state.serialize_field("failing", &self.failing)?;
+        if !self.io_effects.is_empty() {
+            state.serialize_field("io_effects", &self.io_effects)?;
+        }
+        if !self.witnesses.is_empty() {
+            state.serialize_field("witnesses", &self.witnesses)?;
+        }
+        if let Some(impurity_path) = &self.impurity_path {
+            state.serialize_field("impurity_path", impurity_path)?;
+        }
         state.end()
     }
 }
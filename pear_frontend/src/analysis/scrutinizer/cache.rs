@@ -0,0 +1,76 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use itertools::Itertools;
+use pear_backend::{item_fingerprint, Node};
+use rustc_macros::{TyDecodable, TyEncodable};
+use rustc_middle::ty::TyCtxt;
+
+/// Purity analysis result of a function along with the fingerprint of everything it depends on.
+#[derive(TyDecodable, TyEncodable, Debug, Clone)]
+pub struct CachedPurityResult {
+    fingerprint: u64,
+    status: bool,
+    annotated_pure: bool,
+    reason: String,
+    serialized: String,
+}
+
+impl CachedPurityResult {
+    pub fn new(
+        fingerprint: u64,
+        status: bool,
+        annotated_pure: bool,
+        reason: String,
+        serialized: String,
+    ) -> Self {
+        Self {
+            fingerprint,
+            status,
+            annotated_pure,
+            reason,
+            serialized,
+        }
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn status(&self) -> bool {
+        self.status
+    }
+
+    pub fn annotated_pure(&self) -> bool {
+        self.annotated_pure
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub fn serialized(&self) -> &str {
+        &self.serialized
+    }
+}
+
+/// Computes a fingerprint of the reachable items that changes whenever any of their bodies does.
+/// The settings the result was computed with are hashed in as well.
+pub fn purity_fingerprint<'a, 'tcx: 'a>(
+    tcx: TyCtxt<'tcx>,
+    items: impl IntoIterator<Item = &'a Node<'tcx>>,
+    settings: impl Hash,
+) -> u64 {
+    let item_fingerprints = items
+        .into_iter()
+        .map(|node| item_fingerprint(tcx, node.item()))
+        .sorted()
+        .dedup()
+        .collect_vec();
+    let mut hasher = DefaultHasher::new();
+    settings.hash(&mut hasher);
+    item_fingerprints.hash(&mut hasher);
+    hasher.finish()
+}
@@ -1,4 +1,5 @@
 mod analyzer;
+mod cache;
 mod important;
 mod scrutinizer_global;
 mod scrutinizer_local;
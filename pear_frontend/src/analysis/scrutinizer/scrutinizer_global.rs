@@ -1,32 +1,57 @@
-use std::fs;
+use std::{collections::HashMap, fs, path::PathBuf};
 
 use colored::Colorize;
+use itertools::Itertools;
 use regex::Regex;
 use rustc_ast::Mutability;
+use rustc_hir::{def::DefKind, def_id::DefId};
 use rustc_middle::{
     mir::{mono::MonoItem, Local},
-    ty::{self, Ty, TyCtxt},
+    ty::{self, GenericArgs, GenericParamDefKind, Ty, TyCtxt},
 };
+use rustc_span::Symbol;
 
-use pear_backend::{collect_from, refine_from, GlobalAnalysis};
+use pear_backend::{
+    analyze_entry_with, decode_from_file, encode_to_file, instance_sig, num_args_for_instance,
+    print_profile, with_source_map, CollectionBudget, Collector, GlobalAnalysis, RefinerConfig,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::analysis::{
-    scrutinizer::{
-        analyzer::{ImpurityReason, PurityAnalysisResult, ScrutinizerAnalysis},
-        scrutinizer_local::substituted_mir,
-        selector::{select_functions, select_pprs},
+use crate::{
+    analysis::{
+        scrutinizer::{
+            analyzer::{
+                HasInteriorMutArg, ImpurityReason, ImpurityWitness, IoEffect, ItemMatcher,
+                PurityAnalysisResult, ScrutinizerAnalysis,
+            },
+            cache::{purity_fingerprint, CachedPurityResult},
+            selector::{select_all, select_functions, select_named, select_pprs},
+        },
+        utils::matches_filters,
     },
-    utils::instance_sig,
+    watchdog::{write_atomically, write_atomically_with},
 };
 
 pub struct ScrutinizerGlobalAnalysis {
-    filter: Option<Regex>,
+    filter: Vec<Regex>,
+    named_entries: Vec<String>,
+    out_dir: PathBuf,
+    use_cache: bool,
 }
 
 impl<'tcx> ScrutinizerGlobalAnalysis {
-    pub fn new(filter: Option<Regex>) -> Self {
-        Self { filter }
+    pub fn new(
+        filter: Vec<Regex>,
+        named_entries: Vec<String>,
+        out_dir: PathBuf,
+        use_cache: bool,
+    ) -> Self {
+        Self {
+            filter,
+            named_entries,
+            out_dir,
+            use_cache,
+        }
     }
 }
 
@@ -67,6 +92,70 @@ fn default_shallow() -> bool {
     false
 }
 
+fn default_require_unique_fn_ptr() -> bool {
+    false
+}
+
+// Off by default, as printing reaches the lazy initialization of stdout regardless of which
+// arguments are important.
+fn default_detect_lazy_initialization() -> bool {
+    false
+}
+
+/// Accessors that may run an arbitrary initializer the first time they are called.
+const DEFAULT_LAZY_INITIALIZERS: [&str; 5] = [
+    r"std\[\w*\]::sync::once_lock::\{impl#\d+\}::get_or_(try_)?init",
+    r"core\[\w*\]::cell::once::\{impl#\d+\}::get_or_(try_)?init",
+    r"std\[\w*\]::sync::lazy_lock::\{impl#\d+\}::force",
+    r"core\[\w*\]::cell::lazy::\{impl#\d+\}::force",
+    r"lazy_static\[\w*\]::lazy::\{impl#\d+\}::get",
+];
+
+// Off by default, as any reachable I/O function is reported regardless of which arguments are
+// important.
+fn default_detect_io_effects() -> bool {
+    false
+}
+
+fn default_allow_read_io() -> bool {
+    false
+}
+
+fn default_treat_recursion_as_impure() -> bool {
+    false
+}
+
+/// Standard library functions performing I/O, categorized by the kind of effect they have.
+const DEFAULT_IO_EFFECTS: [(&str, IoEffect); 9] = [
+    (
+        r"std\[\w*\]::fs::(read|read_to_string|read_dir|read_link|metadata|symlink_metadata|canonicalize)\b",
+        IoEffect::ReadIO,
+    ),
+    (
+        r"std\[\w*\]::env::(var|var_os|vars|vars_os|args|args_os|current_dir)\b",
+        IoEffect::ReadIO,
+    ),
+    (
+        r"std\[\w*\]::io::stdio::\{impl#\d+\}::read_line",
+        IoEffect::ReadIO,
+    ),
+    (
+        r"std\[\w*\]::fs::(write|copy|rename|hard_link|set_permissions)\b",
+        IoEffect::WriteIO,
+    ),
+    (
+        r"std\[\w*\]::fs::(create_dir|create_dir_all|remove_file|remove_dir|remove_dir_all)\b",
+        IoEffect::WriteIO,
+    ),
+    (
+        r"std\[\w*\]::env::(set_var|remove_var|set_current_dir)\b",
+        IoEffect::WriteIO,
+    ),
+    (r"std\[\w*\]::io::stdio::_e?print\b", IoEffect::WriteIO),
+    (r"std\[\w*\]::net::", IoEffect::Network),
+    (r"std\[\w*\]::os::\w+::net::", IoEffect::Network),
+];
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ScrutinizerConfig {
     #[serde(default = "default_mode")]
@@ -77,11 +166,90 @@ pub struct ScrutinizerConfig {
     output_file: String,
     #[serde(default = "default_shallow")]
     shallow: bool,
+    #[serde(default = "default_require_unique_fn_ptr")]
+    require_unique_fn_ptr: bool,
+    #[serde(default = "default_detect_lazy_initialization")]
+    detect_lazy_initialization: bool,
+    #[serde(default = "default_detect_io_effects")]
+    detect_io_effects: bool,
+    #[serde(default = "default_allow_read_io")]
+    allow_read_io: bool,
+    #[serde(default = "default_treat_recursion_as_impure")]
+    treat_recursion_as_impure: bool,
 
     target_filter: Option<String>,
-    important_args: Option<Vec<usize>>,
+    /// Either one set of important arguments, e.g. `[1, 2]`, or several sets, e.g. `[[1], [2]]`,
+    /// each analyzed separately with the results suffixed by the arguments in the set.
+    important_args: Option<ImportantArgs>,
     allowlist: Option<Vec<String>>,
+    /// Exact def paths of allowlisted items, as printed by `def_path_str`.
+    allowlist_def_paths: Option<Vec<String>>,
+    /// Prefixes of the names of the crates whose items are all allowlisted.
+    allowlist_crates: Option<Vec<String>>,
     trusted_stdlib: Option<Vec<String>>,
+    /// Exact def paths of trusted items, as printed by `def_path_str`.
+    trusted_stdlib_def_paths: Option<Vec<String>>,
+    /// Prefixes of the names of the crates whose items are all trusted.
+    trusted_stdlib_crates: Option<Vec<String>>,
+    lazy_initializers: Option<Vec<String>>,
+    /// Def path regexes of the entries that are analyzed even though they take mutable references,
+    /// e.g. to write their results into out-params. Each regex has to match the whole def path.
+    allow_mut_args: Option<Vec<String>>,
+    /// Concrete types to instantiate generic entries with, keyed by the def path of the entry,
+    /// e.g. `"scrutinizer::tests::generic::sum" = ["usize"]`. The types are given in the order of
+    /// the type parameters, including the ones of the parent impl or trait for methods. Supported
+    /// types are primitives, `String`, and local structs, enums, and unions without generics,
+    /// named by their def path. Lifetimes are erased, const parameters are not supported.
+    instantiations: Option<HashMap<String, Vec<String>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum ImportantArgs {
+    Single(Vec<usize>),
+    Multiple(Vec<Vec<usize>>),
+}
+
+/// Returns the sets of important arguments to analyze an entry with, along with the suffixes
+/// telling their results apart. The arguments given by the annotation take precedence, and no set
+/// means that all arguments are important.
+fn important_arg_sets(
+    attribute_important_args: Option<&Vec<usize>>,
+    config_important_args: Option<&ImportantArgs>,
+) -> Vec<(String, Option<Vec<usize>>)> {
+    match (attribute_important_args, config_important_args) {
+        (Some(important_args), _) | (None, Some(ImportantArgs::Single(important_args))) => {
+            vec![(String::new(), Some(important_args.clone()))]
+        }
+        (None, Some(ImportantArgs::Multiple(important_arg_sets))) => important_arg_sets
+            .iter()
+            .map(|important_args| {
+                (
+                    format!(".args_{}", important_args.iter().join("_")),
+                    Some(important_args.clone()),
+                )
+            })
+            .collect(),
+        (None, None) => vec![(String::new(), None)],
+    }
+}
+
+/// Returns the name of the file to write the results to, keeping the def path short enough.
+fn purity_results_file_name(def_path_str: &str, suffix: &str) -> String {
+    let mut def_path_str = def_path_str.to_owned();
+    def_path_str.truncate(128);
+    format!("{def_path_str}{suffix}.purity.pear.json")
+}
+
+/// Kinds of the expected files checked against the result of the entry.
+const EXPECTED_FILE_KINDS: [&str; 4] = ["reason", "io_effects", "witnesses", "impurity_path"];
+
+/// Returns true if the result of the entry is checked against an expected file, which needs a fresh
+/// analysis, as the cached result does not keep the checked details.
+fn has_expected_files(def_path_str: &str) -> bool {
+    EXPECTED_FILE_KINDS
+        .iter()
+        .any(|kind| fs::metadata(format!("expected/{def_path_str}.{kind}.pear.expected")).is_ok())
 }
 
 /// Dumps the usage map from each entry function to a file.
@@ -92,142 +260,613 @@ impl<'tcx> GlobalAnalysis<'tcx> for ScrutinizerGlobalAnalysis {
 
         println!("{}", "Starting PEAR-Scrutinizer analysis.".blue().bold());
 
-        let config: ScrutinizerConfig = fs::read("scrutinizer-config.toml")
-            .map(|config_bytes| {
-                String::from_utf8(config_bytes).expect("failed to parse the expected file")
-            })
-            .map(|config_str| toml::from_str(&config_str).expect("failed to parse TOML config"))
-            .expect("failed to read config file");
+        fs::create_dir_all(self.out_dir.join("bodies")).expect("failed to create bodies dir");
+
+        let config_str =
+            fs::read_to_string("scrutinizer-config.toml").expect("failed to read config file");
+        let config: ScrutinizerConfig =
+            toml::from_str(&config_str).expect("failed to parse TOML config");
+
+        let cache_dir = self.out_dir.join("cache");
+        fs::create_dir_all(&cache_dir).expect("failed to create cache dir");
 
         let analysis_targets = if config.mode == "function" {
             select_functions(tcx)
         } else if config.mode == "ppr" {
             select_pprs(tcx)
+        } else if config.mode == "all" {
+            select_all(tcx)
         } else {
             panic!("unknown mode");
         };
+        let analysis_targets = select_named(tcx, analysis_targets, &self.named_entries);
+        let allow_mut_args = config
+            .allow_mut_args
+            .iter()
+            .flatten()
+            .map(|re| Regex::new(&format!("^(?:{re})$")).unwrap())
+            .collect_vec();
 
-        for (analysis_target, annotated_pure) in analysis_targets {
+        for (analysis_target, annotated_pure, attribute_important_args) in analysis_targets {
             let def_id = analysis_target.def_id();
-            let mut def_path_str = tcx.def_path_str(def_id);
+            let def_path_str = tcx.def_path_str(def_id);
 
-            if !self
-                .filter
-                .as_ref()
-                .map(|filter| filter.is_match(def_path_str.as_str()))
-                .unwrap_or(true)
-            {
+            if !matches_filters(&self.filter, def_path_str.as_str()) {
                 continue;
             }
 
+            // Analyze generic entries with the concrete types requested for them.
+            let analysis_target = match config
+                .instantiations
+                .as_ref()
+                .and_then(|instantiations| instantiations.get(&def_path_str))
+            {
+                Some(type_names) => instantiate_with(tcx, def_id, type_names).unwrap_or_else(|| {
+                    println!(
+                        "WARNING: failed to instantiate {def_path_str} with {type_names:?}, analyzing it as is."
+                    );
+                    analysis_target
+                }),
+                None => analysis_target,
+            };
+
             let instance_sig = instance_sig(analysis_target, tcx);
 
-            let purity_analysis_result = if instance_sig
-                .inputs()
-                .iter()
-                .any(|ty| contains_non_concrete_type(*ty))
-            {
-                PurityAnalysisResult::error(
-                    def_id,
-                    Some(ImpurityReason::UnresolvedGenerics),
-                    annotated_pure,
-                )
-            } else if instance_sig.inputs().iter().any(|ty| is_mutable_ref(*ty)) {
-                PurityAnalysisResult::error(
-                    def_id,
-                    Some(ImpurityReason::MutableArguments),
-                    annotated_pure,
-                )
-            } else {
-                let (items, _) = collect_from(tcx, MonoItem::Fn(analysis_target));
-
-                let refined_usage_graph = refine_from(analysis_target, items, tcx);
-
-                // Calculate important arguments.
-                let important_args = {
-                    let body_with_facts = substituted_mir(analysis_target, tcx)
-                        .expect("root object does not have a scrutinizer body");
-                    let (body, _) = body_with_facts.clone().split();
-                    // Parse important arguments.
-                    let important_args = if config.important_args.is_none() {
+            // The reachable items do not depend on the important arguments, so they are collected
+            // and refined once for all the sets of important arguments.
+            let mut collector = Collector::new(tcx, CollectionBudget::default());
+            let mut refined_usage_graph_cache = None;
+
+            for (suffix, important_args) in important_arg_sets(
+                attribute_important_args.as_ref(),
+                config.important_args.as_ref(),
+            ) {
+                let purity_results_file_name = purity_results_file_name(&def_path_str, &suffix);
+                let def_path_str = format!("{def_path_str}{suffix}");
+
+                // Where to cache the result and the fingerprint it is valid for.
+                let mut cache_entry = None;
+
+                let purity_analysis_result = if instance_sig
+                    .inputs()
+                    .iter()
+                    .any(|ty| contains_non_concrete_type(*ty))
+                {
+                    PurityAnalysisResult::error(
+                        def_id,
+                        Some(ImpurityReason::UnresolvedGenerics),
+                        annotated_pure,
+                    )
+                } else if instance_sig.inputs().iter().any(|ty| is_mutable_ref(*ty))
+                    && !allow_mut_args.iter().any(|re| re.is_match(&def_path_str))
+                {
+                    PurityAnalysisResult::error(
+                        def_id,
+                        Some(ImpurityReason::MutableArguments),
+                        annotated_pure,
+                    )
+                } else if instance_sig.has_interior_mut_arg(tcx, important_args.as_deref()) {
+                    PurityAnalysisResult::error(
+                        def_id,
+                        Some(ImpurityReason::InteriorMutableArguments),
+                        annotated_pure,
+                    )
+                } else {
+                    // Skip the analysis if neither the reachable bodies nor the settings changed.
+                    if self.use_cache && !has_expected_files(&def_path_str) {
+                        // Refinement below reuses the collection.
+                        let collection = collector.collect(MonoItem::Fn(analysis_target));
+                        let fingerprint = purity_fingerprint(
+                            tcx,
+                            &collection.0,
+                            (&config_str, annotated_pure, &important_args),
+                        );
+                        let cache_path = cache_dir.join(format!(
+                            "{}{suffix}",
+                            tcx.def_path(def_id).to_filename_friendly_no_crate()
+                        ));
+                        if let Ok(cached) = decode_from_file::<CachedPurityResult>(tcx, &cache_path)
+                            && cached.fingerprint() == fingerprint
+                        {
+                            if !config.only_inconsistent
+                                || cached.status() != cached.annotated_pure()
+                            {
+                                print_status(
+                                    tcx,
+                                    def_id,
+                                    &def_path_str,
+                                    cached.status(),
+                                    cached.annotated_pure(),
+                                    cached.reason(),
+                                );
+                                println!("  Reused the cached result.");
+                                write_atomically(
+                                    self.out_dir.join(&purity_results_file_name),
+                                    cached.serialized(),
+                                )
+                                .expect("failed to write purity analysis results to a file");
+                            } else {
+                                println!("Reused the cached result of {def_path_str}.");
+                            }
+                            continue;
+                        }
+                        cache_entry = Some((cache_path, fingerprint));
+                    }
+
+                    let refined_usage_graph = refined_usage_graph_cache
+                        .get_or_insert_with(|| {
+                            analyze_entry_with(
+                                tcx,
+                                &mut collector,
+                                analysis_target,
+                                RefinerConfig {
+                                    require_unique_fn_ptr: config.require_unique_fn_ptr,
+                                    ..RefinerConfig::default()
+                                },
+                            )
+                            .1
+                        })
+                        .clone();
+
+                    // Calculate important arguments.
+                    let important_args = {
                         // If no important arguments are provided, assume all are important.
-                        let arg_count = { body.arg_count };
-                        (1..=arg_count).collect()
+                        let important_args = important_args.clone().unwrap_or_else(|| {
+                            (1..=num_args_for_instance(analysis_target, tcx)).collect()
+                        });
+                        important_args
+                            .into_iter()
+                            .map(|arg_num| Local::from_usize(arg_num))
+                            .collect()
+                    };
+
+                    let io_effects: Vec<(Regex, IoEffect)> = if config.detect_io_effects {
+                        DEFAULT_IO_EFFECTS
+                            .iter()
+                            .map(|(re, io_effect)| (Regex::new(re).unwrap(), *io_effect))
+                            .collect()
                     } else {
-                        config.important_args.as_ref().unwrap().to_owned()
+                        vec![]
                     };
-                    important_args
-                        .into_iter()
-                        .map(|arg_num| Local::from_usize(arg_num))
-                        .collect()
+                    let reachable_io_effects = refined_usage_graph
+                        .instances()
+                        .iter()
+                        .filter_map(|instance| {
+                            let def_path_str = format!("{:?}", instance.def_id());
+                            io_effects
+                                .iter()
+                                .find(|(re, _)| re.is_match(&def_path_str))
+                                .map(|(_, io_effect)| *io_effect)
+                        })
+                        .sorted()
+                        .dedup()
+                        .collect_vec();
+                    let has_rejected_io_effects = reachable_io_effects
+                        .iter()
+                        .any(|io_effect| *io_effect != IoEffect::ReadIO || !config.allow_read_io);
+
+                    // Read-only I/O is accepted by not descending into the functions performing it.
+                    let allowlist = ItemMatcher::new(
+                        config
+                            .allowlist
+                            .as_ref()
+                            .unwrap_or(&vec![])
+                            .iter()
+                            .map(|re| Regex::new(re).unwrap())
+                            .chain(
+                                io_effects
+                                    .iter()
+                                    .filter(|(_, io_effect)| {
+                                        config.allow_read_io && *io_effect == IoEffect::ReadIO
+                                    })
+                                    .map(|(re, _)| re.clone()),
+                            )
+                            .collect(),
+                        config.allowlist_def_paths.clone().unwrap_or_default(),
+                        config.allowlist_crates.clone().unwrap_or_default(),
+                    );
+
+                    let trusted_stdlib = ItemMatcher::new(
+                        config
+                            .trusted_stdlib
+                            .as_ref()
+                            .unwrap_or(&vec![])
+                            .iter()
+                            .map(|re| Regex::new(re).unwrap())
+                            .collect(),
+                        config.trusted_stdlib_def_paths.clone().unwrap_or_default(),
+                        config.trusted_stdlib_crates.clone().unwrap_or_default(),
+                    );
+
+                    let lazy_initializers: Vec<Regex> = match config.lazy_initializers.as_ref() {
+                        Some(lazy_initializers) => lazy_initializers
+                            .iter()
+                            .map(|re| Regex::new(re).unwrap())
+                            .collect(),
+                        None => DEFAULT_LAZY_INITIALIZERS
+                            .iter()
+                            .map(|re| Regex::new(re).unwrap())
+                            .collect(),
+                    };
+                    let has_lazy_initialization = config.detect_lazy_initialization
+                        && refined_usage_graph.instances().iter().any(|instance| {
+                            let def_path_str = format!("{:?}", instance.def_id());
+                            lazy_initializers
+                                .iter()
+                                .any(|re| re.is_match(&def_path_str))
+                        });
+
+                    // Skipped calls leave the graph incomplete, so nothing is known about them.
+                    let purity_analysis_result = if !refined_usage_graph.errors().is_empty() {
+                        PurityAnalysisResult::error(
+                            def_id,
+                            Some(ImpurityReason::RefinementError),
+                            annotated_pure,
+                        )
+                    } else if !refined_usage_graph.ambiguous_fn_ptr_calls().is_empty() {
+                        PurityAnalysisResult::error(
+                            def_id,
+                            Some(ImpurityReason::AmbiguousFnPtr),
+                            annotated_pure,
+                        )
+                    } else if has_lazy_initialization {
+                        PurityAnalysisResult::error(
+                            def_id,
+                            Some(ImpurityReason::LazyInitialization),
+                            annotated_pure,
+                        )
+                    } else if has_rejected_io_effects {
+                        PurityAnalysisResult::error(
+                            def_id,
+                            Some(ImpurityReason::IoEffects),
+                            annotated_pure,
+                        )
+                    } else {
+                        ScrutinizerAnalysis::run(
+                            refined_usage_graph,
+                            important_args,
+                            annotated_pure,
+                            allowlist,
+                            trusted_stdlib,
+                            config.treat_recursion_as_impure,
+                            // Only the direct callees of the target are analyzed when shallow.
+                            config.shallow.then_some(1),
+                            self.out_dir.join("bodies"),
+                            tcx,
+                        )
+                    };
+                    purity_analysis_result.with_io_effects(reachable_io_effects)
                 };
 
-                let allowlist = config
-                    .allowlist
-                    .as_ref()
-                    .unwrap_or(&vec![])
-                    .iter()
-                    .map(|re| Regex::new(re).unwrap())
-                    .collect();
+                // Only the results contradicting their annotation are reported in this mode.
+                let is_reported = !config.only_inconsistent
+                    || purity_analysis_result.status() != purity_analysis_result.annotated_pure();
 
-                let trusted_stdlib = config
-                    .trusted_stdlib
-                    .as_ref()
-                    .unwrap_or(&vec![])
-                    .iter()
-                    .map(|re| Regex::new(re).unwrap())
-                    .collect();
-
-                ScrutinizerAnalysis::run(
-                    refined_usage_graph,
-                    important_args,
-                    annotated_pure,
-                    allowlist,
-                    trusted_stdlib,
-                    tcx,
-                )
-            };
+                if is_reported {
+                    print_status(
+                        tcx,
+                        def_id,
+                        &def_path_str,
+                        purity_analysis_result.status(),
+                        purity_analysis_result.annotated_pure(),
+                        &format!("{:?}", purity_analysis_result.reason()),
+                    );
 
-            if purity_analysis_result.status() != purity_analysis_result.annotated_pure() {
-                let stencil = format!(
-                    "{def_path_str} failed; status = {} but annotation = {}; reason = {:?}",
-                    purity_analysis_result.status(),
-                    purity_analysis_result.annotated_pure(),
-                    purity_analysis_result.reason()
-                );
-
-                println!(
-                    "{}",
-                    match purity_analysis_result.annotated_pure() {
-                        true => stencil.yellow().bold(),
-                        false => stencil.red().bold(),
+                    if !purity_analysis_result.io_effects().is_empty() {
+                        println!(
+                            "  I/O effects: {}",
+                            purity_analysis_result
+                                .io_effects()
+                                .iter()
+                                .map(|io_effect| format!("{io_effect:?}"))
+                                .join(", ")
+                        );
                     }
-                );
-            } else {
-                println!(
-                    "{}",
-                    format!(
-                        "{def_path_str} passed; status = {} and annotation = {}",
-                        purity_analysis_result.status(),
-                        purity_analysis_result.annotated_pure()
+
+                    for foreign_call in purity_analysis_result.foreign_calls() {
+                        println!(
+                            "  Calls foreign function {}",
+                            tcx.def_path_str(foreign_call.def_id())
+                        );
+                    }
+
+                    if let Some(impurity_path) = purity_analysis_result.impurity_path() {
+                        println!("  Impurity reached via {impurity_path}");
+                    }
+                }
+
+                if let Ok(bytes) = fs::read(format!("expected/{def_path_str}.reason.pear.expected"))
+                {
+                    let expected =
+                        String::from_utf8(bytes).expect("failed to parse the expected file");
+                    run_reason_test(&def_path_str, purity_analysis_result.reason(), &expected);
+                }
+
+                if let Ok(bytes) =
+                    fs::read(format!("expected/{def_path_str}.io_effects.pear.expected"))
+                {
+                    let expected =
+                        String::from_utf8(bytes).expect("failed to parse the expected file");
+                    run_io_effects_test(
+                        &def_path_str,
+                        purity_analysis_result.io_effects(),
+                        &expected,
+                    );
+                }
+
+                if let Ok(bytes) =
+                    fs::read(format!("expected/{def_path_str}.witnesses.pear.expected"))
+                {
+                    let expected =
+                        String::from_utf8(bytes).expect("failed to parse the expected file");
+                    run_witnesses_test(
+                        &def_path_str,
+                        purity_analysis_result.witnesses(),
+                        &expected,
+                    );
+                }
+
+                if let Ok(bytes) = fs::read(format!(
+                    "expected/{def_path_str}.impurity_path.pear.expected"
+                )) {
+                    let expected =
+                        String::from_utf8(bytes).expect("failed to parse the expected file");
+                    run_impurity_path_test(
+                        &def_path_str,
+                        purity_analysis_result.impurity_path(),
+                        &expected,
+                    );
+                }
+
+                let purity_results_path = self.out_dir.join(&purity_results_file_name);
+                if is_reported {
+                    with_source_map(tcx, || {
+                        write_atomically_with(&purity_results_path, |writer| {
+                            serde_json::to_writer_pretty(writer, &purity_analysis_result)?;
+                            Ok(())
+                        })
+                    })
+                    .expect("failed to write purity analysis results to a file");
+                }
+
+                if let Some((cache_path, fingerprint)) = cache_entry {
+                    // The cache holds the serialized result, so only then is it held in memory in
+                    // full.
+                    let serialized_purity_analysis_result = if is_reported {
+                        fs::read_to_string(&purity_results_path)
+                            .expect("failed to read purity analysis results back")
+                    } else {
+                        with_source_map(tcx, || {
+                            serde_json::to_string_pretty(&purity_analysis_result)
+                                .expect("failed to serialize purity analysis results")
+                        })
+                    };
+                    encode_to_file(
+                        tcx,
+                        cache_path,
+                        &CachedPurityResult::new(
+                            fingerprint,
+                            purity_analysis_result.status(),
+                            purity_analysis_result.annotated_pure(),
+                            format!("{:?}", purity_analysis_result.reason()),
+                            serialized_purity_analysis_result,
+                        ),
                     )
-                    .green()
-                    .bold()
-                );
+                    .expect("failed to write the cached purity result");
+                }
             }
+        }
+        print_profile();
+        colored::control::unset_override();
+        rustc_driver::Compilation::Continue
+    }
+}
 
-            let serialized_purity_analysis_result =
-                serde_json::to_string_pretty(&purity_analysis_result)
-                    .expect("failed to serialize purity analysis results");
+/// Builds an instance of the item with its type parameters set to the named concrete types.
+fn instantiate_with<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: DefId,
+    type_names: &[String],
+) -> Option<ty::Instance<'tcx>> {
+    let mut types = type_names
+        .iter()
+        .map(|type_name| concrete_ty_named(tcx, type_name))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter();
+    let mut supported = true;
+    let args = GenericArgs::for_item(tcx, def_id, |param, _| match param.kind {
+        GenericParamDefKind::Lifetime => tcx.lifetimes.re_erased.into(),
+        GenericParamDefKind::Type { .. } => types.next().map(Into::into).unwrap_or_else(|| {
+            supported = false;
+            tcx.types.unit.into()
+        }),
+        GenericParamDefKind::Const { .. } => {
+            supported = false;
+            tcx.types.unit.into()
+        }
+    });
+    if !supported || types.next().is_some() {
+        return None;
+    }
+    ty::Instance::resolve(tcx, ty::ParamEnv::reveal_all(), def_id, args)
+        .ok()
+        .flatten()
+}
+
+/// Returns the concrete type with the given name, see [`ScrutinizerConfig::instantiations`].
+fn concrete_ty_named<'tcx>(tcx: TyCtxt<'tcx>, type_name: &str) -> Option<Ty<'tcx>> {
+    let ty = match type_name {
+        "bool" => tcx.types.bool,
+        "char" => tcx.types.char,
+        "i8" => tcx.types.i8,
+        "i16" => tcx.types.i16,
+        "i32" => tcx.types.i32,
+        "i64" => tcx.types.i64,
+        "i128" => tcx.types.i128,
+        "isize" => tcx.types.isize,
+        "u8" => tcx.types.u8,
+        "u16" => tcx.types.u16,
+        "u32" => tcx.types.u32,
+        "u64" => tcx.types.u64,
+        "u128" => tcx.types.u128,
+        "usize" => tcx.types.usize,
+        "f32" => tcx.types.f32,
+        "f64" => tcx.types.f64,
+        "String" => tcx
+            .type_of(tcx.get_diagnostic_item(Symbol::intern("String"))?)
+            .instantiate_identity(),
+        _ => {
+            let def_id = tcx
+                .hir()
+                .items()
+                .map(|item_id| item_id.owner_id.to_def_id())
+                .find(|def_id| {
+                    matches!(
+                        tcx.def_kind(*def_id),
+                        DefKind::Struct | DefKind::Enum | DefKind::Union
+                    ) && tcx.def_path_str(*def_id) == type_name
+                })?;
+            if tcx.generics_of(def_id).count() != 0 {
+                return None;
+            }
+            tcx.type_of(def_id).instantiate_identity()
+        }
+    };
+    Some(ty)
+}
+
+/// Prints the result, and reports a disagreement with the annotation as a compiler warning at the
+/// function, so that it shows up in the compiler output and is denied with `-D warnings`.
+fn print_status(
+    tcx: TyCtxt,
+    def_id: DefId,
+    def_path_str: &str,
+    status: bool,
+    annotated_pure: bool,
+    reason: &str,
+) {
+    if status != annotated_pure {
+        let stencil = format!(
+            "{def_path_str} failed; status = {status} but annotation = {annotated_pure}; reason = {reason}"
+        );
+
+        println!(
+            "{}",
+            match annotated_pure {
+                true => stencil.yellow().bold(),
+                false => stencil.red().bold(),
+            }
+        );
+
+        let purity = |pure: bool| if pure { "pure" } else { "impure" };
+        let mut diagnostic = tcx.dcx().struct_span_warn(
+            tcx.def_span(def_id),
+            format!(
+                "`{def_path_str}` is annotated as {} but was found {}",
+                purity(annotated_pure),
+                purity(status)
+            ),
+        );
+        diagnostic.note(format!("reason = {reason}"));
+        diagnostic.emit();
+    } else {
+        println!(
+            "{}",
+            format!("{def_path_str} passed; status = {status} and annotation = {annotated_pure}")
+                .green()
+                .bold()
+        );
+    }
+}
 
-            def_path_str.truncate(128);
-            fs::write(
-                format!("{def_path_str}.purity.pear.json"),
-                serialized_purity_analysis_result,
+fn run_reason_test(def_path_str: &str, reason: Option<ImpurityReason>, expected: &str) {
+    println!("{}", format!("  [{def_path_str} reason]").blue().bold());
+    let reason = reason
+        .map(|reason| format!("{reason:?}"))
+        .unwrap_or_default();
+    if reason != expected.trim() {
+        println!("{}", "    Test failed.".red().bold());
+        println!(
+            "{}",
+            format!(
+                "      expected reason {} but got {reason}.",
+                expected.trim()
             )
-            .expect("failed to write purity analysis results to a file");
+            .red()
+        );
+        return;
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+/// Each expected line is "<caller> <span>", where an impure call must be made from the caller at a
+/// call site whose span starts with the given location.
+fn run_witnesses_test(def_path_str: &str, witnesses: &Vec<ImpurityWitness>, expected: &str) {
+    println!("{}", format!("  [{def_path_str} witnesses]").blue().bold());
+    for line in expected.lines() {
+        let Some((caller, span)) = line.split_once(' ') else {
+            println!("{}", "    Test failed.".red().bold());
+            println!("{}", format!("      malformed expected line {line}.").red());
+            return;
+        };
+        if !witnesses.iter().any(|witness| {
+            witness.caller().to_string().contains(caller)
+                && format!("{:?}", witness.span()).starts_with(span)
+        }) {
+            println!("{}", "    Test failed.".red().bold());
+            println!(
+                "{}",
+                format!("      no impure call from {caller} at {span}.").red()
+            );
+            return;
         }
-        colored::control::unset_override();
-        rustc_driver::Compilation::Continue
     }
+    println!("{}", "    Test passed.".green());
+}
+
+fn run_impurity_path_test(def_path_str: &str, impurity_path: Option<&String>, expected: &str) {
+    println!(
+        "{}",
+        format!("  [{def_path_str} impurity path]").blue().bold()
+    );
+    let impurity_path = impurity_path.map(String::as_str).unwrap_or_default();
+    if impurity_path != expected.trim() {
+        println!("{}", "    Test failed.".red().bold());
+        println!(
+            "{}",
+            format!(
+                "      expected impurity path {} but got {impurity_path}.",
+                expected.trim()
+            )
+            .red()
+        );
+        return;
+    }
+    println!("{}", "    Test passed.".green());
+}
+
+fn run_io_effects_test(def_path_str: &str, io_effects: &Vec<IoEffect>, expected: &str) {
+    println!(
+        "{}",
+        format!("  [{def_path_str} I/O effects]").blue().bold()
+    );
+    let io_effects = io_effects
+        .iter()
+        .map(|io_effect| format!("{io_effect:?}"))
+        .collect_vec();
+    let expected_io_effects = expected.lines().map(str::trim).collect_vec();
+    if io_effects != expected_io_effects {
+        println!("{}", "    Test failed.".red().bold());
+        println!(
+            "{}",
+            format!(
+                "      expected I/O effects {} but got {}.",
+                expected_io_effects.join(", "),
+                io_effects.join(", ")
+            )
+            .red()
+        );
+        return;
+    }
+    println!("{}", "    Test passed.".green());
 }
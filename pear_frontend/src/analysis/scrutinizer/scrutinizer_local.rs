@@ -5,13 +5,15 @@ use rustc_borrowck::consumers::RustcFacts;
 use rustc_hir::{def::DefKind, def_id::LocalDefId};
 use rustc_macros::{Decodable, Encodable, TyDecodable, TyEncodable};
 use rustc_middle::{
-    mir::{Body, ClearCrossCrate, StatementKind},
+    mir::Body,
     ty::{self, Instance, TyCtxt},
 };
 use rustc_utils::mir::borrowck_facts::get_body_with_borrowck_facts;
 
 use pear_backend::LocalAnalysis;
 
+use crate::analysis::utils::clean_undecodable_data_from_body;
+
 pub struct ScrutinizerLocalAnalysis {}
 
 impl<'tcx> LocalAnalysis<'tcx> for ScrutinizerLocalAnalysis {
@@ -48,7 +50,7 @@ impl<'tcx> ScrutinizerBody<'tcx> {
         let body_with_facts = get_body_with_borrowck_facts(tcx, local_def_id);
 
         let mut body = body_with_facts.body.clone();
-        Self::clean_undecodable_data_from_body(&mut body);
+        clean_undecodable_data_from_body(&mut body);
 
         let input_facts = body_with_facts.input_facts.clone();
         let subset_base = input_facts
@@ -65,25 +67,6 @@ impl<'tcx> ScrutinizerBody<'tcx> {
         }
     }
 
-    /// Some data in a [Body] is not cross-crate compatible. Usually because it
-    /// involves storing a [LocalDefId]. This function makes sure to sanitize those
-    /// out.
-    fn clean_undecodable_data_from_body(body: &mut Body) {
-        for scope in body.source_scopes.iter_mut() {
-            scope.local_data = ClearCrossCrate::Clear;
-        }
-
-        for stmt in body
-            .basic_blocks_mut()
-            .iter_mut()
-            .flat_map(|bb| bb.statements.iter_mut())
-        {
-            if matches!(stmt.kind, StatementKind::FakeRead(_)) {
-                stmt.make_nop()
-            }
-        }
-    }
-
     pub fn split(self) -> (Body<'tcx>, FlowistryFacts) {
         (self.body, self.input_facts)
     }
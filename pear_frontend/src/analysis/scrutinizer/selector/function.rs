@@ -1,46 +1,140 @@
-use rustc_hir::ItemKind;
+use rustc_ast::{
+    token::{Delimiter, Token, TokenKind},
+    tokenstream::TokenTree,
+    AttrArgs, Attribute, DelimArgs,
+};
+use rustc_hir::{def::DefKind, def_id::DefId};
 use rustc_middle::ty::{self, GenericArgs, TyCtxt};
 use rustc_span::Symbol;
 
-pub fn select_functions<'tcx>(tcx: TyCtxt<'tcx>) -> Vec<(ty::Instance<'tcx>, bool)> {
-    let scrutinizer_pure_attribute = [Symbol::intern("pear"), Symbol::intern("scrutinizer_pure")];
+use crate::analysis::utils::{is_ignored, named_entries};
 
-    let scrutinizer_impure_attribute =
-        [Symbol::intern("pear"), Symbol::intern("scrutinizer_impure")];
+/// Returns the annotated functions along with whether they are annotated pure and the important
+/// arguments given by the annotation.
+pub fn select_functions<'tcx>(
+    tcx: TyCtxt<'tcx>,
+) -> Vec<(ty::Instance<'tcx>, bool, Option<Vec<usize>>)> {
+    select_fn_items(tcx, |tcx, def_id| annotation_of(tcx, def_id))
+}
+
+/// Returns every local function or method with a concrete signature. Functions without an annotation are
+/// expected to be pure.
+///
+/// Functions annotated with `#[pear::ignore]` are skipped in every mode but the named entries.
+pub fn select_all<'tcx>(tcx: TyCtxt<'tcx>) -> Vec<(ty::Instance<'tcx>, bool, Option<Vec<usize>>)> {
+    select_fn_items(tcx, |tcx, def_id| {
+        if tcx.generics_of(def_id).requires_monomorphization(tcx) {
+            None
+        } else {
+            Some(annotation_of(tcx, def_id).unwrap_or((true, None)))
+        }
+    })
+}
 
-    let hir = tcx.hir();
+/// Adds the functions named by their def paths to the selected ones, unless already selected.
+/// Functions without an annotation are expected to be pure.
+pub fn select_named<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    mut selected: Vec<(ty::Instance<'tcx>, bool, Option<Vec<usize>>)>,
+    names: &[String],
+) -> Vec<(ty::Instance<'tcx>, bool, Option<Vec<usize>>)> {
+    for def_id in named_entries(names, tcx) {
+        if selected
+            .iter()
+            .any(|(instance, ..)| instance.def_id() == def_id)
+        {
+            continue;
+        }
+        let (annotated_pure, important_args) = annotation_of(tcx, def_id).unwrap_or((true, None));
+        let instance = ty::Instance::new(def_id, GenericArgs::identity_for_item(tcx, def_id));
+        selected.push((instance, annotated_pure, important_args));
+    }
+    selected
+}
 
+fn select_fn_items<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    select: impl Fn(TyCtxt<'tcx>, DefId) -> Option<(bool, Option<Vec<usize>>)>,
+) -> Vec<(ty::Instance<'tcx>, bool, Option<Vec<usize>>)> {
     tcx.hir()
-        .items()
-        .filter_map(|item_id| {
-            let item = hir.item(item_id);
-            let def_id = item.owner_id.to_def_id();
-
-            let annotated_pure;
-            if tcx
-                .get_attrs_by_path(def_id, &scrutinizer_pure_attribute)
-                .next()
-                .is_some()
-            {
-                annotated_pure = true;
-            } else if tcx
-                .get_attrs_by_path(def_id, &scrutinizer_impure_attribute)
-                .next()
-                .is_some()
-            {
-                annotated_pure = false;
-            } else {
+        .body_owners()
+        .filter(|local_def_id| {
+            matches!(tcx.def_kind(*local_def_id), DefKind::Fn | DefKind::AssocFn)
+        })
+        .filter_map(|local_def_id| {
+            let def_id = local_def_id.to_def_id();
+            if is_ignored(tcx, def_id) {
+                println!(
+                    "Skipping {}, it is annotated with #[pear::ignore].",
+                    tcx.def_path_str(def_id)
+                );
                 return None;
             }
-
-            if let ItemKind::Fn(..) = &item.kind {
-                // Retrieve the instance, as we know it exists.
-                let args = GenericArgs::identity_for_item(tcx, def_id);
-                let instance = ty::Instance::new(def_id, args);
-                Some((instance, annotated_pure))
-            } else {
-                None
-            }
+            let (annotated_pure, important_args) = select(tcx, def_id)?;
+            // Retrieve the instance, as we know it exists.
+            let args = GenericArgs::identity_for_item(tcx, def_id);
+            let instance = ty::Instance::new(def_id, args);
+            Some((instance, annotated_pure, important_args))
         })
         .collect()
 }
+
+/// Returns whether the function is annotated pure and the important arguments given by the
+/// annotation, if the function is annotated at all.
+fn annotation_of(tcx: TyCtxt<'_>, def_id: DefId) -> Option<(bool, Option<Vec<usize>>)> {
+    let scrutinizer_pure_attribute = [Symbol::intern("pear"), Symbol::intern("scrutinizer_pure")];
+
+    let scrutinizer_impure_attribute =
+        [Symbol::intern("pear"), Symbol::intern("scrutinizer_impure")];
+
+    if let Some(attribute) = tcx
+        .get_attrs_by_path(def_id, &scrutinizer_pure_attribute)
+        .next()
+    {
+        Some((true, important_args_of(attribute)))
+    } else if let Some(attribute) = tcx
+        .get_attrs_by_path(def_id, &scrutinizer_impure_attribute)
+        .next()
+    {
+        Some((false, important_args_of(attribute)))
+    } else {
+        None
+    }
+}
+
+/// Parses the important argument indices from an attribute like
+/// `#[pear::scrutinizer_pure(important = [1, 3])]`.
+fn important_args_of(attribute: &Attribute) -> Option<Vec<usize>> {
+    let AttrArgs::Delimited(DelimArgs { tokens, .. }) = &attribute.get_normal_item().args else {
+        return None;
+    };
+    let mut trees = tokens.trees();
+    match (trees.next(), trees.next(), trees.next()) {
+        (
+            Some(TokenTree::Token(name, _)),
+            Some(TokenTree::Token(
+                Token {
+                    kind: TokenKind::Eq,
+                    ..
+                },
+                _,
+            )),
+            Some(TokenTree::Delimited(.., Delimiter::Bracket, indices)),
+        ) if name.is_ident_named(Symbol::intern("important")) => Some(
+            indices
+                .trees()
+                .filter_map(|tree| match tree {
+                    TokenTree::Token(
+                        Token {
+                            kind: TokenKind::Literal(literal),
+                            ..
+                        },
+                        _,
+                    ) => literal.symbol.as_str().parse().ok(),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
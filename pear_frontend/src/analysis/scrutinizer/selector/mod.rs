@@ -1,5 +1,5 @@
 mod function;
 mod ppr;
 
-pub use function::select_functions;
+pub use function::{select_all, select_functions, select_named};
 pub use ppr::select_pprs;
@@ -39,7 +39,7 @@ impl<'tcx> Visitor<'tcx> for PPRCollector<'tcx> {
     }
 }
 
-pub fn select_pprs<'tcx>(tcx: TyCtxt<'tcx>) -> Vec<(ty::Instance<'tcx>, bool)> {
+pub fn select_pprs<'tcx>(tcx: TyCtxt<'tcx>) -> Vec<(ty::Instance<'tcx>, bool, Option<Vec<usize>>)> {
     tcx.mir_keys(())
         .iter()
         .map(
@@ -61,6 +61,7 @@ pub fn select_pprs<'tcx>(tcx: TyCtxt<'tcx>) -> Vec<(ty::Instance<'tcx>, bool)> {
                                         substs_ref,
                                     ),
                                     true,
+                                    None,
                                 )
                             } else {
                                 panic!("passed a non-closure to ppr constructor");
@@ -1,22 +1,111 @@
+use regex::Regex;
+use rustc_hir::{def::DefKind, def_id::DefId};
 use rustc_middle::{
-    bug,
-    ty::{FnSig, Instance, TyCtxt},
+    mir::{Body, FakeReadCause, StatementKind},
+    ty::TyCtxt,
 };
+use rustc_span::Symbol;
+use serde_json::Value;
 
-pub fn instance_sig<'tcx>(instance: Instance<'tcx>, tcx: TyCtxt<'tcx>) -> FnSig<'tcx> {
-    if tcx.is_closure_or_coroutine(instance.def_id()) {
-        if tcx.is_coroutine(instance.def_id()) {
-            bug!("coroutines do not have a conventional signature");
+/// Names of all fields that hold spans in the serialized graphs.
+const SPAN_FIELDS: [&str; 2] = ["span", "terminator_span"];
+
+/// Compiles the `--filter` regexes, exiting with an error if any of them is invalid.
+pub fn compile_filters(filters: &[String]) -> Vec<Regex> {
+    filters
+        .iter()
+        .map(|filter| {
+            Regex::new(filter).unwrap_or_else(|err| {
+                eprintln!("ERROR: failed to compile filter regex {filter}: {err}");
+                std::process::exit(1);
+            })
+        })
+        .collect()
+}
+
+/// Returns true if no filters are given or any of them matches the def path.
+pub fn matches_filters(filters: &[Regex], def_path_str: &str) -> bool {
+    filters.is_empty() || filters.iter().any(|filter| filter.is_match(def_path_str))
+}
+
+/// Returns true if the function is annotated with `#[pear::ignore]`, so that it is neither selected
+/// for the analysis nor descended into.
+pub fn is_ignored(tcx: TyCtxt, def_id: DefId) -> bool {
+    let ignore_attribute = [Symbol::intern("pear"), Symbol::intern("ignore")];
+    tcx.get_attrs_by_path(def_id, &ignore_attribute)
+        .next()
+        .is_some()
+}
+
+/// Resolves the `--entry` def paths to the local functions and methods they name. A library fails
+/// with an error if any of them is not found in it, while binaries skip the names they lack, as the
+/// entries of a package usually live in its library.
+pub fn named_entries(names: &[String], tcx: TyCtxt) -> Vec<DefId> {
+    let is_library = tcx
+        .crate_types()
+        .iter()
+        .any(|crate_type| crate_type.has_metadata());
+    let candidates = tcx
+        .hir()
+        .body_owners()
+        .filter(|local_def_id| {
+            matches!(tcx.def_kind(*local_def_id), DefKind::Fn | DefKind::AssocFn)
+        })
+        .map(|local_def_id| (tcx.def_path_str(local_def_id.to_def_id()), local_def_id))
+        .collect::<Vec<_>>();
+    names
+        .iter()
+        .filter_map(|name| {
+            let def_id = candidates
+                .iter()
+                .find(|(def_path_str, _)| def_path_str == name)
+                .map(|(_, local_def_id)| local_def_id.to_def_id());
+            if def_id.is_none() && is_library {
+                tcx.dcx()
+                    .fatal(format!("failed to find entry {name} in the crate"));
+            }
+            def_id
+        })
+        .collect()
+}
+
+/// Some data in a [Body] is not cross-crate compatible, because it involves storing a
+/// [`LocalDefId`](rustc_hir::def_id::LocalDefId) that cannot be decoded by another crate. This
+/// function makes sure to sanitize those out, since the bodies of a crate are read by the crates
+/// depending on it.
+///
+/// Source scope data is left alone, as the encoder already skips it when writing the body out, and
+/// debug info (e.g., the name of `self`) is kept intact.
+pub fn clean_undecodable_data_from_body(body: &mut Body) {
+    for stmt in body
+        .basic_blocks_mut()
+        .iter_mut()
+        .flat_map(|bb| bb.statements.iter_mut())
+    {
+        if let StatementKind::FakeRead(fake_read) = &mut stmt.kind {
+            match &mut fake_read.0 {
+                FakeReadCause::ForMatchedPlace(closure_def_id)
+                | FakeReadCause::ForLet(closure_def_id) => *closure_def_id = None,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Removes all span fields from a serialized graph and sorts the remaining collections, so that the
+/// output only changes when the call structure does.
+pub fn strip_spans(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            fields.retain(|name, _| !SPAN_FIELDS.contains(&name.as_str()));
+            fields.values_mut().for_each(strip_spans);
+        }
+        Value::Array(values) => {
+            values.iter_mut().for_each(strip_spans);
+            // Nodes that only differed by their spans are now duplicates.
+            values.sort_by_cached_key(|value| value.to_string());
+            values.dedup();
         }
-        tcx.instantiate_bound_regions_with_erased(
-            tcx.erase_regions(instance.args.as_closure().sig()),
-        )
-    } else {
-        tcx.instantiate_bound_regions_with_erased(
-            tcx.erase_regions(
-                tcx.fn_sig(instance.def_id())
-                    .instantiate(tcx, instance.args),
-            ),
-        )
+        _ => {}
     }
 }
@@ -17,3 +17,4 @@ pub mod analysis;
 pub mod logging;
 pub mod pear_plugin;
 pub mod scrutinizer_plugin;
+pub mod watchdog;
@@ -1,16 +1,72 @@
 use clap::Parser;
 
-use regex::Regex;
+use pear_backend::{CollectionBudget, RefinerConfig};
 use rustc_plugin::{CrateFilter, RustcPlugin, RustcPluginArgs, Utf8Path};
 use serde::{Deserialize, Serialize};
 use std::{borrow::Cow, env, process::Command};
 
+use crate::{
+    analysis::{runner::DumpingOptions, utils::compile_filters},
+    watchdog,
+};
+
 pub struct PearPlugin;
 
 #[derive(Parser, Serialize, Deserialize)]
 pub struct PearPluginArgs {
     #[clap(short, long)]
-    filter: Option<String>,
+    filter: Vec<String>,
+    /// Def path of a function to analyze in addition to the annotated ones.
+    #[clap(long)]
+    entry: Vec<String>,
+    #[clap(long)]
+    require_unique_fn_ptr: bool,
+    #[clap(long)]
+    closure_captures: bool,
+    #[clap(long)]
+    narrow_dyn_calls: bool,
+    #[clap(long)]
+    strip_spans: bool,
+    #[clap(long)]
+    public_api: bool,
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    include_panic_machinery: bool,
+    #[clap(long)]
+    emit_dot: bool,
+    #[clap(long)]
+    timeout_seconds: Option<u64>,
+    #[clap(long)]
+    entries_file: Option<String>,
+    #[clap(long)]
+    validate_mono: bool,
+    #[clap(long, default_value = "pear-out")]
+    out_dir: String,
+    #[clap(long)]
+    max_items: Option<usize>,
+    #[clap(long)]
+    max_depth: Option<usize>,
+    /// Do not collect the items used by the items from the crates with these names.
+    #[clap(long)]
+    crate_filter: Vec<String>,
+    /// Reuse the uses of the items whose bodies did not change since the previous run.
+    #[clap(long)]
+    incremental: bool,
+    /// Also analyze the allocator shims and the panic handler, which are only called by the runtime.
+    #[clap(long)]
+    runtime_roots: bool,
+    /// Write the refined graph of each entry into the baseline directory, for later runs to be
+    /// diffed against.
+    #[clap(long)]
+    write_baseline: bool,
+    #[clap(long, default_value = "pear-baseline")]
+    baseline_dir: String,
+    #[clap(long)]
+    no_build_std: bool,
+    #[clap(long)]
+    target: Option<String>,
+    // Wall-clock deadline shared by all compiler invocations, computed once by the cargo wrapper.
+    #[clap(skip)]
+    deadline: Option<u64>,
     #[clap(last = true)]
     cargo_args: Vec<String>,
 }
@@ -27,13 +83,19 @@ impl RustcPlugin for PearPlugin {
     }
 
     fn args(&self, _target_dir: &Utf8Path) -> RustcPluginArgs<Self::Args> {
-        let args = PearPluginArgs::parse_from(env::args().skip(1));
+        let mut args = PearPluginArgs::parse_from(env::args().skip(1));
+        args.deadline = args.timeout_seconds.map(watchdog::deadline_after);
         let filter = CrateFilter::AllCrates;
         RustcPluginArgs { args, filter }
     }
 
-    fn modify_cargo(&self, cargo: &mut Command, _args: &Self::Args) {
-        pear_backend::modify_cargo(cargo);
+    fn modify_cargo(&self, cargo: &mut Command, args: &Self::Args) {
+        pear_backend::modify_cargo(
+            cargo,
+            !args.no_build_std,
+            args.target.as_deref(),
+            &args.cargo_args,
+        );
     }
 
     fn run(
@@ -43,6 +105,12 @@ impl RustcPlugin for PearPlugin {
     ) -> rustc_interface::interface::Result<()> {
         pear_backend::modify_compiler_args(&mut compiler_args);
 
+        if let (Some(deadline), Some(timeout_seconds)) =
+            (plugin_args.deadline, plugin_args.timeout_seconds)
+        {
+            watchdog::spawn(deadline, timeout_seconds);
+        }
+
         let mut callbacks = match pear_backend::how_to_handle_this_crate(&mut compiler_args) {
             pear_backend::CrateHandling::Noop => {
                 Box::new(pear_backend::NoopCallbacks) as Box<dyn rustc_driver::Callbacks + Send>
@@ -53,12 +121,12 @@ impl RustcPlugin for PearPlugin {
                 ))
             }
             pear_backend::CrateHandling::GlobalAnalysis => {
+                // rustc only collects dead code in the eager mode, which the validation relies on.
+                if plugin_args.validate_mono {
+                    compiler_args.push("-Clink-dead-code".into());
+                }
                 Box::new(pear_backend::GlobalAnalysisCallbacks::new(
-                    crate::analysis::runner::DumpingGlobalAnalysis::new(plugin_args.filter.map(
-                        |filter| {
-                            Regex::new(filter.as_str()).expect("failed to compile filter regex")
-                        },
-                    )),
+                    crate::analysis::runner::DumpingGlobalAnalysis::new(plugin_args.into()),
                     crate::analysis::runner::CachedBodyAnalysis {},
                 ))
             }
@@ -66,3 +134,33 @@ impl RustcPlugin for PearPlugin {
         rustc_driver::RunCompiler::new(&compiler_args, callbacks.as_mut()).run()
     }
 }
+
+impl From<PearPluginArgs> for DumpingOptions {
+    fn from(args: PearPluginArgs) -> Self {
+        Self {
+            filter: compile_filters(&args.filter),
+            refiner_config: RefinerConfig {
+                require_unique_fn_ptr: args.require_unique_fn_ptr,
+                closure_captures: args.closure_captures,
+                narrow_dyn_calls: args.narrow_dyn_calls,
+            },
+            strip_spans: args.strip_spans,
+            public_api: args.public_api,
+            include_panic_machinery: args.include_panic_machinery,
+            emit_dot: args.emit_dot,
+            entries_file: args.entries_file,
+            named_entries: args.entry,
+            validate_mono: args.validate_mono,
+            out_dir: args.out_dir.into(),
+            baseline_dir: args.baseline_dir.into(),
+            collection_budget: CollectionBudget {
+                max_items: args.max_items,
+                max_depth: args.max_depth,
+            },
+            crate_filter: args.crate_filter,
+            incremental: args.incremental,
+            runtime_roots: args.runtime_roots,
+            write_baseline: args.write_baseline,
+        }
+    }
+}
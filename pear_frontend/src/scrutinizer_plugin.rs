@@ -1,16 +1,28 @@
 use clap::Parser;
 
-use regex::Regex;
 use rustc_plugin::{CrateFilter, RustcPlugin, RustcPluginArgs, Utf8Path};
 use serde::{Deserialize, Serialize};
 use std::{borrow::Cow, env, process::Command};
 
+use crate::analysis::utils::compile_filters;
+
 pub struct ScrutinizerPlugin;
 
 #[derive(Parser, Serialize, Deserialize)]
 pub struct ScrutinizerPluginArgs {
     #[clap(short, long)]
-    filter: Option<String>,
+    filter: Vec<String>,
+    /// Def path of a function to analyze in addition to the annotated ones.
+    #[clap(long)]
+    entry: Vec<String>,
+    #[clap(long, default_value = "pear-out")]
+    out_dir: String,
+    #[clap(long)]
+    no_cache: bool,
+    #[clap(long)]
+    no_build_std: bool,
+    #[clap(long)]
+    target: Option<String>,
     #[clap(last = true)]
     cargo_args: Vec<String>,
 }
@@ -32,8 +44,13 @@ impl RustcPlugin for ScrutinizerPlugin {
         RustcPluginArgs { args, filter }
     }
 
-    fn modify_cargo(&self, cargo: &mut Command, _args: &Self::Args) {
-        pear_backend::modify_cargo(cargo);
+    fn modify_cargo(&self, cargo: &mut Command, args: &Self::Args) {
+        pear_backend::modify_cargo(
+            cargo,
+            !args.no_build_std,
+            args.target.as_deref(),
+            &args.cargo_args,
+        );
     }
 
     fn run(
@@ -55,9 +72,10 @@ impl RustcPlugin for ScrutinizerPlugin {
             pear_backend::CrateHandling::GlobalAnalysis => {
                 Box::new(pear_backend::GlobalAnalysisCallbacks::new(
                     crate::analysis::scrutinizer::ScrutinizerGlobalAnalysis::new(
-                        plugin_args.filter.map(|filter| {
-                            Regex::new(filter.as_str()).expect("failed to compile filter regex")
-                        }),
+                        compile_filters(&plugin_args.filter),
+                        plugin_args.entry,
+                        plugin_args.out_dir.into(),
+                        !plugin_args.no_cache,
                     ),
                     crate::analysis::scrutinizer::ScrutinizerLocalAnalysis {},
                 ))
@@ -0,0 +1,63 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    process,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Exit code used when the analysis exceeds its wall-clock budget.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+// Held while an artifact is being written so that the watchdog never exits mid-write.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Returns the deadline, in seconds since the Unix epoch, for a run starting now.
+pub fn deadline_after(timeout_seconds: u64) -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is before the Unix epoch")
+        .as_secs()
+        + timeout_seconds
+}
+
+/// Spawns a thread that aborts the process once the deadline passes.
+pub fn spawn(deadline: u64, timeout_seconds: u64) {
+    let remaining = UNIX_EPOCH + Duration::from_secs(deadline);
+    thread::spawn(move || {
+        if let Ok(remaining) = remaining.duration_since(SystemTime::now()) {
+            thread::sleep(remaining);
+        }
+        // Wait for any in-progress write to finish; artifacts written so far stay intact.
+        let _guard = WRITE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        eprintln!("ERROR: analysis exceeded the timeout of {timeout_seconds} seconds, aborting");
+        process::exit(TIMEOUT_EXIT_CODE);
+    });
+}
+
+/// Writes the file through a temporary one and a rename, so it is never observed half-written.
+pub fn write_atomically(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_atomically_with(path, |writer| writer.write_all(contents.as_ref()))
+}
+
+/// Like [`write_atomically`], but streams the contents into the file as they are produced
+/// instead of holding all of them in memory.
+pub fn write_atomically_with(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let _guard = WRITE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    fs::rename(&tmp_path, path)
+}
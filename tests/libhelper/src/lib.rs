@@ -0,0 +1,5 @@
+// A dependency whose crate name starts with "lib": its rlib is named
+// `liblibhelper-<hash>.rlib`, and the artifacts left next to it must follow.
+pub fn double(a: u32) -> u32 {
+    a * 2
+}
@@ -0,0 +1,19 @@
+#![no_std]
+#![allow(dead_code, unused)]
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+fn record_panic() {
+    PANICKED.store(true, Ordering::SeqCst);
+}
+
+// The panic handler is only called through the `panic_impl` lang item, so it is only collected
+// from with `--runtime-roots`.
+#[panic_handler]
+fn on_panic(info: &PanicInfo) -> ! {
+    record_panic();
+    loop {}
+}
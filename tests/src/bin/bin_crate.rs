@@ -0,0 +1,19 @@
+#![allow(dead_code, unused)]
+
+// The bodies of the library functions are loaded from the artifacts the library
+// crate left next to its metadata, which are looked up by the library name.
+#[pear::scrutinizer_pure]
+fn bin_entry(a: u32) -> u32 {
+    test_crate::public_api::api_entry() + test_crate::public_api::Counter.count() + a
+}
+
+// The same lookup for a dependency whose name already starts with "lib".
+#[pear::scrutinizer_pure]
+fn lib_prefixed_entry(a: u32) -> u32 {
+    libhelper::double(a)
+}
+
+fn main() {
+    bin_entry(1);
+    lib_prefixed_entry(1);
+}
@@ -0,0 +1,24 @@
+#![allow(dead_code, unused)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+
+// The allocator is only called through the shims generated for `#[global_allocator]`, which are
+// collected from with `--runtime-roots`.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn main() {
+    let boxed = Box::new(1);
+}
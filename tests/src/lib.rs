@@ -1,12 +1,14 @@
 #![feature(allocator_api)]
 #![feature(const_trait_impl)]
 #![feature(const_refs_to_cell)]
+#![feature(trait_upcasting)]
 #![allow(dead_code, unused)]
 
 mod collections;
 mod pear;
 mod scrutinizer;
 mod kani;
+pub mod public_api;
 
 macro_rules! redefine {
     (<$origin_ty:ty> :: $func_ident:ident => $($param_ident:ident : $param_ty:ty),* => $ret_ty:ty, $purity:path) => {
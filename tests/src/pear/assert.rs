@@ -0,0 +1,10 @@
+mod bounds_check {
+    fn get(values: &[u32], idx: usize) -> u32 {
+        values[idx]
+    }
+
+    #[pear::analysis_entry]
+    fn main(idx: usize) -> u32 {
+        get(&[1, 2, 3], idx)
+    }
+}
@@ -16,4 +16,28 @@ mod async_fns {
     async fn two_levels_async() {
         one_level_async().await;
     }
-}
\ No newline at end of file
+}
+mod awaiting {
+    use futures::executor::block_on;
+
+    fn greet(name: &str) -> String {
+        format!("hello, {name}!")
+    }
+
+    async fn inner(name: String) -> String {
+        greet(&name)
+    }
+
+    #[pear::analysis_entry]
+    async fn outer() {
+        let name = String::from("pear");
+        let greeting = inner(name.clone()).await;
+        let again = inner(greeting).await;
+        println!("{again}");
+    }
+
+    #[pear::analysis_entry]
+    fn run() {
+        block_on(outer());
+    }
+}
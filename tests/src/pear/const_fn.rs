@@ -0,0 +1,40 @@
+mod const_and_runtime {
+    const fn compile_time_square(a: usize) -> usize {
+        a * a
+    }
+
+    const fn runtime_square(a: usize) -> usize {
+        a * a
+    }
+
+    const SQUARED: usize = compile_time_square(4);
+
+    #[pear::analysis_entry]
+    fn main(a: usize) -> usize {
+        SQUARED + runtime_square(a)
+    }
+}
+
+mod const_fn_ptrs {
+    fn compile_time_only() {}
+
+    fn runtime_callee() {}
+
+    fn other_runtime_callee() {}
+
+    const FNS: [fn(); 1] = [compile_time_only];
+
+    // Only the length of the array survives to runtime, so its functions cannot be called.
+    const FN_COUNT: usize = FNS.len();
+
+    #[pear::analysis_entry]
+    fn main(a: usize) -> usize {
+        let f: fn() = if a > FN_COUNT {
+            runtime_callee
+        } else {
+            other_runtime_callee
+        };
+        f();
+        FN_COUNT
+    }
+}
@@ -0,0 +1,8 @@
+mod boundary {
+    // With `--crate-filter alloc`, `Vec::push` is collected, but the items it uses are not.
+    #[pear::analysis_entry]
+    fn main() {
+        let mut v = Vec::new();
+        v.push(1u32);
+    }
+}
@@ -0,0 +1,25 @@
+mod shortcut {
+    fn leaf() -> usize {
+        42
+    }
+
+    fn shared() -> usize {
+        leaf()
+    }
+
+    fn inner() -> usize {
+        shared()
+    }
+
+    fn outer() -> usize {
+        inner()
+    }
+
+    // `shared` is first reached at depth 3 through `outer`, where `leaf` is cut off by a maximum
+    // depth of 3, and then at depth 1, where `leaf` is within the budget.
+    #[pear::analysis_entry]
+    fn main() -> usize {
+        outer();
+        shared()
+    }
+}
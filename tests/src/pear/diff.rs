@@ -0,0 +1,14 @@
+mod changed_callee {
+    fn first() -> usize {
+        1
+    }
+
+    fn second() -> usize {
+        2
+    }
+
+    #[pear::analysis_entry]
+    fn main() -> usize {
+        first()
+    }
+}
@@ -17,4 +17,41 @@ mod implicit_drop {
     fn implicit_drop_box() {
         let dyn_foo: Box<dyn T> = Box::new(Foo { a: 42 });
     }
-}
\ No newline at end of file
+}
+mod drop_glue_kind {
+    struct Foo {
+        a: u32,
+    }
+
+    impl Drop for Foo {
+        fn drop(&mut self) {
+            println!("{}", self.a);
+        }
+    }
+
+    struct Bar {
+        foo: Foo,
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let bar = Bar { foo: Foo { a: 42 } };
+    }
+}
+
+mod vec_drop {
+    struct Foo {
+        a: u32,
+    }
+
+    impl Drop for Foo {
+        fn drop(&mut self) {
+            println!("{}", self.a);
+        }
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let foos = vec![Foo { a: 42 }, Foo { a: 43 }];
+    }
+}
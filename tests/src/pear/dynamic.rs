@@ -262,4 +262,124 @@ mod dyn_diamond {
             invoker(s2, a, b)
         };
     }
-}
\ No newline at end of file
+}
+mod dyn_narrowing {
+    trait Foo {
+        fn bar(&self, a: usize, b: usize) -> usize;
+    }
+
+    struct S1;
+    struct S2;
+
+    impl Foo for S1 {
+        fn bar(&self, a: usize, b: usize) -> usize {
+            a + b
+        }
+    }
+
+    impl Foo for S2 {
+        fn bar(&self, a: usize, b: usize) -> usize {
+            a - b
+        }
+    }
+
+    fn invoker(s: &dyn Foo, a: usize, b: usize) -> usize {
+        s.bar(a, b)
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let b = 6;
+
+        // Makes S2 a reachable vtable candidate without ever calling it.
+        let _unused = &S2 {} as &dyn Foo;
+
+        let res = invoker(&S1 {}, a, b);
+    }
+}
+
+mod dyn_upcast {
+    trait Bar {
+        fn super_bar(&self, a: usize, b: usize) -> usize;
+    }
+
+    trait Foo: Bar {
+        fn bar(&self, a: usize, b: usize) -> usize;
+    }
+
+    struct S1;
+    struct S2;
+
+    impl Bar for S1 {
+        fn super_bar(&self, a: usize, b: usize) -> usize {
+            a + b
+        }
+    }
+
+    impl Bar for S2 {
+        fn super_bar(&self, a: usize, b: usize) -> usize {
+            a - b
+        }
+    }
+
+    impl Foo for S1 {
+        fn bar(&self, a: usize, b: usize) -> usize {
+            a + 2 * b
+        }
+    }
+
+    impl Foo for S2 {
+        fn bar(&self, a: usize, b: usize) -> usize {
+            a - 2 * b
+        }
+    }
+
+    fn invoker(s: &dyn Bar, a: usize, b: usize) -> usize {
+        s.super_bar(a, b)
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let b = 6;
+
+        let s = if a > b {
+            &S1 {} as &dyn Foo
+        } else {
+            &S2 {} as &dyn Foo
+        };
+
+        let res = invoker(s as &dyn Bar, a, b);
+    }
+}
+
+mod reified_dyn_method {
+    trait Greeter {
+        fn greet(&self) -> usize;
+    }
+
+    struct English;
+
+    impl Greeter for English {
+        fn greet(&self) -> usize {
+            1
+        }
+    }
+
+    struct French;
+
+    impl Greeter for French {
+        fn greet(&self) -> usize {
+            2
+        }
+    }
+
+    #[pear::analysis_entry]
+    fn main(a: usize) -> usize {
+        let greeter: &dyn Greeter = if a > 1 { &English } else { &French };
+        // The trait object method is taken as a function item and reified.
+        let greet: fn(&dyn Greeter) -> usize = <dyn Greeter as Greeter>::greet;
+        greet(greeter)
+    }
+}
@@ -57,3 +57,213 @@ mod stored_fn_ptr {
         let res = func.eval(a, b);
     }
 }
+
+mod ambiguous_fn_ptr {
+    fn fn_1(a: usize, b: usize) -> usize {
+        a + b
+    }
+
+    fn fn_2(a: usize, b: usize) -> usize {
+        a * b
+    }
+
+    fn pick(a: usize, b: usize) -> fn(usize, usize) -> usize {
+        if a > b {
+            fn_1
+        } else {
+            fn_2
+        }
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let b = 6;
+
+        let func = pick(a, b);
+        let res = func(a, b);
+    }
+}
+
+mod fn_ptr_array {
+    fn fn_1(a: usize, b: usize) -> usize {
+        a + b
+    }
+
+    fn fn_2(a: usize, b: usize) -> usize {
+        a - b
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let b = 6;
+
+        let table: [fn(usize, usize) -> usize; 2] = [fn_1, fn_2];
+        let res = table[a % 2](a, b);
+    }
+}
+
+mod fn_ptr_const_array {
+    fn fn_1(a: usize, b: usize) -> usize {
+        a + b
+    }
+
+    fn fn_2(a: usize, b: usize) -> usize {
+        a - b
+    }
+
+    const TABLE: [fn(usize, usize) -> usize; 2] = [fn_1, fn_2];
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let b = 6;
+
+        let res = TABLE[a % 2](a, b);
+    }
+}
+
+mod fn_ptr_lifetimes {
+    fn first<'a>(a: &'a str, b: &'a str) -> &'a str {
+        a
+    }
+
+    fn second(a: &'static str, b: &'static str) -> &'static str {
+        b
+    }
+
+    fn first_mut<'a>(a: &'a mut str, b: &'a str) -> &'a str {
+        b
+    }
+
+    unsafe fn first_unsafe<'a>(a: &'a str, b: &'a str) -> &'a str {
+        a
+    }
+
+    extern "C" fn first_extern<'a>(a: &'a str, b: &'a str) -> &'a str {
+        a
+    }
+
+    fn first_unary<'a>(a: &'a str) -> &'a str {
+        a
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = "pear";
+        let b = "peach";
+
+        let func = if a > b {
+            first as fn(&'static str, &'static str) -> &'static str
+        } else {
+            second as fn(&'static str, &'static str) -> &'static str
+        };
+        let res = func(a, b);
+
+        let _mutable = first_mut as for<'a> fn(&'a mut str, &'a str) -> &'a str;
+        let _unsafe = first_unsafe as unsafe fn(&'static str, &'static str) -> &'static str;
+        let _extern = first_extern as extern "C" fn(&'static str, &'static str) -> &'static str;
+        let _unary = first_unary as fn(&'static str) -> &'static str;
+    }
+}
+
+mod transmuted_fn_ptr {
+    fn fn_1(a: isize, b: isize) -> isize {
+        a + b
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let b = 6;
+
+        let ptr = fn_1 as *const ();
+        // SAFETY: `isize` and `usize` have the same layout, and the sum does not overflow.
+        let func: fn(usize, usize) -> usize = unsafe { std::mem::transmute(ptr) };
+        let res = func(a, b);
+    }
+}
+
+mod unresolved_fn_ptr {
+    #[pear::analysis_entry]
+    fn main() {
+        // SAFETY: none, the pointer does not point to a function and is never meant to be called.
+        let func: fn(u128, u16) -> i8 = unsafe { std::mem::transmute(0x1000usize) };
+        let res = func(5, 6);
+    }
+}
+
+mod track_caller_fn_ptr {
+    #[track_caller]
+    fn located(a: usize, b: usize) -> usize {
+        assert!(a < b, "called from {}", std::panic::Location::caller());
+        a + b
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let b = 6;
+
+        let res = located(a, b);
+        let func = located as fn(usize, usize) -> usize;
+        let res = func(a, b);
+    }
+}
+
+mod enum_dispatch {
+    #[derive(Clone, Copy)]
+    enum Op {
+        Add,
+        Sub,
+    }
+
+    #[derive(Clone, Copy)]
+    enum BoundOp {
+        Mul(fn(usize, usize) -> usize),
+        Div(fn(usize, usize) -> usize),
+    }
+
+    fn add(a: usize, b: usize) -> usize {
+        a + b
+    }
+
+    fn sub(a: usize, b: usize) -> usize {
+        a - b
+    }
+
+    fn mul(a: usize, b: usize) -> usize {
+        a * b
+    }
+
+    fn div(a: usize, b: usize) -> usize {
+        a / b
+    }
+
+    fn unused(a: usize, b: usize) -> usize {
+        a % b
+    }
+
+    const BOUND_OPS: [BoundOp; 2] = [BoundOp::Mul(mul), BoundOp::Div(div)];
+
+    fn op_fn(op: Op) -> fn(usize, usize) -> usize {
+        match op {
+            Op::Add => add,
+            Op::Sub => sub,
+        }
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 6;
+        let b = 5;
+
+        let op = if a > b { Op::Sub } else { Op::Add };
+        let res = op_fn(op)(a, b);
+
+        let res = match BOUND_OPS[a % 2] {
+            BoundOp::Mul(f) | BoundOp::Div(f) => f(a, b),
+        };
+    }
+}
@@ -129,3 +129,123 @@ mod boxed_closure_as_fn_trait {
         let res = invoker(func, a, b);
     }
 }
+
+mod closure_captures {
+    fn invoker(func: impl Fn() -> usize) -> usize {
+        func()
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let name = String::from("pear");
+        let res = invoker(move || name.len());
+    }
+}
+
+mod fn_mut_closure {
+    fn invoker(func: &mut dyn FnMut(usize) -> usize, a: usize) -> usize {
+        func(a)
+    }
+
+    fn fn_1(total: &mut usize, a: usize) -> usize {
+        *total += a;
+        *total
+    }
+
+    fn fn_2(total: &mut usize, a: usize) -> usize {
+        *total -= a;
+        *total
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let mut total = 6;
+
+        let mut add = |a: usize| fn_1(&mut total, a);
+        let res = invoker(&mut add, a);
+
+        let mut sub = |a: usize| fn_2(&mut total, a);
+        let res = invoker(&mut sub, a);
+    }
+}
+
+mod boxed_fn_once {
+    fn invoker(func: Box<dyn FnOnce(usize) -> String>, a: usize) -> String {
+        func(a)
+    }
+
+    fn fn_1(name: String, a: usize) -> String {
+        name.repeat(a)
+    }
+
+    fn fn_2(name: String, a: usize) -> String {
+        name
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+        let name = String::from("pear");
+
+        let func: Box<dyn FnOnce(usize) -> String> = if a > 6 {
+            Box::new(move |a: usize| fn_1(name, a))
+        } else {
+            Box::new(move |a: usize| fn_2(name, a))
+        };
+
+        let res = invoker(func, a);
+    }
+}
+
+mod reborrowed_dyn_fn {
+    fn fn_1(a: usize) -> usize {
+        a + 1
+    }
+
+    fn fn_2(a: usize) -> usize {
+        a - 1
+    }
+
+    fn invoker(func: &mut dyn FnMut(usize) -> usize, a: usize) -> usize {
+        let reborrowed = &mut *func;
+        (&mut &mut *reborrowed)(a)
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let a = 5;
+
+        let func_1 = &mut fn_1 as &mut dyn FnMut(usize) -> usize;
+        let func_2 = &mut fn_2 as &mut dyn FnMut(usize) -> usize;
+
+        let func = if a > 6 { func_1 } else { func_2 };
+
+        let res = invoker(func, a);
+    }
+}
+
+mod merged_refinement {
+    fn fn_1(a: usize, b: usize) -> usize {
+        a + b
+    }
+
+    fn fn_2(a: usize, b: usize) -> usize {
+        a - b
+    }
+
+    fn apply<F: Fn(usize, usize) -> usize>(func: F, a: usize, b: usize) -> usize {
+        func(a, b)
+    }
+
+    #[pear::analysis_entry]
+    fn refine_fn() -> usize {
+        apply(fn_1, 5, 6)
+    }
+
+    #[pear::analysis_entry]
+    fn refine_fn_box() -> usize {
+        let func: Box<dyn Fn(usize, usize) -> usize> = Box::new(fn_2);
+        func(6, 5)
+    }
+}
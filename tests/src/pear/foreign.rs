@@ -0,0 +1,10 @@
+mod foreign_call {
+    extern "C" {
+        fn abs(x: i32) -> i32;
+    }
+
+    #[pear::analysis_entry]
+    fn main() -> i32 {
+        unsafe { abs(-1) }
+    }
+}
@@ -0,0 +1,14 @@
+mod changed_body {
+    fn unchanged(a: usize) -> usize {
+        a * 2
+    }
+
+    fn changed(a: usize) -> usize {
+        a + 1
+    }
+
+    #[pear::analysis_entry]
+    fn main() -> usize {
+        unchanged(1) + changed(2)
+    }
+}
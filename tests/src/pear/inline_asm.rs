@@ -0,0 +1,9 @@
+mod sym_fn {
+    extern "C" fn asm_callee() {}
+
+    #[pear::analysis_entry]
+    fn main() {
+        // SAFETY: the callee takes no arguments and clobbers only what the C ABI allows.
+        unsafe { std::arch::asm!("call {callee}", callee = sym asm_callee, clobber_abi("C")) };
+    }
+}
@@ -0,0 +1,35 @@
+mod inherent_method {
+    struct Counter {
+        count: usize,
+    }
+
+    impl Counter {
+        fn step(&self) -> usize {
+            1
+        }
+
+        #[pear::analysis_entry]
+        fn advance(&mut self) {
+            self.count += self.step();
+        }
+    }
+}
+
+mod trait_impl_method {
+    trait Greeter {
+        fn greet(&self) -> String;
+    }
+
+    struct English;
+
+    fn greeting() -> String {
+        String::from("hello")
+    }
+
+    impl Greeter for English {
+        #[pear::analysis_entry]
+        fn greet(&self) -> String {
+            greeting()
+        }
+    }
+}
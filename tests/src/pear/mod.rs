@@ -1,6 +1,20 @@
+mod assert;
 mod async_fns;
+mod const_fn;
+mod crate_filter;
+mod depth;
+mod diff;
 mod drop;
 mod dynamic;
 mod fn_ptr;
 mod fn_trait;
+mod foreign;
+mod incremental;
+#[cfg(target_arch = "x86_64")]
+mod inline_asm;
+mod methods;
+mod prune;
+mod recursion;
 mod r#static;
+mod strip_spans;
+mod unresolved;
@@ -0,0 +1,23 @@
+mod merged {
+    fn shared() -> usize {
+        42
+    }
+
+    fn only_first() -> usize {
+        shared() + 1
+    }
+
+    fn only_second() -> usize {
+        shared() - 1
+    }
+
+    #[pear::analysis_entry]
+    fn first() -> usize {
+        only_first()
+    }
+
+    #[pear::analysis_entry]
+    fn second() -> usize {
+        only_second()
+    }
+}
@@ -0,0 +1,37 @@
+mod self_recursive {
+    fn factorial(n: usize) -> usize {
+        if n == 0 {
+            1
+        } else {
+            n * factorial(n - 1)
+        }
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let res = factorial(5);
+    }
+}
+
+mod mutually_recursive {
+    fn is_even(n: usize) -> bool {
+        if n == 0 {
+            true
+        } else {
+            is_odd(n - 1)
+        }
+    }
+
+    fn is_odd(n: usize) -> bool {
+        if n == 0 {
+            false
+        } else {
+            is_even(n - 1)
+        }
+    }
+
+    #[pear::analysis_entry]
+    fn main() {
+        let res = is_even(5);
+    }
+}
@@ -0,0 +1,9 @@
+mod structure {
+    fn callee() {}
+
+    #[pear::analysis_entry]
+    fn main() {
+        callee();
+        callee();
+    }
+}
@@ -0,0 +1,14 @@
+mod generic_entry {
+    trait Shape {
+        fn area(&self) -> u32;
+    }
+
+    fn helper() -> u32 {
+        42
+    }
+
+    #[pear::analysis_entry]
+    fn main<T: Shape>(shape: &T) -> u32 {
+        shape.area() + helper()
+    }
+}
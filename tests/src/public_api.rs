@@ -0,0 +1,31 @@
+fn private_helper() -> u32 {
+    2
+}
+
+pub fn api_leaf() -> u32 {
+    1
+}
+
+pub fn api_entry() -> u32 {
+    private_helper() + api_leaf()
+}
+
+pub fn another_entry() {
+    private_helper();
+}
+
+pub(crate) fn crate_visible() -> u32 {
+    private_helper()
+}
+
+pub struct Counter;
+
+impl Counter {
+    pub fn count(&self) -> u32 {
+        private_helper()
+    }
+
+    fn internal(&self) -> u32 {
+        api_leaf()
+    }
+}
@@ -26,6 +26,8 @@ redefine! { <HashMap<usize, usize>>::get_mut<'a> => hashmap: &'a mut HashMap<usi
 redefine! { <HashMap<usize, usize>>::hasher => hashmap: &HashMap<usize, usize> => &RandomState, pear::scrutinizer_pure }
 redefine! { <HashMap<usize, usize>>::index<'a> => hashmap: &'a HashMap<usize, usize>, key: &'a usize => &'a usize, pear::scrutinizer_pure }
 redefine! { <HashMap<usize, usize>>::insert => hashmap: &mut HashMap<usize, usize>, k: usize, v: usize => Option<usize>, pear::scrutinizer_impure }
+// Methods taking the map by value are not trusted, so they are analyzed down to the raw table and
+// stay pure: they only move the allocation out of the map they own.
 redefine! { into_iter_1, <HashMap<usize, usize>>::into_iter => hashmap: HashMap<usize, usize> => IntoIter<usize, usize>, pear::scrutinizer_pure }
 #[pear::scrutinizer_impure]
 pub fn into_iter_2(hashmap: &mut HashMap<usize, usize>) -> IterMut<'_, usize, usize> {
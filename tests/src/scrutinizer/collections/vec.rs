@@ -25,6 +25,8 @@ redefine! { <Vec<usize>>::drain => vec: &mut Vec<usize>, range: impl RangeBounds
 redefine! { <Vec<usize>>::extend_from_slice => vec: &mut Vec<usize>, other: &[usize] => (), pear::scrutinizer_impure }
 redefine! { <Vec<usize>>::extend_from_within => vec: &mut Vec<usize>, src: impl RangeBounds<usize> => (), pear::scrutinizer_impure }
 redefine! { <Vec<usize>>::insert => vec: &mut Vec<usize>, index: usize, element: usize => (), pear::scrutinizer_impure }
+// Methods taking the vector by value are not trusted, so they are analyzed down to the raw vector
+// and stay pure: they only move the buffer out of the vector they own.
 redefine! { <Vec<usize>>::into_boxed_slice => vec: Vec<usize> => Box<[usize], Global>, pear::scrutinizer_pure }
 redefine! { <Vec<usize>>::is_empty => vec: &Vec<usize> => bool, pear::scrutinizer_pure }
 redefine! { <Vec<usize>>::leak => vec: Vec<usize> => &'static mut [usize], pear::scrutinizer_pure }
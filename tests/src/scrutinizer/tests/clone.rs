@@ -0,0 +1,31 @@
+mod tuple_clone {
+    static mut CLONES: usize = 0;
+
+    fn count_clones(n: usize) {
+        unsafe {
+            CLONES += n;
+        }
+    }
+
+    struct Tracked(usize);
+
+    impl Clone for Tracked {
+        fn clone(&self) -> Self {
+            count_clones(self.0);
+            Tracked(self.0)
+        }
+    }
+
+    // Tuples are cloned by a shim that clones every element.
+    #[pear::scrutinizer_impure]
+    fn clone_tracked_pair(pair: &(Tracked, usize)) -> usize {
+        let cloned = pair.clone();
+        cloned.1
+    }
+
+    #[pear::scrutinizer_pure]
+    fn clone_plain_pair(pair: &(usize, usize)) -> usize {
+        let cloned = pair.clone();
+        cloned.1
+    }
+}
@@ -17,3 +17,12 @@ mod fmt {
         format!("{}", data)
     }
 }
+
+mod unresolved_fn_ptr {
+    #[pear::scrutinizer_impure]
+    pub fn call_unknown_fn_ptr(data: u128) -> i8 {
+        // SAFETY: none, the pointer does not point to a function and is never meant to be called.
+        let fn_ptr: fn(u128, u16) -> i8 = unsafe { std::mem::transmute(0x1000usize) };
+        fn_ptr(data, 0)
+    }
+}
@@ -0,0 +1,5 @@
+// Instantiated with concrete types in scrutinizer-config.toml.
+#[pear::scrutinizer_pure]
+pub fn sum<T: Into<usize>>(a: T, b: usize) -> usize {
+    a.into() + b
+}
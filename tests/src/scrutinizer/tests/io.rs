@@ -0,0 +1,19 @@
+mod read_file {
+    use std::fs;
+
+    #[pear::scrutinizer_pure]
+    fn read_len(path: &str) -> usize {
+        fs::read_to_string(path)
+            .map(|contents| contents.len())
+            .unwrap_or(0)
+    }
+}
+
+mod write_file {
+    use std::fs;
+
+    #[pear::scrutinizer_impure]
+    fn write_len(path: &str, len: usize) -> bool {
+        fs::write(path, len.to_string()).is_ok()
+    }
+}
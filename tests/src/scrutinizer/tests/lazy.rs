@@ -0,0 +1,10 @@
+mod once_lock {
+    use std::sync::OnceLock;
+
+    static CACHED: OnceLock<usize> = OnceLock::new();
+
+    #[pear::scrutinizer_impure]
+    fn cached_inc(a: usize) -> usize {
+        *CACHED.get_or_init(|| a + 1)
+    }
+}
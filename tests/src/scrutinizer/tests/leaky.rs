@@ -18,11 +18,41 @@ mod network {
 
 mod interior {
     use std::cell::RefCell;
+    use std::rc::Rc;
 
     #[pear::scrutinizer_impure]
     pub fn ref_cell_mut(refcell: &RefCell<usize>) {
         *refcell.borrow_mut() = 10;
     }
+
+    // Not caught by the signature, since the interior mutability is behind an `Rc`.
+    #[pear::scrutinizer_impure]
+    pub fn ref_cell_rc_mut(refcell: Rc<RefCell<usize>>) {
+        *refcell.borrow_mut() = 10;
+    }
+}
+
+mod atomic {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Taken through an `Arc`, as a shared reference to an atomic is rejected by its signature.
+    #[pear::scrutinizer_impure]
+    pub fn atomic_store(counter: Arc<AtomicUsize>, value: usize) {
+        counter.store(value, Ordering::SeqCst);
+    }
+
+    #[pear::scrutinizer_impure]
+    pub fn atomic_fetch_add(counter: Arc<AtomicUsize>) -> usize {
+        counter.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // Loading does not modify the atomic. The `Arc` is borrowed, so its reference count is not
+    // decremented on return either.
+    #[pear::scrutinizer_pure]
+    pub fn atomic_load(counter: &Arc<AtomicUsize>) -> usize {
+        counter.load(Ordering::SeqCst)
+    }
 }
 
 mod implicit {
@@ -40,6 +70,22 @@ mod implicit {
     pub fn sneaky_drop(data: usize) {
         let sp = CustomSmartPointer { data };
     }
+
+    #[pear::scrutinizer_impure]
+    pub fn sneaky_vec_drop(data: usize) {
+        let sps = vec![CustomSmartPointer { data }];
+    }
+}
+
+mod ffi {
+    extern "C" {
+        fn abs(value: i32) -> i32;
+    }
+
+    #[pear::scrutinizer_impure]
+    pub fn extern_abs(value: i32) -> i32 {
+        unsafe { abs(value) }
+    }
 }
 
 mod adversarial {
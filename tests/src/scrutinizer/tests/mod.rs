@@ -1,10 +1,17 @@
+mod clone;
 mod r#dyn;
 mod fn_ptr;
 mod foreign;
+mod generic;
+mod io;
 mod lam;
+mod lazy;
 mod leaky;
+mod out_param;
 mod raw_ptr;
 mod recursive;
+mod shallow;
 mod r#static;
 mod structs;
+mod trusted;
 mod vartrack;
@@ -0,0 +1,16 @@
+// Allowed to take mutable references by `allow_mut_args` in the config.
+#[pear::scrutinizer_pure]
+fn write_sum(a: usize, b: usize, out: &mut usize) {
+    *out = a + b;
+}
+
+#[pear::scrutinizer_impure]
+fn write_product(a: usize, b: usize, out: &mut usize) {
+    *out = a * b;
+}
+
+// Not allowed by `allow_mut_args`, as its entries match whole def paths only.
+#[pear::scrutinizer_impure]
+fn write_sum_into(a: usize, b: usize, out: &mut usize) {
+    *out = a + b;
+}
@@ -56,3 +56,16 @@ mod raw_const_ptr {
         let _points_at = *raw;
     }
 }
+
+mod raw_mut_ptr_helper {
+    fn overwrite(raw: *mut usize, a: usize) {
+        unsafe { *raw = a };
+    }
+
+    // Raw mut pointer dereference in a helper, reached through the call to it.
+    #[pear::scrutinizer_impure]
+    pub fn overwrite_via_helper(a: usize) {
+        let mut x = 0;
+        overwrite(&mut x as *mut usize, a);
+    }
+}
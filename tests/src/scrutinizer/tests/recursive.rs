@@ -46,3 +46,15 @@ mod mutually_recursive {
         println!("{}", a);
     }
 }
+
+mod unbounded_recursive {
+    // Pure as long as recursion is not treated as impure.
+    #[pear::scrutinizer_pure]
+    fn countdown(a: usize) -> usize {
+        if a == 0 {
+            0
+        } else {
+            countdown(a - 1) + countdown(a / 2)
+        }
+    }
+}
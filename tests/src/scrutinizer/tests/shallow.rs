@@ -0,0 +1,19 @@
+mod two_level_chain {
+    static mut GLOBAL_COUNTER: u32 = 0;
+
+    fn leak_into_static(a: u32) {
+        unsafe {
+            GLOBAL_COUNTER += a;
+        }
+    }
+
+    fn forward(a: u32) {
+        leak_into_static(a);
+    }
+
+    // Passes when shallow, as the write is nested two calls deep.
+    #[pear::scrutinizer_impure]
+    fn impure(a: u32) {
+        forward(a);
+    }
+}
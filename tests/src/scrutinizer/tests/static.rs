@@ -7,6 +7,13 @@ mod mutable_static {
             GLOBAL_VEC.push(a);
         }
     }
+
+    static mut GLOBAL_COUNTER: u32 = 0;
+
+    #[pear::scrutinizer_impure]
+    fn read_from_static(a: u32) -> u32 {
+        unsafe { a + GLOBAL_COUNTER }
+    }
 }
 
 mod mutation_from_static {
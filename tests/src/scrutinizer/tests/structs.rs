@@ -14,3 +14,16 @@ fn structs(a: usize) {
     foo.a = 30;
     foo.b = "hello2";
 }
+
+struct Accumulator {
+    total: usize,
+}
+
+impl Accumulator {
+    // Rejected by its signature, as it takes a mutable reference.
+    #[pear::scrutinizer_impure]
+    fn add(&mut self, a: usize) -> usize {
+        self.total += a;
+        self.total
+    }
+}
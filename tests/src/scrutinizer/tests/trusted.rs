@@ -0,0 +1,93 @@
+// Trusted through the `trusted_stdlib` config, which only trusts methods confined to changing
+// their receiver.
+mod receivers {
+    pub struct Counter {
+        count: usize,
+    }
+
+    impl Counter {
+        pub fn bump(&mut self) {
+            let raw = &mut self.count as *mut usize;
+            unsafe { *raw += 1 };
+        }
+
+        pub fn bumped(mut self) -> Self {
+            let raw = &mut self.count as *mut usize;
+            unsafe { *raw += 1 };
+            self
+        }
+    }
+
+    #[pear::scrutinizer_pure]
+    pub fn bump_mut_ref(start: usize) -> usize {
+        let mut counter = Counter { count: start };
+        counter.bump();
+        counter.count
+    }
+
+    #[pear::scrutinizer_impure]
+    pub fn bump_by_value(start: usize) -> usize {
+        Counter { count: start }.bumped().count
+    }
+}
+
+// Allowlisted through `allowlist_def_paths` in the config.
+mod allowlisted {
+    pub fn overwrite(value: usize) -> usize {
+        let mut copy = value;
+        let raw = &mut copy as *mut usize;
+        unsafe { *raw = 0 };
+        copy
+    }
+
+    #[pear::scrutinizer_pure]
+    pub fn overwrite_allowlisted(value: usize) -> usize {
+        overwrite(value)
+    }
+}
+
+// Allowlisted through a prefix of the name of the crate.
+mod allowlisted_crate {
+    #[pear::scrutinizer_pure]
+    pub fn overwrite_in_allowlisted_crate(value: usize) -> usize {
+        unchecked_helper::overwrite(value)
+    }
+}
+
+// Trusted through the `#[pear::assume_pure]` attribute.
+mod assumed_pure {
+    #[pear::assume_pure]
+    pub fn overwrite(value: usize) -> usize {
+        let mut copy = value;
+        let raw = &mut copy as *mut usize;
+        unsafe { *raw = 0 };
+        copy
+    }
+
+    #[pear::scrutinizer_pure]
+    pub fn overwrite_assumed_pure(value: usize) -> usize {
+        overwrite(value)
+    }
+}
+
+// Excluded from the analysis through the `#[pear::ignore]` attribute.
+mod ignored {
+    macro_rules! overwriting {
+        ($name:ident) => {
+            #[pear::ignore]
+            pub fn $name(value: usize) -> usize {
+                let mut copy = value;
+                let raw = &mut copy as *mut usize;
+                unsafe { *raw = 0 };
+                copy
+            }
+        };
+    }
+
+    overwriting!(overwrite);
+
+    #[pear::scrutinizer_pure]
+    pub fn overwrite_ignored(value: usize) -> usize {
+        overwrite(value)
+    }
+}
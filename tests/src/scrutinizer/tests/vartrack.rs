@@ -81,3 +81,20 @@ mod non_leaky_flows {
         println!("{}", variable);
     }
 }
+
+mod attribute_important_args {
+    use std::cell::RefCell;
+
+    // Only the first argument is important, so mutating the second one is not a leak.
+    #[pear::scrutinizer_pure(important = [1])]
+    pub fn mutate_unimportant(sensitive_arg: i32, public_arg: &RefCell<i32>) -> i32 {
+        *public_arg.borrow_mut() = 10;
+        sensitive_arg + 1
+    }
+
+    #[pear::scrutinizer_impure(important = [2])]
+    pub fn mutate_important(sensitive_arg: i32, public_arg: &RefCell<i32>) -> i32 {
+        *public_arg.borrow_mut() = 10;
+        sensitive_arg + 1
+    }
+}
@@ -0,0 +1,7 @@
+// The items of this crate are allowlisted by the `unchecked_` crate name prefix.
+pub fn overwrite(value: usize) -> usize {
+    let mut copy = value;
+    let raw = &mut copy as *mut usize;
+    unsafe { *raw = 0 };
+    copy
+}